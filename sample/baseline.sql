@@ -0,0 +1,11 @@
+-- A database as created by trs before schema migrations were introduced
+CREATE TABLE Channels ( id INTEGER PRIMARY KEY, name TEXT NOT NULL, link TEXT NOT NULL UNIQUE, feed_link TEXT NOT NULL UNIQUE, description TEXT, last_update INTEGER);
+CREATE TABLE Articles ( id INTEGER PRIMARY KEY, channel_id INTEGER NOT NULL, title TEXT NOT NULL, description TEXT, link TEXT NOT NULL UNIQUE, pub_date INTEGER, last_update INTEGER , unread BOOLEAN DEFAULT TRUE, FOREIGN KEY(channel_id) REFERENCES Channels(id) ON DELETE CASCADE );
+
+INSERT INTO Channels VALUES (1, 'Bryce Vandegrift''s Website', 'https://brycev.com/', 'https://brycev.com/rss.xml', 'Updates to Bryce Vandegrift''s blog', 1750000000);
+INSERT INTO Channels VALUES (2, 'ploeh blog', 'https://blog.ploeh.dk', 'https://blog.ploeh.dk/rss.xml', 'danish software design', 1750000100);
+
+INSERT INTO Articles VALUES (1, 1, 'New Domain Name', '<p>It&rsquo;s about time that I got a new domain name</p>', 'https://brycev.com/blog/new-domain-name/', 1747180800, 1750000000, 1);
+INSERT INTO Articles VALUES (2, 1, 'Blogging on Paper', '<p>I&rsquo;ve been wanting to post more articles</p>', 'https://brycev.com/blog/blogging-on-paper/', 1743379200, 1750000000, 0);
+INSERT INTO Articles VALUES (3, 2, 'Song recommendations from C# combinators', '<p>LINQ-style composition</p>', 'https://blog.ploeh.dk/2025/06/16/song-recommendations-from-c-combinators/', 1750059660, 1750000100, 1);
+INSERT INTO Articles VALUES (4, 2, 'Song recommendations from combinators', '<p>Interleaving impure actions</p>', 'https://blog.ploeh.dk/2025/06/09/song-recommendations-from-combinators/', 1749454860, 1750000100, 0);
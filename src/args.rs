@@ -1,8 +1,29 @@
+use std::path::PathBuf;
+
 use argh::FromArgs;
+use serde::{Deserialize, Serialize};
+
+use crate::{output::OutputFormat, schedule};
+
+/// Downloads run at the same time by refresh and import
+pub const DEFAULT_JOBS: usize = 8;
 
 /// Tiny RSS reader
 #[derive(FromArgs, PartialEq, Debug)]
 pub struct TrsArgs {
+    /// name of the subscription set to use (default: "default")
+    #[argh(option, short = 'p')]
+    pub profile: Option<String>,
+
+    /// path to the database file, takes precedence over --profile and $TRS_DB
+    #[argh(option)]
+    pub db: Option<PathBuf>,
+
+    /// how list and articles print: text, json, jsonl, csv, tsv or table
+    /// (default: text)
+    #[argh(option, default = "OutputFormat::Text")]
+    pub format: OutputFormat,
+
     #[argh(subcommand)]
     pub sub_command: TrsSubCommand,
 }
@@ -15,11 +36,25 @@ pub enum TrsSubCommand {
     GetArticles(GetArticlesArgs),
     RemoveChannel(RemoveChannelArgs),
     MarkRead(MarkReadArgs),
+    Star(StarArgs),
+    ShowArticle(ShowArticleArgs),
+    Search(SearchArgs),
+    SetInterval(SetIntervalArgs),
+    Retention(RetentionArgs),
+    Prune(PruneArgs),
+    Folder(FolderArgs),
+    MoveChannel(MoveChannelArgs),
+    Refresh(RefreshArgs),
+    Import(ImportArgs),
+    Export(ExportArgs),
     Ui(UiArgs),
+    Daemon(DaemonArgs),
+    Serve(ServeArgs),
+    Sync(SyncArgs),
 }
 
 /// Add a new RSS channel
-#[derive(FromArgs, PartialEq, Debug)]
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
 #[argh(subcommand, name = "add")]
 pub struct AddChannelArgs {
     /// link to RSS channel
@@ -28,7 +63,7 @@ pub struct AddChannelArgs {
 }
 
 /// List RSS channels
-#[derive(FromArgs, PartialEq, Debug)]
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
 #[argh(subcommand, name = "list")]
 pub struct ListChannelArgs {
     /// limit the number of channels to list
@@ -37,7 +72,7 @@ pub struct ListChannelArgs {
 }
 
 /// Get articles
-#[derive(FromArgs, PartialEq, Debug)]
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
 #[argh(subcommand, name = "articles")]
 pub struct GetArticlesArgs {
     /// id of the channel to get articles from
@@ -47,10 +82,14 @@ pub struct GetArticlesArgs {
     /// only get unread articles
     #[argh(switch)]
     pub unread: bool,
+
+    /// only get starred articles
+    #[argh(switch)]
+    pub starred: bool,
 }
 
 /// Mark article as read/unread
-#[derive(FromArgs, PartialEq, Debug)]
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
 #[argh(subcommand, name = "read")]
 pub struct MarkReadArgs {
     /// id of the article to mark read/unread
@@ -62,8 +101,43 @@ pub struct MarkReadArgs {
     pub unread: bool,
 }
 
+/// Star an article to keep it, or unstar it
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "star")]
+pub struct StarArgs {
+    /// id of the article to star/unstar
+    #[argh(option)]
+    pub id: u32,
+
+    /// remove the star
+    #[argh(switch)]
+    pub unstar: bool,
+}
+
+/// Print the full text of an article
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "show")]
+pub struct ShowArticleArgs {
+    /// id of the article to show
+    #[argh(option)]
+    pub id: u32,
+}
+
+/// Search the title and text of articles, best matches first
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "search")]
+pub struct SearchArgs {
+    /// words the articles must contain
+    #[argh(positional, greedy)]
+    pub query: Vec<String>,
+
+    /// limit the number of articles to list
+    #[argh(option, default = "20")]
+    pub limit: u32,
+}
+
 /// Delete an RSS channel
-#[derive(FromArgs, PartialEq, Debug)]
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
 #[argh(subcommand, name = "remove")]
 pub struct RemoveChannelArgs {
     /// delete the channel with this id
@@ -71,6 +145,163 @@ pub struct RemoveChannelArgs {
     pub id: u32,
 }
 
+/// Set how often the UI or the daemon refreshes a channel in the background
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "interval")]
+pub struct SetIntervalArgs {
+    /// id of the channel
+    #[argh(option)]
+    pub id: u32,
+
+    /// minutes between refreshes
+    #[argh(option)]
+    pub minutes: Option<u32>,
+
+    /// go back to the default interval and the feed's own hints
+    #[argh(switch)]
+    pub default: bool,
+}
+
+/// Set how long articles are kept, for every channel or only one. Starred
+/// and unread articles are always kept. Shows the settings without options.
+#[derive(FromArgs, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[argh(subcommand, name = "retention")]
+pub struct RetentionArgs {
+    /// only change the channel with this id
+    #[argh(option)]
+    pub id: Option<u32>,
+
+    /// keep the newest N articles, 0 for no limit
+    #[argh(option)]
+    pub keep: Option<u32>,
+
+    /// drop read articles older than D days, 0 for no limit
+    #[argh(option)]
+    pub days: Option<u32>,
+
+    /// make the channel given with --id follow the global settings again
+    #[argh(switch)]
+    pub default: bool,
+
+    /// prune after every refresh (global only)
+    #[argh(option)]
+    pub after_refresh: Option<bool>,
+}
+
+/// Delete the articles that are past their retention
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "prune")]
+pub struct PruneArgs {
+    /// only report what would be deleted
+    #[argh(switch)]
+    pub dry_run: bool,
+
+    /// compact the database file afterwards
+    #[argh(switch)]
+    pub vacuum: bool,
+}
+
+/// Manage the folders channels are grouped in
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "folder")]
+pub struct FolderArgs {
+    #[argh(subcommand)]
+    pub command: FolderCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand)]
+pub enum FolderCommand {
+    List(ListFoldersArgs),
+    Add(AddFolderArgs),
+    Rename(RenameFolderArgs),
+    Remove(RemoveFolderArgs),
+}
+
+/// List folders
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "list")]
+pub struct ListFoldersArgs {}
+
+/// Create a folder
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "add")]
+pub struct AddFolderArgs {
+    /// name of the folder
+    #[argh(option)]
+    pub name: String,
+}
+
+/// Rename a folder
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "rename")]
+pub struct RenameFolderArgs {
+    /// id of the folder
+    #[argh(option)]
+    pub id: u32,
+
+    /// new name of the folder
+    #[argh(option)]
+    pub name: String,
+}
+
+/// Delete a folder, its channels are kept
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "remove")]
+pub struct RemoveFolderArgs {
+    /// id of the folder
+    #[argh(option)]
+    pub id: u32,
+}
+
+/// Move a channel into a folder, or out of its folder
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "move")]
+pub struct MoveChannelArgs {
+    /// id of the channel
+    #[argh(option)]
+    pub id: u32,
+
+    /// id of the folder, leave out to take the channel out of its folder
+    #[argh(option)]
+    pub folder: Option<u32>,
+}
+
+/// Fetch new articles for subscribed channels
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "refresh")]
+pub struct RefreshArgs {
+    /// only refresh the channel with this id, can be repeated
+    #[argh(option)]
+    pub id: Vec<u32>,
+
+    /// maximum number of channels to download at the same time
+    #[argh(option, short = 'j', default = "DEFAULT_JOBS")]
+    pub jobs: usize,
+}
+
+/// Subscribe to the feeds listed in an OPML file
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "import")]
+pub struct ImportArgs {
+    /// path to the OPML file
+    #[argh(option)]
+    pub opml: PathBuf,
+
+    /// maximum number of feeds to download at the same time
+    #[argh(option, short = 'j', default = "DEFAULT_JOBS")]
+    pub jobs: usize,
+}
+
+/// Write subscriptions to an OPML file
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "export")]
+pub struct ExportArgs {
+    /// path to the OPML file
+    #[argh(option)]
+    pub opml: PathBuf,
+}
+
 /// Open UI
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand, name = "ui")]
@@ -78,6 +309,58 @@ pub struct UiArgs {
     /// enable debug window
     #[argh(switch)]
     pub debug: bool,
+
+    /// minutes between background refreshes of channels without their own
+    /// interval, 0 turns them off
+    #[argh(option, default = "schedule::DEFAULT_REFRESH_INTERVAL")]
+    pub refresh_interval: u32,
+}
+
+/// Serve the database to the other commands over a local socket
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "daemon")]
+pub struct DaemonArgs {
+    /// minutes between refreshes of channels without their own interval, 0
+    /// turns them off
+    #[argh(option, default = "schedule::DEFAULT_REFRESH_INTERVAL")]
+    pub refresh_interval: u32,
+}
+
+/// Serve a JSON API over HTTP
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "serve")]
+pub struct ServeArgs {
+    /// address to listen on (default: 127.0.0.1:8080)
+    #[argh(option, default = "String::from(\"127.0.0.1:8080\")")]
+    pub bind: String,
+
+    /// token clients have to send, defaults to $TRS_TOKEN. Clients that log
+    /// in with a user name and password use it as the password.
+    #[argh(option)]
+    pub token: Option<String>,
+
+    /// user name for clients that log in (default: trs)
+    #[argh(option, default = "String::from(\"trs\")")]
+    pub user: String,
+}
+
+/// Sync subscriptions and read state with a reader serving the Google
+/// Reader API, such as Miniflux or FreshRSS
+#[derive(FromArgs, PartialEq, Debug, Serialize, Deserialize)]
+#[argh(subcommand, name = "sync")]
+pub struct SyncArgs {
+    /// address of the reader, remembered after the first sync
+    #[argh(option, from_str_fn(valid_url))]
+    pub url: Option<String>,
+
+    /// user name on the reader, remembered after the first sync
+    #[argh(option)]
+    pub user: Option<String>,
+
+    /// password or API password on the reader, defaults to
+    /// $TRS_SYNC_PASSWORD. It is never stored.
+    #[argh(option)]
+    pub password: Option<String>,
 }
 
 pub fn valid_url(url: &str) -> Result<String, String> {
@@ -1,62 +1,371 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
 use crate::{
-    args::{self, AddChannelArgs, ListChannelArgs, RemoveChannelArgs},
+    args::{
+        self, AddChannelArgs, ExportArgs, FolderArgs, FolderCommand, ImportArgs, ListChannelArgs,
+        MoveChannelArgs, PruneArgs, RefreshArgs, RemoveChannelArgs, RetentionArgs, SearchArgs,
+        SetIntervalArgs, ShowArticleArgs, StarArgs, SyncArgs,
+    },
+    discovery,
     error::TrsError,
-    parser,
-    persistence::{Db, RssChannelD},
+    opml::{self, OpmlFeed},
+    parser::{self, DocumentKind, RssChannel},
+    persistence::{
+        ArticleIds, ArticlePage, ArticleQuery, Db, Folder, PruneReport, Retention,
+        RetentionSettings, RssArticleD, RssChannelD, SearchHit, SyncReport,
+    },
+    sync::{self, SyncResult},
 };
 
 pub struct TrsEnv {
-    name: String,
+    db_path: PathBuf,
     db: Db,
-    http_client: reqwest::blocking::Client,
+    http_client: reqwest::Client,
 }
 
 impl Clone for TrsEnv {
     fn clone(&self) -> Self {
-        let name = self.name.clone();
-        TrsEnv::new(&name).expect("Failed to clone TrsEnv")
+        TrsEnv::new(&self.db_path).expect("Failed to clone TrsEnv")
     }
 }
 
 impl TrsEnv {
-    pub fn new(instance_name: &str) -> Result<Self, TrsError> {
-        let db = Db::create(instance_name)?;
-        let http_client = reqwest::blocking::Client::builder()
+    pub fn new(db_path: &Path) -> Result<Self, TrsError> {
+        let db = Db::create(db_path)?;
+        let http_client = reqwest::Client::builder()
             .user_agent("cool-mist/trs")
             .build()
             .map_err(|e| TrsError::ReqwestError(e, "Failed to create HTTP client".to_string()))?;
         Ok(TrsEnv {
-            name: instance_name.to_string(),
+            db_path: db_path.to_path_buf(),
             db,
             http_client,
         })
     }
 }
 
-pub fn add_channel(ctx: &TrsEnv, args: &AddChannelArgs) -> Result<RssChannelD, TrsError> {
-    let rss = ctx.http_client.get(&args.link).send().map_err(|e| {
-        TrsError::ReqwestError(
-            e,
-            "Unable to download provided RSS channel link".to_string(),
-        )
-    })?;
+pub async fn add_channel(ctx: &TrsEnv, args: &AddChannelArgs) -> Result<RssChannelD, TrsError> {
+    let document = download(ctx, &args.link).await?;
+    if DocumentKind::detect(document.content_type.as_deref(), &document.bytes) != DocumentKind::Html
+    {
+        return store_feed(ctx, &args.link, &document).map(|(channel, _)| channel);
+    }
+
+    // Not a feed, look for the feeds that this web page links to
+    let html = String::from_utf8_lossy(&document.bytes);
+    let mut candidates = discovery::discover_feeds(&args.link, &html);
+    match candidates.len() {
+        0 => {}
+        1 => {
+            let feed_link = candidates.remove(0);
+            let document = download(ctx, &feed_link).await?;
+            return store_feed(ctx, &feed_link, &document).map(|(channel, _)| channel);
+        }
+        _ => return Err(TrsError::MultipleFeeds(candidates)),
+    }
+
+    for feed_link in discovery::common_feed_urls(&args.link) {
+        let Ok(document) = download(ctx, &feed_link).await else {
+            continue;
+        };
+
+        if let Ok((channel, _)) = store_feed(ctx, &feed_link, &document) {
+            return Ok(channel);
+        }
+    }
+
+    Err(TrsError::Error(format!("No feed found at {}", args.link)))
+}
+
+struct Download {
+    content_type: Option<String>,
+    bytes: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+async fn download(ctx: &TrsEnv, link: &str) -> Result<Download, TrsError> {
+    download_if_modified(ctx, link, None, None)
+        .await?
+        .ok_or_else(|| TrsError::Error(format!("Unexpected 304 Not Modified from {}", link)))
+}
+
+/// Conditional GET, returns `None` when the server says the document hasn't
+/// changed since it sent us `etag`/`last_modified`
+async fn download_if_modified(
+    ctx: &TrsEnv,
+    link: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<Option<Download>, TrsError> {
+    let mut request = ctx.http_client.get(link);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let rss = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            TrsError::ReqwestError(
+                e,
+                "Unable to download provided RSS channel link".to_string(),
+            )
+        })?;
+
+    if rss.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let header = |name| {
+        rss.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_type = header(reqwest::header::CONTENT_TYPE);
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
 
     // TODO: Streaming read
-    let bytes = rss.bytes().map_err(|e| {
+    let bytes = rss.bytes().await.map_err(|e| {
         TrsError::ReqwestError(e, "Unable to read bytes from RSS response".to_string())
     })?;
 
-    let xml_source_stream = xml::ParserConfig::new()
-        .ignore_invalid_encoding_declarations(true)
-        .create_reader(&bytes[..]);
-    let channel = parser::parse_rss_channel(xml_source_stream)?;
-    ctx.db.add_channel(&args.link, &channel)
+    Ok(Some(Download {
+        content_type,
+        bytes: bytes.to_vec(),
+        etag,
+        last_modified,
+    }))
+}
+
+fn store_feed(
+    ctx: &TrsEnv,
+    feed_link: &str,
+    document: &Download,
+) -> Result<(RssChannelD, SyncReport), TrsError> {
+    let feed = parse_download(feed_link, document)?;
+    let (channel, report) = ctx.db.sync_channel(feed_link, &feed)?;
+    ctx.db.set_channel_cache_headers(
+        channel.id,
+        document.etag.as_deref(),
+        document.last_modified.as_deref(),
+    )?;
+    Ok((channel, report))
+}
+
+/// Feeds without a link of their own, which JSON Feed allows, link to where
+/// they were downloaded from
+fn parse_download(feed_link: &str, document: &Download) -> Result<RssChannel, TrsError> {
+    let mut feed = parser::parse_feed(document.content_type.as_deref(), &document.bytes)?;
+    if feed.link.is_empty() {
+        feed.link = feed_link.to_string();
+    }
+    Ok(feed)
+}
+
+/// Store a new download of a subscribed channel
+fn refresh_feed(
+    ctx: &TrsEnv,
+    channel: &RssChannelD,
+    document: &Download,
+) -> Result<SyncReport, TrsError> {
+    let feed = parse_download(&channel.feed_link, document)?;
+    let (_, report) = ctx.db.refresh_channel(channel.id, &feed)?;
+    ctx.db.set_channel_cache_headers(
+        channel.id,
+        document.etag.as_deref(),
+        document.last_modified.as_deref(),
+    )?;
+    Ok(report)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RefreshResult {
+    pub channel_id: i64,
+    pub title: String,
+    pub result: Result<SyncReport, TrsError>,
+}
+
+/// Download every subscribed channel (or the ones asked for), at most
+/// `args.jobs` at a time, and store what changed
+pub async fn refresh(ctx: &TrsEnv, args: &RefreshArgs) -> Result<Vec<RefreshResult>, TrsError> {
+    let mut channels = ctx.db.list_channels(u32::MAX)?;
+    let mut results = Vec::new();
+    if !args.id.is_empty() {
+        channels.retain(|channel| args.id.contains(&(channel.id as u32)));
+        for id in &args.id {
+            if !channels.iter().any(|channel| channel.id == *id as i64) {
+                results.push(RefreshResult {
+                    channel_id: *id as i64,
+                    title: String::new(),
+                    result: Err(TrsError::Error(format!("No channel with id {}", id))),
+                });
+            }
+        }
+    }
+
+    let mut downloads = futures::stream::iter(channels.into_iter().map(|channel| async move {
+        let document = download_if_modified(
+            ctx,
+            &channel.feed_link,
+            channel.etag.as_deref(),
+            channel.last_modified.as_deref(),
+        )
+        .await;
+        (channel, document)
+    }))
+    .buffer_unordered(args.jobs.max(1));
+
+    // Downloads run concurrently, the database is only written from here
+    while let Some((channel, document)) = downloads.next().await {
+        // Failures count too, so a broken feed isn't retried on every tick
+        ctx.db
+            .set_channel_fetched(channel.id, OffsetDateTime::now_utc())?;
+        let result = match document {
+            Ok(Some(document)) => refresh_feed(ctx, &channel, &document),
+            Ok(None) => Ok(SyncReport {
+                not_modified: true,
+                ..Default::default()
+            }),
+            Err(e) => Err(e),
+        };
+        results.push(RefreshResult {
+            channel_id: channel.id,
+            title: channel.title,
+            result,
+        });
+    }
+
+    if ctx.db.retention_settings()?.after_refresh {
+        ctx.db.prune(false, OffsetDateTime::now_utc())?;
+    }
+
+    Ok(results)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportResult {
+    pub feed: OpmlFeed,
+    pub result: Result<RssChannelD, TrsError>,
+}
+
+/// Read the feeds listed in an OPML file
+pub fn read_opml(args: &ImportArgs) -> Result<Vec<OpmlFeed>, TrsError> {
+    let file = File::open(&args.opml)
+        .map_err(|e| TrsError::IoError(e, format!("Unable to open {}", args.opml.display())))?;
+    opml::parse_opml(BufReader::new(file))
+}
+
+/// Subscribe to every feed, at most `jobs` downloads at a time, and put them
+/// in their folders. A feed that fails doesn't stop the others from being
+/// imported.
+pub async fn import_feeds(
+    ctx: &TrsEnv,
+    feeds: Vec<OpmlFeed>,
+    jobs: usize,
+) -> Result<Vec<ImportResult>, TrsError> {
+    let mut downloads = futures::stream::iter(feeds.into_iter().map(|feed| async move {
+        let document = download(ctx, &feed.xml_url).await;
+        (feed, document)
+    }))
+    .buffer_unordered(jobs.max(1));
+
+    let mut results = Vec::new();
+    while let Some((feed, document)) = downloads.next().await {
+        let result = document
+            .and_then(|document| store_feed(ctx, &feed.xml_url, &document))
+            .and_then(|(mut channel, _)| {
+                if let Some(folder) = &feed.folder {
+                    let folder = ctx.db.folder_named(folder)?;
+                    ctx.db.set_channel_folder(channel.id, Some(folder.id))?;
+                    channel.folder_id = Some(folder.id);
+                }
+                Ok(channel)
+            });
+        results.push(ImportResult { feed, result });
+    }
+
+    Ok(results)
+}
+
+/// Write `channels` to an OPML file, grouped in their `folders`
+pub fn export_opml(
+    channels: Vec<RssChannelD>,
+    folders: &[Folder],
+    args: &ExportArgs,
+) -> Result<usize, TrsError> {
+    let feeds = channels
+        .into_iter()
+        .map(|channel| OpmlFeed {
+            title: channel.title,
+            xml_url: channel.feed_link,
+            html_url: Some(channel.link),
+            folder: folders
+                .iter()
+                .find(|folder| Some(folder.id) == channel.folder_id)
+                .map(|folder| folder.name.clone()),
+        })
+        .collect::<Vec<_>>();
+
+    let file = File::create(&args.opml)
+        .map_err(|e| TrsError::IoError(e, format!("Unable to create {}", args.opml.display())))?;
+    opml::write_opml(BufWriter::new(file), &feeds)?;
+    Ok(feeds.len())
+}
+
+pub async fn sync(ctx: &TrsEnv, args: &SyncArgs) -> Result<SyncResult, TrsError> {
+    sync::sync(&ctx.db, &ctx.http_client, args).await
 }
 
 pub fn list_channels(ctx: &TrsEnv, args: &ListChannelArgs) -> Result<Vec<RssChannelD>, TrsError> {
     ctx.db.list_channels(args.limit.unwrap_or(u32::MAX))
 }
 
+pub fn get_channel(ctx: &TrsEnv, id: i64) -> Result<RssChannelD, TrsError> {
+    ctx.db.get_channel(id)
+}
+
+pub fn channels_by_id(ctx: &TrsEnv, ids: &[i64]) -> Result<Vec<RssChannelD>, TrsError> {
+    ctx.db.channels_by_id(ids)
+}
+
+pub fn list_folders(ctx: &TrsEnv) -> Result<Vec<Folder>, TrsError> {
+    ctx.db.list_folders()
+}
+
+/// Apply a folder change, and list the folders as they are after it
+pub fn folder(ctx: &TrsEnv, args: &FolderArgs) -> Result<Vec<Folder>, TrsError> {
+    match &args.command {
+        FolderCommand::List(_) => {}
+        FolderCommand::Add(args) => {
+            ctx.db.add_folder(&args.name)?;
+        }
+        FolderCommand::Rename(args) => ctx.db.rename_folder(args.id as i64, &args.name)?,
+        FolderCommand::Remove(args) => ctx.db.remove_folder(args.id as i64)?,
+    }
+
+    ctx.db.list_folders()
+}
+
+pub fn move_channel(ctx: &TrsEnv, args: &MoveChannelArgs) -> Result<(), TrsError> {
+    ctx.db
+        .set_channel_folder(args.id as i64, args.folder.map(i64::from))
+}
+
 pub fn remove_channel(ctx: &TrsEnv, args: &RemoveChannelArgs) -> Result<(), TrsError> {
     ctx.db.remove_channel(args.id).map(|_| ())
 }
@@ -70,8 +379,147 @@ pub fn mark_read(ctx: &TrsEnv, args: &args::MarkReadArgs) -> Result<(), TrsError
     Ok(())
 }
 
+pub fn star(ctx: &TrsEnv, args: &StarArgs) -> Result<(), TrsError> {
+    match args.unstar {
+        true => ctx.db.unstar_article(args.id as i64)?,
+        false => ctx.db.star_article(args.id as i64)?,
+    };
+
+    Ok(())
+}
+
+pub fn set_refresh_interval(ctx: &TrsEnv, args: &SetIntervalArgs) -> Result<(), TrsError> {
+    let minutes = match (args.minutes, args.default) {
+        (Some(0), false) => {
+            return Err(TrsError::Error(
+                "The interval must be at least a minute".to_string(),
+            ))
+        }
+        (Some(minutes), false) => Some(minutes),
+        (None, true) => None,
+        _ => {
+            return Err(TrsError::Error(
+                "Pass either --minutes or --default".to_string(),
+            ))
+        }
+    };
+
+    ctx.db.set_channel_refresh_interval(args.id as i64, minutes)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetentionResult {
+    pub settings: RetentionSettings,
+    /// The settings of the channel given with `--id`
+    pub channel: Option<Retention>,
+}
+
+/// Apply the options that were given and return the settings as they are now
+pub fn retention(ctx: &TrsEnv, args: &RetentionArgs) -> Result<RetentionResult, TrsError> {
+    // 0 lifts a limit
+    let limit = |value: u32| (value > 0).then_some(value);
+    let mut settings = ctx.db.retention_settings()?;
+    let Some(id) = args.id else {
+        if args.default {
+            return Err(TrsError::Error(
+                "--default needs a channel --id".to_string(),
+            ));
+        }
+        if let Some(keep) = args.keep {
+            settings.defaults.keep_articles = limit(keep);
+        }
+        if let Some(days) = args.days {
+            settings.defaults.keep_days = limit(days);
+        }
+        if let Some(after_refresh) = args.after_refresh {
+            settings.after_refresh = after_refresh;
+        }
+        ctx.db.set_retention_settings(&settings)?;
+        return Ok(RetentionResult {
+            settings,
+            channel: None,
+        });
+    };
+
+    if args.after_refresh.is_some() {
+        return Err(TrsError::Error(
+            "--after-refresh applies to every channel, leave out --id".to_string(),
+        ));
+    }
+    let id = id as i64;
+    let mut channel = ctx.db.channel_retention(id)?;
+    match (args.default, args.keep, args.days) {
+        (true, None, None) => channel = Retention::default(),
+        (true, _, _) => {
+            return Err(TrsError::Error(
+                "Pass either --default or --keep and --days".to_string(),
+            ))
+        }
+        (false, keep, days) => {
+            // A channel's 0 overrides the global limit rather than falling
+            // back to it
+            if let Some(keep) = keep {
+                channel.keep_articles = Some(keep);
+            }
+            if let Some(days) = days {
+                channel.keep_days = Some(days);
+            }
+        }
+    }
+    ctx.db.set_channel_retention(id, &channel)?;
+
+    Ok(RetentionResult {
+        settings,
+        channel: Some(channel),
+    })
+}
+
+pub fn prune(ctx: &TrsEnv, args: &PruneArgs) -> Result<Vec<PruneReport>, TrsError> {
+    let reports = ctx.db.prune(args.dry_run, OffsetDateTime::now_utc())?;
+    if args.vacuum && !args.dry_run {
+        ctx.db.vacuum()?;
+    }
+
+    Ok(reports)
+}
+
+pub fn get_article(ctx: &TrsEnv, args: &ShowArticleArgs) -> Result<RssArticleD, TrsError> {
+    ctx.db.get_article_by_id(args.id as i64)
+}
+
+pub fn search(ctx: &TrsEnv, args: &SearchArgs) -> Result<Vec<SearchHit>, TrsError> {
+    ctx.db.search_articles(&args.query.join(" "), args.limit)
+}
+
+pub fn query_articles(ctx: &TrsEnv, query: &ArticleQuery) -> Result<ArticlePage, TrsError> {
+    ctx.db.query_articles(query)
+}
+
+pub fn articles_by_id(ctx: &TrsEnv, ids: &ArticleIds, limit: u32) -> Result<ArticlePage, TrsError> {
+    ctx.db.articles_by_id(ids, limit)
+}
+
+pub fn unread_article_ids(ctx: &TrsEnv) -> Result<Vec<i64>, TrsError> {
+    ctx.db.unread_article_ids()
+}
+
+pub fn starred_article_ids(ctx: &TrsEnv) -> Result<Vec<i64>, TrsError> {
+    ctx.db.starred_article_ids()
+}
+
+/// Mark everything published up to `before` (a unix timestamp) read
+pub fn mark_all_read(
+    ctx: &TrsEnv,
+    channel_id: Option<i64>,
+    before: i64,
+) -> Result<usize, TrsError> {
+    let before = OffsetDateTime::from_unix_timestamp(before)
+        .map_err(|e| TrsError::Error(format!("Invalid timestamp {}: {}", before, e)))?;
+    ctx.db.mark_read_before(channel_id, before)
+}
+
 pub fn get_articles_by_channel(
-    ctx: &mut TrsEnv,
+    ctx: &TrsEnv,
     args: &args::GetArticlesArgs,
 ) -> Result<Vec<RssChannelD>, TrsError> {
     let mut channels = ctx.db.list_channels(u32::MAX)?;
@@ -83,10 +531,10 @@ pub fn get_articles_by_channel(
         }
     });
 
-    if args.unread {
-        for channel in &mut channels {
-            channel.articles.retain(|article| article.unread);
-        }
+    for channel in &mut channels {
+        channel.articles.retain(|article| {
+            (!args.unread || article.unread) && (!args.starred || article.starred)
+        });
     }
 
     Ok(channels)
@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use time::OffsetDateTime;
+use tokio::{
+    io::BufReader,
+    net::{UnixListener, UnixStream},
+    signal::unix::{signal, SignalKind},
+};
+
+use crate::{
+    args::{self, DaemonArgs},
+    commands::TrsEnv,
+    error::{Result, TrsError},
+    ipc::{self, Request, Response, Worker},
+    schedule,
+};
+
+/// Own the database and serve requests for it until interrupted
+pub async fn run(args: &DaemonArgs, db_path: &Path) -> Result<()> {
+    let socket = ipc::socket_path(db_path);
+    if UnixStream::connect(&socket).await.is_ok() {
+        return Err(TrsError::Error(format!(
+            "A daemon is already listening on {}",
+            socket.display()
+        )));
+    }
+
+    // Left behind by a daemon that didn't get to clean up
+    if socket.exists() {
+        std::fs::remove_file(&socket)
+            .map_err(|e| TrsError::IoError(e, format!("Unable to remove {}", socket.display())))?;
+    }
+
+    let ctx = TrsEnv::new(db_path)?;
+    let listener = UnixListener::bind(&socket)
+        .map_err(|e| TrsError::IoError(e, format!("Unable to listen on {}", socket.display())))?;
+    let worker = Worker::start(ctx);
+    if args.refresh_interval > 0 {
+        tokio::spawn(refresh_due_channels(worker.clone(), args.refresh_interval));
+    }
+
+    println!("Listening on {}", socket.display());
+    let result = tokio::select! {
+        result = accept(listener, worker) => result,
+        result = shutdown() => result,
+    };
+
+    std::fs::remove_file(&socket)
+        .map_err(|e| TrsError::IoError(e, format!("Unable to remove {}", socket.display())))?;
+    result
+}
+
+async fn accept(listener: UnixListener, worker: Worker) -> Result<()> {
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| TrsError::IoError(e, "Unable to accept a client".to_string()))?;
+        tokio::spawn(serve(stream, worker.clone()));
+    }
+}
+
+/// Answer one client's requests until it hangs up
+async fn serve(stream: UnixStream, worker: Worker) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    loop {
+        let response = match ipc::read_message(&mut reader).await {
+            Ok(Some(request)) => worker.call(request).await.unwrap_or_else(Response::Error),
+            Ok(None) => break,
+            Err(e) => Response::Error(e),
+        };
+
+        if ipc::write_message(&mut writer, &response).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Wait for ctrl-c or SIGTERM
+pub async fn shutdown() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())
+        .map_err(|e| TrsError::IoError(e, "Unable to listen for SIGTERM".to_string()))?;
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+
+    Ok(())
+}
+
+async fn refresh_due_channels(worker: Worker, default_minutes: u32) {
+    let mut check = tokio::time::interval(schedule::CHECK_INTERVAL);
+    loop {
+        check.tick().await;
+        let Ok(Response::Channels(channels)) = worker
+            .call(Request::ListChannels(args::ListChannelArgs { limit: None }))
+            .await
+        else {
+            continue;
+        };
+
+        let due = schedule::due_channels(&channels, OffsetDateTime::now_utc(), default_minutes);
+        if due.is_empty() {
+            continue;
+        }
+
+        let refresh = Request::Refresh(args::RefreshArgs {
+            id: due,
+            jobs: args::DEFAULT_JOBS,
+        });
+        match worker.call(refresh).await {
+            Ok(Response::Refreshed(results)) => {
+                let failed = results.iter().filter(|r| r.result.is_err()).count();
+                println!(
+                    "Refreshed {} channels, {} failed",
+                    results.len() - failed,
+                    failed
+                );
+            }
+            Err(e) => eprintln!("Unable to refresh: {}", e),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::Session;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn requests_go_through_the_daemon() {
+        let db_path =
+            std::env::temp_dir().join(format!("trs-daemon-test-{}.db", std::process::id()));
+        let socket = ipc::socket_path(&db_path);
+        _ = std::fs::remove_file(&socket);
+
+        let listener = UnixListener::bind(&socket).unwrap();
+        let worker = Worker::start(TrsEnv::new(&db_path).unwrap());
+        tokio::spawn(accept(listener, worker));
+
+        let mut session = Session::open(&db_path).await.unwrap();
+        assert!(session.is_daemon());
+        let channels = session
+            .list_channels(args::ListChannelArgs { limit: None })
+            .await
+            .unwrap();
+        assert!(channels.is_empty());
+
+        // Errors make it back to the client
+        let e = session
+            .execute(Request::SetInterval(args::SetIntervalArgs {
+                id: 7,
+                minutes: Some(5),
+                default: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "No channel with id 7");
+
+        drop(session);
+        std::fs::remove_file(&socket).unwrap();
+        std::fs::remove_file(&db_path).unwrap();
+    }
+}
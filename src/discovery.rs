@@ -0,0 +1,113 @@
+use reqwest::Url;
+
+use crate::html::{self, HtmlToken};
+
+/// Paths tried, in order, when a page doesn't advertise its feeds
+pub const COMMON_FEED_PATHS: [&str; 7] = [
+    "/feed",
+    "/feed/",
+    "/rss.xml",
+    "/atom.xml",
+    "/feed.xml",
+    "/index.xml",
+    "/feed.json",
+];
+
+const FEED_TYPES: [&str; 4] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/json",
+];
+
+/// Find feeds advertised by an HTML page through
+/// `<link rel="alternate" type="application/rss+xml" href="...">` tags.
+/// Relative links are resolved against `page_url`.
+pub fn discover_feeds(page_url: &str, html: &str) -> Vec<String> {
+    let base = Url::parse(page_url).ok();
+    let mut feeds = Vec::new();
+    for tag in html::tokenize(html) {
+        if !matches!(&tag, HtmlToken::Start { name, .. } if name == "link") {
+            continue;
+        }
+
+        let attribute = |name: &str| tag.attribute(name);
+
+        let is_alternate = attribute("rel")
+            .map(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case("alternate"))
+            })
+            .unwrap_or(false);
+        let is_feed = attribute("type")
+            .map(|t| FEED_TYPES.contains(&t.trim().to_ascii_lowercase().as_str()))
+            .unwrap_or(false);
+        let Some(href) = attribute("href") else {
+            continue;
+        };
+
+        if !is_alternate || !is_feed {
+            continue;
+        }
+
+        let href = href.trim();
+        let resolved = match &base {
+            Some(base) => base.join(href).map(|url| url.to_string()).ok(),
+            None => Url::parse(href).map(|url| url.to_string()).ok(),
+        };
+
+        if let Some(url) = resolved {
+            if !feeds.contains(&url) {
+                feeds.push(url);
+            }
+        }
+    }
+
+    feeds
+}
+
+/// Well known feed locations on the same host as `page_url`
+pub fn common_feed_urls(page_url: &str) -> Vec<String> {
+    let Ok(base) = Url::parse(page_url) else {
+        return Vec::new();
+    };
+
+    COMMON_FEED_PATHS
+        .iter()
+        .filter_map(|path| base.join(path).ok())
+        .map(|url| url.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovers_advertised_feeds() {
+        let html = include_str!("../sample/blog.html");
+        let feeds = discover_feeds("https://blog.example.com/posts/", html);
+        assert_eq!(
+            feeds,
+            vec![
+                "https://blog.example.com/index.xml".to_string(),
+                "https://blog.example.com/atom.xml?lang=en&full=1".to_string(),
+                "https://feeds.example.net/blog-comments".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn discovers_single_feed() {
+        let html = include_str!("../sample/blog_single.html");
+        let feeds = discover_feeds("https://notes.example.org/", html);
+        assert_eq!(feeds, vec!["https://notes.example.org/rss/".to_string()]);
+    }
+
+    #[test]
+    fn common_paths_are_resolved_from_the_host() {
+        let urls = common_feed_urls("https://notes.example.org/some/page.html");
+        assert_eq!(urls[0], "https://notes.example.org/feed");
+        assert_eq!(urls.len(), COMMON_FEED_PATHS.len());
+    }
+}
@@ -1,14 +1,22 @@
 use std::fmt::Display;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 pub type Result<T> = std::result::Result<T, TrsError>;
 
 #[derive(Debug)]
 pub enum TrsError {
     Error(String),
+    /// The channel or article asked for doesn't exist
+    NotFound(String),
+    MultipleFeeds(Vec<String>),
     TuiError(std::io::Error),
     XmlRsError(xml::reader::Error, String),
+    XmlRsWriterError(xml::writer::Error, String),
+    IoError(std::io::Error, String),
     SqlError(rusqlite::Error, String),
     ReqwestError(reqwest::Error, String),
+    SerdeJsonError(serde_json::Error, String),
 }
 
 impl From<rusqlite::Error> for TrsError {
@@ -29,12 +37,33 @@ impl Display for TrsError {
             f,
             "{}",
             match self {
-                TrsError::Error(msg) => format!("{}", msg),
+                TrsError::Error(msg) => msg.to_string(),
+                TrsError::NotFound(msg) => msg.to_string(),
+                TrsError::MultipleFeeds(links) => format!(
+                    "Found several feeds, add one of them instead: {}",
+                    links.join(", ")
+                ),
                 TrsError::TuiError(err) => format!("TUI Error: {}", err),
                 TrsError::XmlRsError(err, msg) => format!("XML Rs Error: {} {}", msg, err),
+                TrsError::XmlRsWriterError(err, msg) => format!("XML Rs Error: {} {}", msg, err),
+                TrsError::IoError(err, msg) => format!("IO Error: {} - {}", err, msg),
                 TrsError::SqlError(err, msg) => format!("SQL Error: {} - {}", err, msg),
                 TrsError::ReqwestError(err, msg) => format!("Reqwest Error: {} - {}", err, msg),
+                TrsError::SerdeJsonError(err, msg) => format!("JSON Error: {} - {}", err, msg),
             }
         )
     }
 }
+
+/// Errors cross the daemon socket as their message
+impl Serialize for TrsError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TrsError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(TrsError::Error)
+    }
+}
@@ -0,0 +1,289 @@
+/// Just enough HTML tokenizing for feed discovery and for rendering article
+/// bodies in the terminal. Malformed markup is passed through as text rather
+/// than rejected.
+#[derive(Debug, PartialEq)]
+pub enum HtmlToken {
+    Text(String),
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+    },
+    End {
+        name: String,
+    },
+}
+
+impl HtmlToken {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        match self {
+            HtmlToken::Start { attributes, .. } => attributes
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Elements whose content is not markup and is skipped entirely
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "noscript", "template"];
+
+pub fn tokenize(html: &str) -> Vec<HtmlToken> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(HtmlToken::Text(decode_entities(rest)));
+            break;
+        };
+
+        if start > 0 {
+            tokens.push(HtmlToken::Text(decode_entities(&rest[..start])));
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment
+                .find("-->")
+                .map(|end| &comment[end + "-->".len()..])
+                .unwrap_or_default();
+            continue;
+        }
+
+        let is_tag =
+            rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        let Some(end) = rest.find('>').filter(|_| is_tag) else {
+            tokens.push(HtmlToken::Text("<".to_string()));
+            rest = &rest[1..];
+            continue;
+        };
+
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('!') || tag.starts_with('?') {
+            // <!DOCTYPE ...>, <![CDATA[ is not expected in HTML
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(HtmlToken::End {
+                name: name.trim().to_ascii_lowercase(),
+            });
+            continue;
+        }
+
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        let attributes = parse_attributes(&tag[name_end..]);
+
+        if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            let closing = format!("</{}", name);
+            rest = rest
+                .to_ascii_lowercase()
+                .find(&closing)
+                .and_then(|idx| rest[idx..].find('>').map(|end| &rest[idx + end + 1..]))
+                .unwrap_or_default();
+            continue;
+        }
+
+        tokens.push(HtmlToken::Start { name, attributes });
+    }
+
+    tokens
+}
+
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut chars = tag.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace() && *c != '=') {
+            name.push(c);
+        }
+
+        if name.is_empty() {
+            break;
+        }
+
+        // Self-closing `<link ... />`
+        if name == "/" {
+            continue;
+        }
+
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(quote) => {
+                    for c in chars.by_ref() {
+                        if c == quote {
+                            break;
+                        }
+                        value.push(c);
+                    }
+                }
+                None => {
+                    while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+                        value.push(c);
+                    }
+                }
+            }
+        }
+
+        attributes.push((name.to_ascii_lowercase(), decode_entities(&value)));
+    }
+
+    attributes
+}
+
+const NAMED_ENTITIES: [(&str, &str); 24] = [
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", "\u{a0}"),
+    ("rsquo", "\u{2019}"),
+    ("lsquo", "\u{2018}"),
+    ("rdquo", "\u{201d}"),
+    ("ldquo", "\u{201c}"),
+    ("ndash", "\u{2013}"),
+    ("mdash", "\u{2014}"),
+    ("hellip", "\u{2026}"),
+    ("copy", "\u{a9}"),
+    ("reg", "\u{ae}"),
+    ("trade", "\u{2122}"),
+    ("deg", "\u{b0}"),
+    ("times", "\u{d7}"),
+    ("middot", "\u{b7}"),
+    ("bull", "\u{2022}"),
+    ("laquo", "\u{ab}"),
+    ("raquo", "\u{bb}"),
+    ("euro", "\u{20ac}"),
+    ("pound", "\u{a3}"),
+];
+
+/// Elements that don't separate the words around them
+const INLINE_ELEMENTS: [&str; 12] = [
+    "a", "abbr", "b", "code", "em", "i", "mark", "s", "small", "span", "strong", "u",
+];
+
+/// The words of `html` without its markup, separated by single spaces
+pub fn text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    for token in tokenize(html) {
+        match token {
+            HtmlToken::Text(words) => text.push_str(&words),
+            HtmlToken::Start { name, .. } | HtmlToken::End { name }
+                if !INLINE_ELEMENTS.contains(&name.as_str()) =>
+            {
+                text.push(' ')
+            }
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+
+        let replacement = entity.and_then(|entity| {
+            if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(String::from)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse::<u32>()
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(String::from)
+            } else {
+                NAMED_ENTITIES
+                    .iter()
+                    .find(|(name, _)| *name == entity)
+                    .map(|(_, value)| value.to_string())
+            }
+        });
+
+        match (entity, replacement) {
+            (Some(entity), Some(replacement)) => {
+                decoded.push_str(&replacement);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize(
+            "<!-- hi --><p class=\"x\">a &amp; b<br/><script>if (a < b) {}</script></P>1 < 2",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                HtmlToken::Start {
+                    name: "p".to_string(),
+                    attributes: vec![("class".to_string(), "x".to_string())],
+                },
+                HtmlToken::Text("a & b".to_string()),
+                HtmlToken::Start {
+                    name: "br".to_string(),
+                    attributes: vec![],
+                },
+                HtmlToken::End {
+                    name: "p".to_string()
+                },
+                HtmlToken::Text("1 ".to_string()),
+                HtmlToken::Text("<".to_string()),
+                HtmlToken::Text(" 2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            decode_entities("It&rsquo;s &#34;quoted&#x22; &amp;&unknown; & done"),
+            "It\u{2019}s \"quoted\" &&unknown; & done"
+        );
+    }
+
+    #[test]
+    fn text_without_markup() {
+        assert_eq!(
+            text("<p>A <a href=\"https://example.com/paper\">pa<b>per</b></a></p><p>on&nbsp;rock<img src=\"x\">s</p>"),
+            "A paper on rock s"
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    runtime::Handle,
+    sync::{
+        mpsc::{self, UnboundedSender},
+        oneshot,
+    },
+};
+
+use crate::{
+    args::{
+        AddChannelArgs, FolderArgs, GetArticlesArgs, ListChannelArgs, MarkReadArgs,
+        MoveChannelArgs, PruneArgs, RefreshArgs, RemoveChannelArgs, RetentionArgs, SearchArgs,
+        SetIntervalArgs, ShowArticleArgs, StarArgs, SyncArgs,
+    },
+    commands::{self, ImportResult, RefreshResult, RetentionResult, TrsEnv},
+    error::{Result, TrsError},
+    opml::OpmlFeed,
+    persistence::{
+        ArticleIds, ArticlePage, ArticleQuery, Folder, PruneReport, RssArticleD, RssChannelD,
+        SearchHit,
+    },
+    sync::SyncResult,
+};
+
+/// What a client can ask of the daemon. Every request gets exactly one
+/// `Response`.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Request {
+    ListChannels(ListChannelArgs),
+    GetChannel {
+        id: i64,
+    },
+    /// Without their articles
+    ChannelsById {
+        ids: Vec<i64>,
+    },
+    GetArticles(GetArticlesArgs),
+    ListArticles(ArticleQuery),
+    ArticlesById {
+        ids: ArticleIds,
+        limit: u32,
+    },
+    UnreadArticleIds,
+    StarredArticleIds,
+    ShowArticle(ShowArticleArgs),
+    Search(SearchArgs),
+    MarkRead(MarkReadArgs),
+    Star(StarArgs),
+    /// Everything published up to `before`, a unix timestamp
+    MarkAllRead {
+        channel_id: Option<i64>,
+        before: i64,
+    },
+    AddChannel(AddChannelArgs),
+    RemoveChannel(RemoveChannelArgs),
+    SetInterval(SetIntervalArgs),
+    /// Answered with the settings as they are after the change
+    Retention(RetentionArgs),
+    Prune(PruneArgs),
+    ListFolders,
+    /// Answered with the folders as they are after the change
+    Folder(FolderArgs),
+    MoveChannel(MoveChannelArgs),
+    Refresh(RefreshArgs),
+    Import {
+        feeds: Vec<OpmlFeed>,
+        jobs: usize,
+    },
+    Sync(SyncArgs),
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Response {
+    Channels(Vec<RssChannelD>),
+    Channel(RssChannelD),
+    Folders(Vec<Folder>),
+    Articles(ArticlePage),
+    Article(RssArticleD),
+    SearchHits(Vec<SearchHit>),
+    Ids(Vec<i64>),
+    Refreshed(Vec<RefreshResult>),
+    Imported(Vec<ImportResult>),
+    Synced(SyncResult),
+    Retention(RetentionResult),
+    Pruned(Vec<PruneReport>),
+    Done,
+    Error(TrsError),
+}
+
+/// The daemon listens next to the database it serves, so every profile gets
+/// its own
+pub fn socket_path(db_path: &Path) -> PathBuf {
+    db_path.with_extension("sock")
+}
+
+/// Run a request against the database
+pub async fn handle(ctx: &TrsEnv, request: Request) -> Response {
+    let response = match request {
+        Request::ListChannels(args) => commands::list_channels(ctx, &args).map(Response::Channels),
+        Request::GetChannel { id } => commands::get_channel(ctx, id).map(Response::Channel),
+        Request::ChannelsById { ids } => {
+            commands::channels_by_id(ctx, &ids).map(Response::Channels)
+        }
+        Request::GetArticles(args) => {
+            commands::get_articles_by_channel(ctx, &args).map(Response::Channels)
+        }
+        Request::ListArticles(query) => {
+            commands::query_articles(ctx, &query).map(Response::Articles)
+        }
+        Request::ArticlesById { ids, limit } => {
+            commands::articles_by_id(ctx, &ids, limit).map(Response::Articles)
+        }
+        Request::UnreadArticleIds => commands::unread_article_ids(ctx).map(Response::Ids),
+        Request::StarredArticleIds => commands::starred_article_ids(ctx).map(Response::Ids),
+        Request::MarkAllRead { channel_id, before } => {
+            commands::mark_all_read(ctx, channel_id, before).map(|_| Response::Done)
+        }
+        Request::ShowArticle(args) => commands::get_article(ctx, &args).map(Response::Article),
+        Request::Search(args) => commands::search(ctx, &args).map(Response::SearchHits),
+        Request::MarkRead(args) => commands::mark_read(ctx, &args).map(|_| Response::Done),
+        Request::Star(args) => commands::star(ctx, &args).map(|_| Response::Done),
+        Request::AddChannel(args) => commands::add_channel(ctx, &args)
+            .await
+            .map(Response::Channel),
+        Request::RemoveChannel(args) => {
+            commands::remove_channel(ctx, &args).map(|_| Response::Done)
+        }
+        Request::SetInterval(args) => {
+            commands::set_refresh_interval(ctx, &args).map(|_| Response::Done)
+        }
+        Request::Retention(args) => commands::retention(ctx, &args).map(Response::Retention),
+        Request::Prune(args) => commands::prune(ctx, &args).map(Response::Pruned),
+        Request::ListFolders => commands::list_folders(ctx).map(Response::Folders),
+        Request::Folder(args) => commands::folder(ctx, &args).map(Response::Folders),
+        Request::MoveChannel(args) => commands::move_channel(ctx, &args).map(|_| Response::Done),
+        Request::Refresh(args) => commands::refresh(ctx, &args).await.map(Response::Refreshed),
+        Request::Import { feeds, jobs } => commands::import_feeds(ctx, feeds, jobs)
+            .await
+            .map(Response::Imported),
+        Request::Sync(args) => commands::sync(ctx, &args).await.map(Response::Synced),
+    };
+
+    response.unwrap_or_else(Response::Error)
+}
+
+type Job = (Request, oneshot::Sender<Response>);
+
+/// Runs requests one at a time on a thread of its own, which is the only one
+/// touching the sqlite connection
+#[derive(Clone)]
+pub struct Worker {
+    jobs: UnboundedSender<Job>,
+}
+
+impl Worker {
+    pub fn start(ctx: TrsEnv) -> Self {
+        let (jobs, mut job_recv) = mpsc::unbounded_channel::<Job>();
+        let runtime = Handle::current();
+        std::thread::spawn(move || {
+            while let Some((request, reply)) = job_recv.blocking_recv() {
+                let response = runtime.block_on(handle(&ctx, request));
+                // The client may have gone away in the meantime
+                _ = reply.send(response);
+            }
+        });
+
+        Worker { jobs }
+    }
+
+    /// Queue `request`, an error response comes back as `Err`
+    pub async fn call(&self, request: Request) -> Result<Response> {
+        let (reply, response) = oneshot::channel();
+        self.jobs
+            .send((request, reply))
+            .map_err(|_| TrsError::Error("The database worker has stopped".to_string()))?;
+
+        match response.await {
+            Ok(Response::Error(e)) => Err(e),
+            Ok(response) => Ok(response),
+            Err(_) => Err(TrsError::Error("The request was dropped".to_string())),
+        }
+    }
+}
+
+/// Messages are JSON, one per line
+pub async fn write_message<W, T>(writer: &mut W, message: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(message)
+        .map_err(|e| TrsError::SerdeJsonError(e, "Unable to encode message".to_string()))?;
+    line.push(b'\n');
+    writer
+        .write_all(&line)
+        .await
+        .map_err(|e| TrsError::IoError(e, "Unable to send message".to_string()))
+}
+
+/// Returns `None` once the other end hangs up
+pub async fn read_message<R, T>(reader: &mut R) -> Result<Option<T>>
+where
+    R: AsyncBufRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut line = String::new();
+    let read = reader
+        .read_line(&mut line)
+        .await
+        .map_err(|e| TrsError::IoError(e, "Unable to receive message".to_string()))?;
+    if read == 0 {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| TrsError::SerdeJsonError(e, "Unable to decode message".to_string()))
+}
+
+pub struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Connection {
+    async fn send(&mut self, request: &Request) -> Result<Response> {
+        write_message(&mut self.writer, request).await?;
+        read_message(&mut self.reader)
+            .await?
+            .ok_or_else(|| TrsError::Error("The daemon closed the connection".to_string()))
+    }
+}
+
+/// Where requests go: the daemon when one is running for the database,
+/// otherwise the database itself
+pub enum Session {
+    Local(TrsEnv),
+    Daemon(Connection),
+}
+
+impl Session {
+    pub async fn open(db_path: &Path) -> Result<Self> {
+        if let Ok(stream) = UnixStream::connect(socket_path(db_path)).await {
+            let (reader, writer) = stream.into_split();
+            return Ok(Session::Daemon(Connection {
+                reader: BufReader::new(reader),
+                writer,
+            }));
+        }
+
+        TrsEnv::new(db_path).map(Session::Local)
+    }
+
+    pub fn is_daemon(&self) -> bool {
+        matches!(self, Session::Daemon(_))
+    }
+
+    /// Send `request`, an error response comes back as `Err`
+    pub async fn request(&mut self, request: Request) -> Result<Response> {
+        let response = match self {
+            Session::Local(ctx) => handle(ctx, request).await,
+            Session::Daemon(connection) => connection.send(&request).await?,
+        };
+
+        match response {
+            Response::Error(e) => Err(e),
+            response => Ok(response),
+        }
+    }
+
+    pub async fn list_channels(&mut self, args: ListChannelArgs) -> Result<Vec<RssChannelD>> {
+        match self.request(Request::ListChannels(args)).await? {
+            Response::Channels(channels) => Ok(channels),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn list_folders(&mut self) -> Result<Vec<Folder>> {
+        match self.request(Request::ListFolders).await? {
+            Response::Folders(folders) => Ok(folders),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn folder(&mut self, args: FolderArgs) -> Result<Vec<Folder>> {
+        match self.request(Request::Folder(args)).await? {
+            Response::Folders(folders) => Ok(folders),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn get_articles(&mut self, args: GetArticlesArgs) -> Result<Vec<RssChannelD>> {
+        match self.request(Request::GetArticles(args)).await? {
+            Response::Channels(channels) => Ok(channels),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn get_article(&mut self, args: ShowArticleArgs) -> Result<RssArticleD> {
+        match self.request(Request::ShowArticle(args)).await? {
+            Response::Article(article) => Ok(article),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn search(&mut self, args: SearchArgs) -> Result<Vec<SearchHit>> {
+        match self.request(Request::Search(args)).await? {
+            Response::SearchHits(hits) => Ok(hits),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn add_channel(&mut self, args: AddChannelArgs) -> Result<RssChannelD> {
+        match self.request(Request::AddChannel(args)).await? {
+            Response::Channel(channel) => Ok(channel),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn refresh(&mut self, args: RefreshArgs) -> Result<Vec<RefreshResult>> {
+        match self.request(Request::Refresh(args)).await? {
+            Response::Refreshed(results) => Ok(results),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn import(&mut self, feeds: Vec<OpmlFeed>, jobs: usize) -> Result<Vec<ImportResult>> {
+        match self.request(Request::Import { feeds, jobs }).await? {
+            Response::Imported(results) => Ok(results),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn sync(&mut self, args: SyncArgs) -> Result<SyncResult> {
+        match self.request(Request::Sync(args)).await? {
+            Response::Synced(result) => Ok(result),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn retention(&mut self, args: RetentionArgs) -> Result<RetentionResult> {
+        match self.request(Request::Retention(args)).await? {
+            Response::Retention(result) => Ok(result),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub async fn prune(&mut self, args: PruneArgs) -> Result<Vec<PruneReport>> {
+        match self.request(Request::Prune(args)).await? {
+            Response::Pruned(reports) => Ok(reports),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// For the requests that only succeed or fail
+    pub async fn execute(&mut self, request: Request) -> Result<()> {
+        self.request(request).await.map(|_| ())
+    }
+}
+
+fn unexpected() -> TrsError {
+    TrsError::Error("Unexpected response from the daemon".to_string())
+}
@@ -1,26 +1,49 @@
+use std::io::IsTerminal;
+
 use args::{TrsArgs, TrsSubCommand};
-use commands::TrsEnv;
 use error::Result;
+use ipc::{Request, Session};
+use output::{ArticleRecord, ChannelRecord, OutputFormat};
 pub mod args;
 pub mod commands;
+pub mod daemon;
+pub mod discovery;
 pub mod error;
+pub mod html;
+pub mod ipc;
+pub mod opml;
+pub mod output;
 pub mod parser;
 pub mod persistence;
+pub mod schedule;
+pub mod server;
+pub mod sync;
 pub mod ui;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = argh::from_env::<TrsArgs>();
-    let db_name = "test4";
+    let db_path = persistence::resolve_db_path(args.db.as_deref(), args.profile.as_deref())?;
+    let format = args.format;
+    // Everything else goes through the daemon when there is one
+    let mut session = match args.sub_command {
+        TrsSubCommand::Ui(args) => return ui::ui(&args, &db_path).await,
+        TrsSubCommand::Daemon(args) => return daemon::run(&args, &db_path).await,
+        TrsSubCommand::Serve(args) => return server::run(&args, &db_path).await,
+        _ => Session::open(&db_path).await?,
+    };
     match args.sub_command {
         TrsSubCommand::AddChannel(args) => {
-            let mut ctx = TrsEnv::new(db_name)?;
-            commands::add_channel(&mut ctx, &args)?;
+            session.add_channel(args).await?;
             Ok(())
         }
         TrsSubCommand::ListChannels(args) => {
-            let mut ctx = TrsEnv::new(db_name)?;
-            let channels = commands::list_channels(&mut ctx, &args)?;
+            let channels = session.list_channels(args).await?;
+            if format != OutputFormat::Text {
+                let records = channels.iter().map(ChannelRecord::from).collect::<Vec<_>>();
+                return output::write(&mut std::io::stdout().lock(), format, &records);
+            }
+
             for channel in channels {
                 println!(
                     "{}: {} ({}) updated on {}",
@@ -30,13 +53,22 @@ async fn main() -> Result<()> {
 
             Ok(())
         }
-        TrsSubCommand::RemoveChannel(args) => {
-            let mut ctx = TrsEnv::new("test3")?;
-            commands::remove_channel(&mut ctx, &args)
-        }
+        TrsSubCommand::RemoveChannel(args) => session.execute(Request::RemoveChannel(args)).await,
         TrsSubCommand::GetArticles(args) => {
-            let mut ctx = TrsEnv::new(db_name)?;
-            let channels = commands::get_articles_by_channel(&mut ctx, &args)?;
+            let channels = session.get_articles(args).await?;
+            if format != OutputFormat::Text {
+                let records = channels
+                    .iter()
+                    .flat_map(|channel| {
+                        channel
+                            .articles
+                            .iter()
+                            .map(|article| ArticleRecord::new(channel, article))
+                    })
+                    .collect::<Vec<_>>();
+                return output::write(&mut std::io::stdout().lock(), format, &records);
+            }
+
             for channel in channels {
                 println!(
                     "Channel #{}: {} ({})",
@@ -44,8 +76,9 @@ async fn main() -> Result<()> {
                 );
                 for article in channel.articles {
                     println!(
-                        " #{} - {} ({}) [{}]",
+                        " #{}{} - {} ({}) [{}]",
                         article.id,
+                        if article.starred { " *" } else { "" },
                         article.title,
                         article.link,
                         article
@@ -56,10 +89,205 @@ async fn main() -> Result<()> {
             }
             Ok(())
         }
-        TrsSubCommand::MarkRead(args) => {
-            let mut ctx = TrsEnv::new("test3")?;
-            commands::mark_read(&mut ctx, &args)
+        TrsSubCommand::MarkRead(args) => session.execute(Request::MarkRead(args)).await,
+        TrsSubCommand::Star(args) => session.execute(Request::Star(args)).await,
+        TrsSubCommand::ShowArticle(args) => {
+            let article = session.get_article(args).await?;
+            println!("{}", article.title);
+            println!("{}", article.link);
+            if let Some(date) = article.pub_date {
+                println!("{}", date);
+            }
+            println!();
+            for line in ui::reader::html_to_lines(article.body()) {
+                println!(
+                    "{}",
+                    line.spans
+                        .iter()
+                        .map(|span| span.content.as_ref())
+                        .collect::<String>()
+                );
+            }
+            Ok(())
+        }
+        TrsSubCommand::Search(args) => {
+            let hits = session.search(args).await?;
+            if hits.is_empty() {
+                println!("No articles found");
+            }
+
+            // Highlight the matches, unless piped somewhere
+            let (start, end) = match std::io::stdout().is_terminal() {
+                true => ("\x1b[1;33m", "\x1b[0m"),
+                false => ("", ""),
+            };
+            for hit in hits {
+                let article = hit.article;
+                println!(
+                    "#{}{} - {} ({}) [{}]",
+                    article.id,
+                    if article.starred { " *" } else { "" },
+                    article.title,
+                    article.link,
+                    article
+                        .pub_date
+                        .map_or("No date".to_string(), |d| d.to_string())
+                );
+                let snippet = hit
+                    .snippet
+                    .replace(persistence::MATCH_START, start)
+                    .replace(persistence::MATCH_END, end);
+                println!("    {}", snippet);
+            }
+            Ok(())
+        }
+        TrsSubCommand::SetInterval(args) => session.execute(Request::SetInterval(args)).await,
+        TrsSubCommand::Retention(args) => {
+            let limits = |retention: persistence::Retention| {
+                let keep = match retention.keep_articles {
+                    Some(keep) if keep > 0 => format!("newest {} articles", keep),
+                    _ => "all articles".to_string(),
+                };
+                match retention.keep_days {
+                    Some(days) if days > 0 => format!("{}, read ones for {} days", keep, days),
+                    _ => keep,
+                }
+            };
+            let result = session.retention(args).await?;
+            println!(
+                "Default: keep {}{}",
+                limits(result.settings.defaults),
+                if result.settings.after_refresh {
+                    ", pruned after every refresh"
+                } else {
+                    ""
+                }
+            );
+            if let Some(channel) = result.channel {
+                println!(
+                    "Channel: keep {}",
+                    limits(channel.or(result.settings.defaults))
+                );
+            }
+            Ok(())
+        }
+        TrsSubCommand::Prune(args) => {
+            let dry_run = args.dry_run;
+            let reports = session.prune(args).await?;
+            for report in &reports {
+                println!(
+                    "#{} {}: {} articles",
+                    report.channel_id, report.title, report.articles
+                );
+            }
+
+            let total = reports.iter().map(|report| report.articles).sum::<usize>();
+            match dry_run {
+                true => println!("Would prune {} articles", total),
+                false => println!("Pruned {} articles", total),
+            }
+            Ok(())
+        }
+        TrsSubCommand::Folder(args) => {
+            let listing = matches!(args.command, args::FolderCommand::List(_));
+            let folders = session.folder(args).await?;
+            if listing {
+                let channels = session
+                    .list_channels(args::ListChannelArgs { limit: None })
+                    .await?;
+                for folder in folders {
+                    let count = channels
+                        .iter()
+                        .filter(|channel| channel.folder_id == Some(folder.id))
+                        .count();
+                    println!("{}: {} ({} channels)", folder.id, folder.name, count);
+                }
+            }
+            Ok(())
+        }
+        TrsSubCommand::MoveChannel(args) => session.execute(Request::MoveChannel(args)).await,
+        TrsSubCommand::Refresh(args) => {
+            let results = session.refresh(args).await?;
+            let failed = results.iter().filter(|r| r.result.is_err()).count();
+            for refreshed in &results {
+                match &refreshed.result {
+                    Ok(report) if report.not_modified => println!(
+                        "#{} {}: not modified",
+                        refreshed.channel_id, refreshed.title
+                    ),
+                    Ok(report) => println!(
+                        "#{} {}: {} new, {} updated",
+                        refreshed.channel_id, refreshed.title, report.new, report.updated
+                    ),
+                    Err(e) => println!(
+                        "#{} {}: failed - {}",
+                        refreshed.channel_id, refreshed.title, e
+                    ),
+                }
+            }
+
+            println!(
+                "Refreshed {} channels, {} failed",
+                results.len() - failed,
+                failed
+            );
+            Ok(())
+        }
+        TrsSubCommand::Import(args) => {
+            let feeds = commands::read_opml(&args)?;
+            let results = session.import(feeds, args.jobs).await?;
+            let failed = results.iter().filter(|r| r.result.is_err()).count();
+            for imported in &results {
+                if let Err(e) = &imported.result {
+                    println!(
+                        "Failed to import {} ({}) - {}",
+                        imported.feed.title, imported.feed.xml_url, e
+                    );
+                }
+            }
+
+            println!(
+                "Imported {} channels, {} failed",
+                results.len() - failed,
+                failed
+            );
+            Ok(())
+        }
+        TrsSubCommand::Export(args) => {
+            let channels = session
+                .list_channels(args::ListChannelArgs { limit: None })
+                .await?;
+            let folders = session.list_folders().await?;
+            let exported = commands::export_opml(channels, &folders, &args)?;
+            println!("Exported {} channels to {}", exported, args.opml.display());
+            Ok(())
+        }
+        TrsSubCommand::Sync(mut args) => {
+            // The daemon doesn't share our environment
+            args.password = args
+                .password
+                .or_else(|| std::env::var(sync::PASSWORD_ENV_VAR).ok());
+            let result = session.sync(args).await?;
+            println!(
+                "Synced {} channels: {} new articles, {} changes pushed, {} pulled",
+                result.channels, result.new_articles, result.pushed, result.pulled
+            );
+            if result.dropped > 0 {
+                println!(
+                    "{} local changes dropped, the remote no longer has those articles",
+                    result.dropped
+                );
+            }
+            if result.overridden > 0 {
+                println!(
+                    "{} local changes dropped, the articles changed on the remote since",
+                    result.overridden
+                );
+            }
+            Ok(())
+        }
+        TrsSubCommand::Ui(_) | TrsSubCommand::Daemon(_) | TrsSubCommand::Serve(_) => {
+            unreachable!()
         }
-        TrsSubCommand::Ui(args) => ui::ui(&args, db_name).await,
     }
 }
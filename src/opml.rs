@@ -0,0 +1,218 @@
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+use xml::reader::XmlEvent;
+use xml::writer::XmlEvent as XmlWriteEvent;
+use xml::{EmitterConfig, EventReader};
+
+use crate::error::Result;
+use crate::error::TrsError;
+
+/// A feed subscription from an OPML outline
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpmlFeed {
+    pub title: String,
+    pub xml_url: String,
+    pub html_url: Option<String>,
+    /// The outline the feed is grouped in
+    pub folder: Option<String>,
+}
+
+/// Collect every outline with an `xmlUrl`, at any depth. Outlines without
+/// one group the feeds in them into a folder. Folders don't nest, feeds go
+/// in the outermost one.
+pub fn parse_opml<R: Read>(source: R) -> Result<Vec<OpmlFeed>> {
+    let reader = EventReader::new(source);
+    let mut feeds = Vec::new();
+    let mut is_opml = false;
+    // The name of every open outline that groups feeds
+    let mut outlines: Vec<Option<String>> = Vec::new();
+    for e in reader {
+        match e {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                if name.local_name == "opml" {
+                    is_opml = true;
+                }
+
+                if name.local_name != "outline" {
+                    continue;
+                }
+
+                let attribute = |local_name: &str| {
+                    attributes
+                        .iter()
+                        .find(|attr| attr.name.local_name == local_name)
+                        .map(|attr| attr.value.trim().to_string())
+                        .filter(|value| !value.is_empty())
+                };
+
+                let title = attribute("title").or(attribute("text"));
+                let Some(xml_url) = attribute("xmlUrl") else {
+                    outlines.push(title);
+                    continue;
+                };
+
+                let folder = outlines.iter().flatten().next().cloned();
+                outlines.push(None);
+                feeds.push(OpmlFeed {
+                    title: title.unwrap_or_else(|| xml_url.clone()),
+                    html_url: attribute("htmlUrl"),
+                    xml_url,
+                    folder,
+                });
+            }
+            Ok(XmlEvent::EndElement { name }) if name.local_name == "outline" => {
+                outlines.pop();
+            }
+            Err(e) => {
+                return Err(TrsError::XmlRsError(
+                    e,
+                    "Unable to parse OPML document".to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    if !is_opml {
+        return Err(TrsError::Error("This is not an OPML document".to_string()));
+    }
+
+    Ok(feeds)
+}
+
+pub fn write_opml<W: Write>(sink: W, feeds: &[OpmlFeed]) -> Result<()> {
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(sink);
+    let created = OffsetDateTime::now_utc()
+        .format(&Rfc2822)
+        .unwrap_or_default();
+
+    let mut events: Vec<XmlWriteEvent> = vec![
+        XmlWriteEvent::start_element("opml")
+            .attr("version", "2.0")
+            .into(),
+        XmlWriteEvent::start_element("head").into(),
+        XmlWriteEvent::start_element("title").into(),
+        XmlWriteEvent::characters("trs subscriptions"),
+        XmlWriteEvent::end_element().into(),
+        XmlWriteEvent::start_element("dateCreated").into(),
+        XmlWriteEvent::characters(&created),
+        XmlWriteEvent::end_element().into(),
+        XmlWriteEvent::end_element().into(),
+        XmlWriteEvent::start_element("body").into(),
+    ];
+
+    // Feeds outside of any folder first, then a folder at a time
+    let mut folders: Vec<&str> = Vec::new();
+    for folder in feeds.iter().filter_map(|feed| feed.folder.as_deref()) {
+        if !folders.contains(&folder) {
+            folders.push(folder);
+        }
+    }
+
+    for feed in feeds.iter().filter(|feed| feed.folder.is_none()) {
+        push_feed(&mut events, feed);
+    }
+    for folder in folders {
+        events.push(
+            XmlWriteEvent::start_element("outline")
+                .attr("text", folder)
+                .attr("title", folder)
+                .into(),
+        );
+        for feed in feeds
+            .iter()
+            .filter(|feed| feed.folder.as_deref() == Some(folder))
+        {
+            push_feed(&mut events, feed);
+        }
+        events.push(XmlWriteEvent::end_element().into());
+    }
+
+    events.push(XmlWriteEvent::end_element().into());
+    events.push(XmlWriteEvent::end_element().into());
+    for event in events {
+        writer.write(event).map_err(|e| {
+            TrsError::XmlRsWriterError(e, "Unable to write OPML document".to_string())
+        })?;
+    }
+
+    writer
+        .into_inner()
+        .write_all(b"\n")
+        .map_err(|e| TrsError::IoError(e, "Unable to write OPML document".to_string()))
+}
+
+fn push_feed<'a>(events: &mut Vec<XmlWriteEvent<'a>>, feed: &'a OpmlFeed) {
+    let mut outline = XmlWriteEvent::start_element("outline")
+        .attr("type", "rss")
+        .attr("text", &feed.title)
+        .attr("title", &feed.title)
+        .attr("xmlUrl", &feed.xml_url);
+    if let Some(html_url) = &feed.html_url {
+        outline = outline.attr("htmlUrl", html_url);
+    }
+    events.push(outline.into());
+    events.push(XmlWriteEvent::end_element().into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_outlines_become_folders() {
+        let bytes = include_bytes!("../sample/subscriptions.opml");
+        let feeds = parse_opml(&bytes[..]).unwrap();
+        assert_eq!(feeds.len(), 4);
+        assert_eq!(
+            feeds[0],
+            OpmlFeed {
+                title: "ploeh blog".to_string(),
+                xml_url: "https://blog.ploeh.dk/rss.xml".to_string(),
+                html_url: Some("https://blog.ploeh.dk".to_string()),
+                folder: Some("Programming".to_string()),
+            }
+        );
+        // Deeper outlines end up in the outermost folder
+        assert_eq!(feeds[1].folder.as_deref(), Some("Programming"));
+        assert_eq!(feeds[2].folder, None);
+        // Falls back to the text attribute when there's no title
+        assert_eq!(feeds[2].title, "Department News");
+        assert_eq!(feeds[3].html_url, None);
+    }
+
+    #[test]
+    fn written_opml_can_be_read_back() {
+        let feeds = vec![
+            OpmlFeed {
+                title: "Tom & Jerry's <blog>".to_string(),
+                xml_url: "https://example.com/feed?a=1&b=2".to_string(),
+                html_url: Some("https://example.com".to_string()),
+                folder: None,
+            },
+            OpmlFeed {
+                title: "No homepage".to_string(),
+                xml_url: "https://example.org/atom.xml".to_string(),
+                html_url: None,
+                folder: Some("Blogs & news".to_string()),
+            },
+        ];
+
+        let mut written = Vec::new();
+        write_opml(&mut written, &feeds).unwrap();
+        assert_eq!(parse_opml(&written[..]).unwrap(), feeds);
+    }
+
+    #[test]
+    fn other_xml_is_rejected() {
+        let bytes = include_bytes!("../sample/rss.xml");
+        assert!(parse_opml(&bytes[..]).is_err());
+    }
+}
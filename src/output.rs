@@ -0,0 +1,337 @@
+use std::{fmt::Display, io::Write, str::FromStr};
+
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    error::{Result, TrsError},
+    persistence::{RssArticleD, RssChannelD},
+};
+
+/// How `list` and `articles` print what they found
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Lines meant for people
+    #[default]
+    Text,
+    /// One array
+    Json,
+    /// One object per line
+    Jsonl,
+    Csv,
+    Tsv,
+    /// Columns padded to line up
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!(
+                "Unknown format {}, expected text, json, jsonl, csv, tsv or table",
+                value
+            )),
+        }
+    }
+}
+
+/// A row of machine-readable output
+pub trait Record: Serialize {
+    const COLUMNS: &'static [&'static str];
+
+    /// Values in the order of `COLUMNS`
+    fn fields(&self) -> Vec<String>;
+}
+
+#[derive(Serialize)]
+pub struct ChannelRecord {
+    pub id: i64,
+    pub title: String,
+    pub link: String,
+    pub feed_link: String,
+    pub folder_id: Option<i64>,
+    pub articles: usize,
+    pub unread: usize,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_update: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_fetch: Option<OffsetDateTime>,
+}
+
+impl From<&RssChannelD> for ChannelRecord {
+    fn from(channel: &RssChannelD) -> Self {
+        ChannelRecord {
+            id: channel.id,
+            title: channel.title.clone(),
+            link: channel.link.clone(),
+            feed_link: channel.feed_link.clone(),
+            folder_id: channel.folder_id,
+            articles: channel.articles.len(),
+            unread: channel
+                .articles
+                .iter()
+                .filter(|article| article.unread)
+                .count(),
+            last_update: channel.last_update,
+            last_fetch: channel.last_fetch,
+        }
+    }
+}
+
+impl Record for ChannelRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "title",
+        "link",
+        "feed_link",
+        "folder_id",
+        "articles",
+        "unread",
+        "last_update",
+        "last_fetch",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.title.clone(),
+            self.link.clone(),
+            self.feed_link.clone(),
+            optional(self.folder_id),
+            self.articles.to_string(),
+            self.unread.to_string(),
+            date(Some(self.last_update)),
+            date(self.last_fetch),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+pub struct ArticleRecord {
+    pub id: i64,
+    pub channel_id: i64,
+    pub channel: String,
+    pub title: String,
+    pub link: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub pub_date: Option<OffsetDateTime>,
+    pub unread: bool,
+    pub starred: bool,
+}
+
+impl ArticleRecord {
+    pub fn new(channel: &RssChannelD, article: &RssArticleD) -> Self {
+        ArticleRecord {
+            id: article.id,
+            channel_id: channel.id,
+            channel: channel.title.clone(),
+            title: article.title.clone(),
+            link: article.link.clone(),
+            pub_date: article.pub_date,
+            unread: article.unread,
+            starred: article.starred,
+        }
+    }
+}
+
+impl Record for ArticleRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "channel_id",
+        "channel",
+        "title",
+        "link",
+        "pub_date",
+        "unread",
+        "starred",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.channel_id.to_string(),
+            self.channel.clone(),
+            self.title.clone(),
+            self.link.clone(),
+            date(self.pub_date),
+            self.unread.to_string(),
+            self.starred.to_string(),
+        ]
+    }
+}
+
+fn optional<T: Display>(value: Option<T>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+fn date(value: Option<OffsetDateTime>) -> String {
+    value
+        .and_then(|date| date.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+/// Write `records` in any format but [`OutputFormat::Text`], which every
+/// command prints its own way and is refused here
+pub fn write<W: Write, R: Record>(out: &mut W, format: OutputFormat, records: &[R]) -> Result<()> {
+    let json_failed = |e| TrsError::SerdeJsonError(e, "Unable to encode output".to_string());
+    let lines = match format {
+        OutputFormat::Text => {
+            return Err(TrsError::Error(
+                "Text output is printed by each command".to_string(),
+            ))
+        }
+        OutputFormat::Json => {
+            vec![serde_json::to_string_pretty(records).map_err(json_failed)?]
+        }
+        OutputFormat::Jsonl => records
+            .iter()
+            .map(|record| serde_json::to_string(record).map_err(json_failed))
+            .collect::<Result<Vec<_>>>()?,
+        OutputFormat::Csv => rows(records)
+            .map(|row| {
+                row.iter()
+                    .map(|f| csv_field(f))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect(),
+        OutputFormat::Tsv => rows(records)
+            .map(|row| {
+                row.iter()
+                    .map(|f| tsv_field(f))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .collect(),
+        OutputFormat::Table => table(rows(records).collect()),
+    };
+
+    for line in lines {
+        writeln!(out, "{}", line)
+            .map_err(|e| TrsError::IoError(e, "Unable to write output".to_string()))?;
+    }
+    Ok(())
+}
+
+/// The column names, then the fields of every record
+fn rows<R: Record>(records: &[R]) -> impl Iterator<Item = Vec<String>> + '_ {
+    let header = R::COLUMNS.iter().map(|column| column.to_string()).collect();
+    std::iter::once(header).chain(records.iter().map(Record::fields))
+}
+
+/// Quoted when it holds a separator, a quote or a line break (RFC 4180)
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// TSV can't quote, so tabs and line breaks become spaces
+fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+fn table(rows: Vec<Vec<String>>) -> Vec<String> {
+    let rows = rows
+        .into_iter()
+        .map(|row| row.iter().map(|f| tsv_field(f)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut widths = Vec::new();
+    for row in &rows {
+        widths.resize(row.len(), 0);
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(field, width)| format!("{:<width$}", field, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(title: &str) -> ArticleRecord {
+        ArticleRecord {
+            id: 3,
+            channel_id: 1,
+            channel: "Blog".to_string(),
+            title: title.to_string(),
+            link: "https://example.com/a".to_string(),
+            pub_date: Some(time::macros::datetime!(2025-06-01 12:30 UTC)),
+            unread: true,
+            starred: false,
+        }
+    }
+
+    fn written(format: OutputFormat, records: &[ArticleRecord]) -> String {
+        let mut out = Vec::new();
+        write(&mut out, format, records).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn records_in_every_format() {
+        let records = [record("Hello, \"world\""), record("Tabs\tand\nlines")];
+
+        let csv = written(OutputFormat::Csv, &records);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,channel_id,channel,title,link,pub_date,unread,starred")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("3,1,Blog,\"Hello, \"\"world\"\"\",https://example.com/a,2025-06-01T12:30:00Z,true,false")
+        );
+
+        let tsv = written(OutputFormat::Tsv, &records);
+        assert_eq!(tsv.lines().count(), 3);
+        assert!(tsv.lines().nth(2).unwrap().contains("\tTabs and lines\t"));
+
+        let jsonl = written(OutputFormat::Jsonl, &records);
+        let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(first["pub_date"], "2025-06-01T12:30:00Z");
+        assert_eq!(first["unread"], true);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&written(OutputFormat::Json, &records)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+
+        // Every column starts at the same place
+        let table = written(OutputFormat::Table, &records);
+        let starts = table
+            .lines()
+            .map(|line| {
+                line.find("https")
+                    .unwrap_or_else(|| line.find("link").unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert!(starts.windows(2).all(|pair| pair[0] == pair[1]));
+
+        assert_eq!(
+            written(OutputFormat::Csv, &[]),
+            format!("{}\n", ArticleRecord::COLUMNS.join(","))
+        );
+        assert!(write(&mut Vec::new(), OutputFormat::Text, &records).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+pub mod json_feed;
+
 use std::io::Read;
 
 use time::format_description;
@@ -6,6 +8,9 @@ use time::format_description::well_known::Rfc2822;
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 use time::PrimitiveDateTime;
+use time::Weekday;
+use xml::attribute::OwnedAttribute;
+use xml::name::OwnedName;
 use xml::{reader::XmlEvent, EventReader};
 
 use crate::error::Result;
@@ -15,6 +20,11 @@ pub struct RssChannel {
     pub title: String,
     pub link: String,
     pub description: String,
+    /// Minutes the feed may be cached for, from `<ttl>`
+    pub ttl: Option<u32>,
+    /// Hours (0-23, GMT) and days in which the feed asks not to be fetched
+    pub skip_hours: Vec<u8>,
+    pub skip_days: Vec<Weekday>,
     pub articles: Vec<RssArticle>,
 }
 
@@ -22,15 +32,78 @@ pub struct RssArticle {
     pub title: String,
     pub link: String,
     pub description: String,
+    /// The full post (`content:encoded`, Atom `<content>`), empty when the
+    /// feed only has a summary
+    pub content: String,
+    pub guid: String,
     pub date: Option<OffsetDateTime>,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DocumentKind {
+    Xml,
+    Json,
+    Html,
+}
+
+impl DocumentKind {
+    /// Sniff the start of the body, falling back to the Content-Type header.
+    /// Plenty of servers send feeds as text/html or text/plain, so the header
+    /// alone can't be trusted.
+    pub fn detect(content_type: Option<&str>, bytes: &[u8]) -> Self {
+        let content_type = content_type.unwrap_or_default().to_ascii_lowercase();
+        if content_type.contains("json") {
+            return DocumentKind::Json;
+        }
+
+        let body = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        let start = body.iter().position(|b| !b.is_ascii_whitespace());
+        let body = &body[start.unwrap_or(body.len())..];
+        let starts_with = |prefix: &[u8]| {
+            body.len() >= prefix.len() && body[..prefix.len()].eq_ignore_ascii_case(prefix)
+        };
+
+        if starts_with(b"{") {
+            DocumentKind::Json
+        } else if starts_with(b"<!doctype html") || starts_with(b"<html") {
+            DocumentKind::Html
+        } else if starts_with(b"<?xml") || starts_with(b"<rss") || starts_with(b"<feed") {
+            DocumentKind::Xml
+        } else if content_type.contains("html") {
+            DocumentKind::Html
+        } else {
+            DocumentKind::Xml
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FeedFormat {
+    Rss,
+    Rdf,
+    Atom,
+}
+
+impl FeedFormat {
+    fn from_root_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "rss" => Some(FeedFormat::Rss),
+            "RDF" => Some(FeedFormat::Rdf),
+            "feed" => Some(FeedFormat::Atom),
+            _ => None,
+        }
+    }
+}
+
 impl RssChannel {
     fn new() -> Self {
         RssChannel {
             title: String::new(),
             link: String::new(),
             description: String::new(),
+            ttl: None,
+            skip_hours: Vec::new(),
+            skip_days: Vec::new(),
             articles: Vec::new(),
         }
     }
@@ -48,14 +121,35 @@ impl RssChannel {
             XmlField::ChannelTitle => self.title = value,
             XmlField::ChannelLink => self.link = value,
             XmlField::ChannelDescription => self.description = value,
+            // Caching hints are optional, a bad value is not worth failing the feed for
+            XmlField::ChannelTtl => self.ttl = value.parse().ok(),
+            XmlField::ChannelSkipHour => {
+                if let Some(hour) = value.parse().ok().filter(|hour| *hour < 24) {
+                    self.skip_hours.push(hour);
+                }
+            }
+            XmlField::ChannelSkipDay => {
+                if let Some(day) = parse_weekday(&value) {
+                    self.skip_days.push(day);
+                }
+            }
             XmlField::ArticleTitle => last_article.ok_or_else(no_item_error)?.title = value,
             XmlField::ArticleLink => last_article.ok_or_else(no_item_error)?.link = value,
             XmlField::ArticleDescription => {
                 last_article.ok_or_else(no_item_error)?.description = value
             }
+            XmlField::ArticleContent => last_article.ok_or_else(no_item_error)?.content = value,
+            XmlField::ArticleGuid => last_article.ok_or_else(no_item_error)?.guid = value,
             XmlField::ArticlePubDate => {
                 last_article.ok_or_else(no_item_error)?.date = Some(RssArticle::parse_date(&value)?)
             }
+            XmlField::ArticleUpdated => {
+                // <published> wins over <updated> regardless of their order
+                let article = last_article.ok_or_else(no_item_error)?;
+                if article.date.is_none() {
+                    article.date = Some(RssArticle::parse_date(&value)?);
+                }
+            }
         }
 
         Ok(())
@@ -68,10 +162,29 @@ impl RssArticle {
             title: String::new(),
             link: String::new(),
             description: String::new(),
+            content: String::new(),
+            guid: String::new(),
             date: None,
         }
     }
 
+    /// Identifies the article within its channel. Falls back to the link, and
+    /// then to the title and date, for feeds that don't give items an id.
+    pub fn unique_id(&self) -> String {
+        if !self.guid.is_empty() {
+            return self.guid.clone();
+        }
+
+        if !self.link.is_empty() {
+            return self.link.clone();
+        }
+
+        match self.date {
+            Some(date) => format!("{}@{}", self.title, date.unix_timestamp()),
+            None => self.title.clone(),
+        }
+    }
+
     fn parse_date(value: &str) -> Result<OffsetDateTime> {
         let weird_format = format_description::parse(
             "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] UTC",
@@ -99,109 +212,232 @@ enum XmlField {
     ArticleTitle,
     ArticleLink,
     ArticlePubDate,
+    ArticleUpdated,
     ArticleDescription,
+    ArticleContent,
+    ArticleGuid,
     ChannelTitle,
     ChannelLink,
     ChannelDescription,
+    ChannelTtl,
+    ChannelSkipHour,
+    ChannelSkipDay,
+}
+
+enum XmlValue {
+    /// Text content of the element
+    Text,
+    /// Text content of the element, which may also contain child markup
+    /// (Atom text constructs with type="xhtml")
+    Markup,
+    /// Value of the `href` attribute of an Atom `<link>` element
+    AtomLink,
 }
 
 struct XmlTagField {
     hierarchical_tag: &'static str,
-    tag: &'static str,
     field: XmlField,
+    value: XmlValue,
 }
 
 impl XmlTagField {
-    const fn mapping(hierarchical_tag: &'static str, tag: &'static str, field: XmlField) -> Self {
+    const fn mapping(hierarchical_tag: &'static str, field: XmlField) -> Self {
         XmlTagField {
             hierarchical_tag,
-            tag,
             field,
+            value: XmlValue::Text,
         }
     }
 
-    fn corresponding_field(hierarchical_tag: &str) -> Option<&'static XmlTagField> {
-        for field in FIELD_TAG_MAPPINGS.iter() {
-            if field.hierarchical_tag == hierarchical_tag {
-                return Some(field);
-            }
+    const fn markup(hierarchical_tag: &'static str, field: XmlField) -> Self {
+        XmlTagField {
+            hierarchical_tag,
+            field,
+            value: XmlValue::Markup,
+        }
+    }
+
+    const fn atom_link(hierarchical_tag: &'static str, field: XmlField) -> Self {
+        XmlTagField {
+            hierarchical_tag,
+            field,
+            value: XmlValue::AtomLink,
         }
+    }
 
-        None
+    fn corresponding_field(hierarchical_tag: &str) -> Option<&'static XmlTagField> {
+        FIELD_TAG_MAPPINGS
+            .iter()
+            .find(|field| field.hierarchical_tag == hierarchical_tag)
     }
 }
 
-const FIELD_TAG_MAPPINGS: [XmlTagField; 7] = [
-    XmlTagField::mapping("title", "title", XmlField::ChannelTitle),
-    XmlTagField::mapping("link", "link", XmlField::ChannelLink),
-    XmlTagField::mapping("description", "description", XmlField::ChannelDescription),
-    XmlTagField::mapping("item > title", "title", XmlField::ArticleTitle),
-    XmlTagField::mapping("item > link", "link", XmlField::ArticleLink),
-    XmlTagField::mapping(
-        "item > description",
-        "description",
-        XmlField::ArticleDescription,
-    ),
-    XmlTagField::mapping("item > pubDate", "pubDate", XmlField::ArticlePubDate),
+/// Fields are looked up by `<parent> > <tag>`. Elements of the namespaces in
+/// `NAMESPACES` go by their usual prefix, whatever the document binds to it.
+const FIELD_TAG_MAPPINGS: &[XmlTagField] = &[
+    // RSS 2.0
+    XmlTagField::mapping("channel > title", XmlField::ChannelTitle),
+    XmlTagField::mapping("channel > link", XmlField::ChannelLink),
+    XmlTagField::mapping("channel > description", XmlField::ChannelDescription),
+    XmlTagField::mapping("channel > ttl", XmlField::ChannelTtl),
+    XmlTagField::mapping("skipHours > hour", XmlField::ChannelSkipHour),
+    XmlTagField::mapping("skipDays > day", XmlField::ChannelSkipDay),
+    XmlTagField::mapping("item > title", XmlField::ArticleTitle),
+    XmlTagField::mapping("item > link", XmlField::ArticleLink),
+    XmlTagField::mapping("item > description", XmlField::ArticleDescription),
+    XmlTagField::mapping("item > content:encoded", XmlField::ArticleContent),
+    XmlTagField::mapping("item > pubDate", XmlField::ArticlePubDate),
+    XmlTagField::mapping("item > guid", XmlField::ArticleGuid),
+    // RSS 1.0 (RDF), items are siblings of the channel rather than children
+    XmlTagField::mapping("item > dc:date", XmlField::ArticlePubDate),
+    // Atom (RFC 4287)
+    XmlTagField::mapping("feed > title", XmlField::ChannelTitle),
+    XmlTagField::atom_link("feed > link", XmlField::ChannelLink),
+    XmlTagField::mapping("feed > subtitle", XmlField::ChannelDescription),
+    XmlTagField::mapping("entry > title", XmlField::ArticleTitle),
+    XmlTagField::atom_link("entry > link", XmlField::ArticleLink),
+    XmlTagField::markup("entry > summary", XmlField::ArticleDescription),
+    XmlTagField::markup("entry > content", XmlField::ArticleContent),
+    XmlTagField::mapping("entry > id", XmlField::ArticleGuid),
+    XmlTagField::mapping("entry > published", XmlField::ArticlePubDate),
+    XmlTagField::mapping("entry > updated", XmlField::ArticleUpdated),
+];
+
+/// Namespaces of the fields, with the prefix they are looked up by
+const NAMESPACES: &[(&str, &str)] = &[
+    ("http://purl.org/dc/elements/1.1/", "dc"),
+    ("http://purl.org/rss/1.0/modules/content/", "content"),
 ];
 
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Parse a downloaded feed document, whatever its format
+pub fn parse_feed(content_type: Option<&str>, bytes: &[u8]) -> Result<RssChannel> {
+    match DocumentKind::detect(content_type, bytes) {
+        DocumentKind::Json => json_feed::parse_json_feed(bytes),
+        DocumentKind::Html => Err(TrsError::Error(
+            "This is a web page, not a feed".to_string(),
+        )),
+        DocumentKind::Xml => {
+            let xml_source_stream = xml::ParserConfig::new()
+                .ignore_invalid_encoding_declarations(true)
+                .create_reader(bytes);
+            parse_rss_channel(xml_source_stream)
+        }
+    }
+}
+
 pub fn parse_rss_channel<R: Read>(xml_source_stream: EventReader<R>) -> Result<RssChannel> {
     let mut channel = RssChannel::new();
-    let mut tag_prefix = "";
+    let mut format: Option<FeedFormat> = None;
+    let mut open_tags: Vec<String> = Vec::new();
     let mut current_field: Option<&XmlTagField> = None;
+    let mut current_value = String::new();
+    let mut escape_text = false;
+    let mut markup_depth = 0;
+    let mut self_link = String::new();
     for e in xml_source_stream {
         match e {
-            Ok(XmlEvent::StartElement { name, .. }) => match name.local_name.as_str() {
-                "item" => {
-                    tag_prefix = "item > ";
-                    channel.articles.push(RssArticle::new());
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let tag = qualified_name(&name);
+                if format.is_none() {
+                    format =
+                        Some(FeedFormat::from_root_tag(&name.local_name).ok_or_else(|| {
+                            TrsError::Error(format!("Unsupported feed root element <{}>", tag))
+                        })?);
                 }
-                tag => {
-                    let None = current_field else {
-                        let current_field_name = current_field.unwrap();
+
+                if let Some(field) = current_field {
+                    let XmlValue::Markup = field.value else {
                         return Err(TrsError::Error(format!(
                             "Unexpected <{}> start tag without closing existing tag <{}>",
-                            tag, current_field_name.hierarchical_tag
+                            tag, field.hierarchical_tag
                         )));
                     };
 
-                    let tag_name_with_prefix = format!("{}{}", tag_prefix, tag);
-                    current_field = XmlTagField::corresponding_field(&tag_name_with_prefix);
+                    current_value.push_str(&start_tag_markup(&tag, &attributes));
+                    markup_depth += 1;
+                    continue;
                 }
-            },
-            Ok(XmlEvent::EndElement { name }) => match name.local_name.as_str() {
-                "item" => {
-                    let None = current_field else {
-                        let current_field_name = current_field.unwrap();
-                        return Err(TrsError::Error(format!(
-                            "Unexpected </item> end tag without closing field {}",
-                            current_field_name.hierarchical_tag
-                        )));
-                    };
-                    tag_prefix = "";
+
+                if name.local_name == "item" || name.local_name == "entry" {
+                    let mut article = RssArticle::new();
+                    // RSS 1.0 identifies items by their rdf:about attribute
+                    if let Some(about) = attributes.iter().find(|attr| {
+                        attr.name.namespace.as_deref() == Some(RDF_NAMESPACE)
+                            && attr.name.local_name == "about"
+                    }) {
+                        article.guid = about.value.trim().to_string();
+                    }
+                    channel.articles.push(article);
                 }
-                tag => {
-                    if let Some(field) = current_field.take() {
-                        if field.tag == tag {
-                            current_field = None;
-                        } else {
-                            return Err(TrsError::Error(format!(
-                                "Unexpected </{}> end tag, expected </{}>",
-                                tag, field.hierarchical_tag
-                            )));
+
+                let parent = open_tags.last().cloned().unwrap_or_default();
+                let lookup_tag = lookup_name(&name);
+                let hierarchical_tag = format!("{} > {}", parent, lookup_tag);
+                open_tags.push(lookup_tag);
+
+                let Some(field) = XmlTagField::corresponding_field(&hierarchical_tag) else {
+                    continue;
+                };
+
+                match field.value {
+                    XmlValue::AtomLink => {
+                        let rel = attribute(&attributes, "rel").unwrap_or("alternate");
+                        let href = attribute(&attributes, "href").unwrap_or_default();
+                        match rel {
+                            "alternate" => {
+                                channel.update_channel_field(field, href.trim().to_string())?
+                            }
+                            "self" if parent == "feed" => self_link = href.trim().to_string(),
+                            _ => {}
                         }
                     }
+                    XmlValue::Markup => {
+                        // Everything is stored as HTML, so plain text is escaped too
+                        escape_text =
+                            matches!(attribute(&attributes, "type"), Some("xhtml" | "text"));
+                        current_field = Some(field);
+                    }
+                    XmlValue::Text => current_field = Some(field),
                 }
-            },
-            Ok(XmlEvent::Characters(data)) => {
-                if let Some(field) = current_field {
-                    let err = channel.update_channel_field(field, data);
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                if markup_depth > 0 {
+                    current_value.push_str(&format!("</{}>", qualified_name(&name)));
+                    markup_depth -= 1;
+                    continue;
+                }
+
+                open_tags.pop();
+                if let Some(field) = current_field.take() {
+                    escape_text = false;
+                    let value = std::mem::take(&mut current_value);
+                    let err = channel.update_channel_field(field, value.trim().to_string());
                     if let Err(e) = err {
                         eprintln!("Error updating channel field: {}", e);
                         return Err(e);
                     }
                 }
             }
+            Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data))
+                if current_field.is_some() =>
+            {
+                if escape_text {
+                    current_value.push_str(&escape_markup(&data));
+                } else {
+                    current_value.push_str(&data);
+                }
+            }
+            Ok(XmlEvent::CData(data)) if current_field.is_some() => {
+                if escape_text {
+                    current_value.push_str(&escape_markup(&data));
+                } else {
+                    current_value.push_str(&data);
+                }
+            }
             Err(e) => {
                 eprintln!("Error parsing XML: {}", e);
                 return Err(TrsError::XmlRsError(
@@ -213,27 +449,101 @@ pub fn parse_rss_channel<R: Read>(xml_source_stream: EventReader<R>) -> Result<R
         }
     }
 
-    if channel.title.is_empty() || channel.link.is_empty() || channel.description.is_empty() {
-        return Err(TrsError::Error("This is not a valid RSS feed".to_string()));
+    match format {
+        Some(FeedFormat::Rss) | Some(FeedFormat::Rdf) => {
+            if channel.title.is_empty() || channel.link.is_empty() || channel.description.is_empty()
+            {
+                return Err(TrsError::Error("This is not a valid RSS feed".to_string()));
+            }
+        }
+        Some(FeedFormat::Atom) => {
+            // An Atom feed is not required to have an alternate link
+            if channel.link.is_empty() {
+                channel.link = self_link;
+            }
+
+            if channel.title.is_empty() || channel.link.is_empty() {
+                return Err(TrsError::Error("This is not a valid Atom feed".to_string()));
+            }
+        }
+        None => return Err(TrsError::Error("This is not a valid RSS feed".to_string())),
     }
 
     Ok(channel)
 }
 
+pub fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "monday" => Some(Weekday::Monday),
+        "tuesday" => Some(Weekday::Tuesday),
+        "wednesday" => Some(Weekday::Wednesday),
+        "thursday" => Some(Weekday::Thursday),
+        "friday" => Some(Weekday::Friday),
+        "saturday" => Some(Weekday::Saturday),
+        "sunday" => Some(Weekday::Sunday),
+        _ => None,
+    }
+}
+
+fn qualified_name(name: &OwnedName) -> String {
+    match &name.prefix {
+        Some(prefix) => format!("{}:{}", prefix, name.local_name),
+        None => name.local_name.clone(),
+    }
+}
+
+/// The name an element is looked up by in `FIELD_TAG_MAPPINGS`: known
+/// namespaces get their usual prefix, other prefixed names the namespace
+/// itself so that a prefix alone can't pass for a known one
+fn lookup_name(name: &OwnedName) -> String {
+    let known_prefix = NAMESPACES
+        .iter()
+        .find(|(namespace, _)| name.namespace.as_deref() == Some(*namespace))
+        .map(|(_, prefix)| prefix);
+    match (known_prefix, &name.prefix, &name.namespace) {
+        (Some(prefix), _, _) => format!("{}:{}", prefix, name.local_name),
+        (None, Some(_), Some(namespace)) => format!("{{{}}}{}", namespace, name.local_name),
+        _ => qualified_name(name),
+    }
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], local_name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attr| attr.name.prefix.is_none() && attr.name.local_name == local_name)
+        .map(|attr| attr.value.as_str())
+}
+
+fn start_tag_markup(tag: &str, attributes: &[OwnedAttribute]) -> String {
+    let mut markup = format!("<{}", tag);
+    for attr in attributes {
+        markup.push_str(&format!(
+            " {}=\"{}\"",
+            qualified_name(&attr.name),
+            escape_markup(&attr.value)
+        ));
+    }
+    markup.push('>');
+    markup
+}
+
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use xml::ParserConfig;
 
     macro_rules! validate_sample {
         ($test_name:ident, $file_name:literal, $title:literal, $link:literal, $description: literal, $article_count: literal) => {
             #[test]
             fn $test_name() {
                 let bytes = include_bytes!(concat!("../sample/", $file_name));
-                let xml_source_stream = ParserConfig::new()
-                    .ignore_invalid_encoding_declarations(true)
-                    .create_reader(&bytes[..]);
-                let rss_channel = parse_rss_channel(xml_source_stream).unwrap();
+                let rss_channel = parse_feed(None, &bytes[..]).unwrap();
 
                 assert_eq!(rss_channel.title, $title);
                 assert_eq!(rss_channel.link, $link);
@@ -242,8 +552,9 @@ mod tests {
                 for article in &rss_channel.articles {
                     assert!(!article.title.is_empty());
                     assert!(!article.link.is_empty());
-                    assert!(!article.description.is_empty());
+                    assert!(!article.description.is_empty() || !article.content.is_empty());
                     assert!(article.date.is_some());
+                    assert!(!article.guid.is_empty());
                 }
             }
         };
@@ -266,4 +577,198 @@ mod tests {
         "danish software design",
         10
     );
+
+    validate_sample!(
+        sample_atom,
+        "atom.xml",
+        "Notes from the Workshop",
+        "https://workshop.example.org/",
+        "Woodworking, tools and the occasional rant",
+        4
+    );
+
+    validate_sample!(
+        sample_rdf,
+        "rdf.xml",
+        "Department News",
+        "https://news.example.edu/",
+        "News and seminars from the Department of Computing",
+        3
+    );
+
+    validate_sample!(
+        sample_json_feed,
+        "feed.json",
+        "Field Recordings",
+        "https://sounds.example.net/",
+        "Ambient recordings from walks around the city",
+        3
+    );
+
+    #[test]
+    fn caching_hints() {
+        let bytes = include_bytes!("../sample/rss2.xml");
+        let channel = parse_feed(None, &bytes[..]).unwrap();
+        assert_eq!(channel.ttl, Some(180));
+        // Out of range hours are ignored
+        assert_eq!(channel.skip_hours, vec![0, 1]);
+        assert_eq!(channel.skip_days, vec![Weekday::Sunday]);
+
+        let bytes = include_bytes!("../sample/rss.xml");
+        let channel = parse_feed(None, &bytes[..]).unwrap();
+        assert_eq!(channel.ttl, None);
+        assert!(channel.skip_hours.is_empty());
+    }
+
+    #[test]
+    fn content_encoded_is_kept_apart_from_description() {
+        let bytes = include_bytes!("../sample/rdf.xml");
+        let channel = parse_feed(None, &bytes[..]).unwrap();
+        let first = &channel.articles[0];
+        assert_eq!(
+            first.description,
+            "Join us on Thursday for a talk on adopting gradual typing in large codebases."
+        );
+        assert_eq!(
+            first.content,
+            "<p>Join us on <strong>Thursday</strong> for a talk on adopting gradual typing in large codebases.</p><p>Coffee from 3pm.</p>"
+        );
+        assert_eq!(channel.articles[1].content, "");
+    }
+
+    #[test]
+    fn namespaces_are_matched_by_uri() {
+        let bytes = include_bytes!("../sample/rdf.xml");
+        let renamed = String::from_utf8_lossy(bytes)
+            .replace("rdf:", "r:")
+            .replace("xmlns:rdf=", "xmlns:r=")
+            .replace("dc:", "d:")
+            .replace("xmlns:dc=", "xmlns:d=")
+            .replace("content:", "c:")
+            .replace("xmlns:content=", "xmlns:c=");
+        let expected = parse_feed(None, bytes).unwrap();
+        let channel = parse_feed(None, renamed.as_bytes()).unwrap();
+        assert_eq!(channel.articles.len(), expected.articles.len());
+        for (article, expected) in channel.articles.iter().zip(&expected.articles) {
+            assert_eq!(article.guid, expected.guid);
+            assert_eq!(article.date, expected.date);
+            assert_eq!(article.content, expected.content);
+        }
+        assert!(channel.articles[0].date.is_some());
+        assert!(!channel.articles[0].content.is_empty());
+
+        // The prefix alone doesn't make it Dublin Core
+        let unbound = String::from_utf8_lossy(bytes)
+            .replace("http://purl.org/dc/elements/1.1/", "https://example.com/dc");
+        let channel = parse_feed(None, unbound.as_bytes()).unwrap();
+        assert!(channel
+            .articles
+            .iter()
+            .all(|article| article.date.is_none()));
+    }
+
+    #[test]
+    fn json_feed_is_detected() {
+        let bytes = include_bytes!("../sample/feed.json");
+        let channel = parse_feed(Some("application/feed+json"), &bytes[..]).unwrap();
+        assert_eq!(
+            channel.articles[0].guid,
+            "https://sounds.example.net/2025/06/harbour/"
+        );
+        assert_eq!(channel.articles[2].guid, "3");
+        assert_eq!(
+            channel.articles[0].content,
+            "<p>Gulls, rigging and a very distant ferry horn.</p>"
+        );
+        assert_eq!(channel.articles[1].description, "");
+
+        // Servers often send JSON feeds as text/plain
+        let channel = parse_feed(Some("text/plain; charset=utf-8"), &bytes[..]).unwrap();
+        assert_eq!(channel.title, "Field Recordings");
+    }
+
+    #[test]
+    fn json_feed_tolerates_missing_links_and_bad_dates() {
+        let bytes = include_bytes!("../sample/feed.json");
+        let trimmed = String::from_utf8_lossy(bytes)
+            .replace("\"home_page_url\": \"https://sounds.example.net/\",", "")
+            .replace(
+                "\"feed_url\": \"https://sounds.example.net/feed.json\",",
+                "",
+            )
+            .replace("2025-06-08T05:12:00+01:00", "last Sunday");
+        let channel = parse_feed(None, trimmed.as_bytes()).unwrap();
+        assert_eq!(channel.link, "");
+        assert_eq!(channel.articles[0].date, None);
+        assert!(channel.articles[1].date.is_some());
+    }
+
+    #[test]
+    fn html_is_detected() {
+        let bytes = include_bytes!("../sample/blog.html");
+        assert_eq!(DocumentKind::detect(None, bytes), DocumentKind::Html);
+
+        let bytes = include_bytes!("../sample/rss2.xml");
+        assert_eq!(
+            DocumentKind::detect(Some("text/html"), bytes),
+            DocumentKind::Xml
+        );
+    }
+
+    #[test]
+    fn atom_entry_fields() {
+        let bytes = include_bytes!("../sample/atom.xml");
+        let channel = parse_feed(Some("application/atom+xml"), &bytes[..]).unwrap();
+
+        let first = &channel.articles[0];
+        assert_eq!(
+            first.guid,
+            "tag:workshop.example.org,2025:/posts/hand-planes"
+        );
+        assert_eq!(
+            first.link,
+            "https://workshop.example.org/posts/hand-planes/"
+        );
+        assert_eq!(first.description, "Why a No. 4 is all most people need.");
+        assert_eq!(
+            first.content,
+            "<p>Why a No. 4 is all most people need, and what to buy after that.</p>"
+        );
+        assert_eq!(
+            first.date.unwrap(),
+            RssArticle::parse_date("2025-06-02T09:30:00Z").unwrap()
+        );
+
+        // No <published>, falls back to <updated>
+        let second = &channel.articles[1];
+        assert_eq!(
+            second.date.unwrap(),
+            RssArticle::parse_date("2025-05-20T18:00:00+02:00").unwrap()
+        );
+        // Text content is escaped, everything is stored as HTML
+        assert_eq!(
+            second.content,
+            "Shellac &amp; alcohol, nothing &lt;else&gt;."
+        );
+
+        // No <summary>, only the full content
+        let third = &channel.articles[2];
+        assert_eq!(third.description, "");
+        assert_eq!(third.content, "<p>Glue-ups are <em>stressful</em>.</p>");
+
+        // xhtml <content> keeps its markup
+        let fourth = &channel.articles[3];
+        assert_eq!(
+            fourth.content,
+            "<div><p>Sharpening &amp; honing, <b>again</b>.</p></div>"
+        );
+
+        // CDATA is only a way of writing text, escaped all the same
+        let cdata = String::from_utf8_lossy(bytes).replace(
+            "Shellac &amp; alcohol, nothing &lt;else&gt;.",
+            "<![CDATA[Shellac & alcohol, nothing <else>.]]>",
+        );
+        let channel = parse_feed(None, cdata.as_bytes()).unwrap();
+        assert_eq!(channel.articles[1].content, second.content);
+    }
 }
@@ -0,0 +1,84 @@
+use serde::Deserialize;
+
+use crate::error::Result;
+use crate::error::TrsError;
+
+use super::{escape_markup, RssArticle, RssChannel};
+
+/// https://www.jsonfeed.org/version/1.1/
+#[derive(Deserialize)]
+struct JsonFeed {
+    version: String,
+    title: String,
+    home_page_url: Option<String>,
+    feed_url: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedItem {
+    id: serde_json::Value,
+    url: Option<String>,
+    external_url: Option<String>,
+    title: Option<String>,
+    content_html: Option<String>,
+    content_text: Option<String>,
+    summary: Option<String>,
+    date_published: Option<String>,
+    date_modified: Option<String>,
+}
+
+/// Both links are optional in JSON Feed, so `link` is left empty for the
+/// caller to fill with the address the feed came from
+pub fn parse_json_feed(bytes: &[u8]) -> Result<RssChannel> {
+    let feed: JsonFeed = serde_json::from_slice(bytes)
+        .map_err(|e| TrsError::SerdeJsonError(e, "Unable to parse JSON feed".to_string()))?;
+
+    if !feed.version.starts_with("https://jsonfeed.org/version/") {
+        return Err(TrsError::Error(format!(
+            "Unsupported JSON feed version {}",
+            feed.version
+        )));
+    }
+
+    let mut channel = RssChannel::new();
+    channel.title = feed.title;
+    channel.link = feed.home_page_url.or(feed.feed_url).unwrap_or_default();
+    channel.description = feed.description.unwrap_or_default();
+    for item in feed.items {
+        channel.articles.push(item.into_article());
+    }
+
+    if channel.title.is_empty() {
+        return Err(TrsError::Error("This is not a valid JSON feed".to_string()));
+    }
+
+    Ok(channel)
+}
+
+impl JsonFeedItem {
+    fn into_article(self) -> RssArticle {
+        let mut article = RssArticle::new();
+        // 1.0 allowed numeric ids, 1.1 requires strings
+        article.guid = match self.id {
+            serde_json::Value::String(id) => id,
+            other => other.to_string(),
+        };
+        article.link = self.url.or(self.external_url).unwrap_or_default();
+        article.description = self.summary.unwrap_or_default();
+        article.content = self
+            .content_html
+            .or(self.content_text.map(|text| escape_markup(&text)))
+            .unwrap_or_default();
+        article.title = self.title.unwrap_or_default();
+        // A date that doesn't parse only costs this item its date
+        article.date = self
+            .date_published
+            .or(self.date_modified)
+            .and_then(|date| RssArticle::parse_date(&date).ok());
+
+        article
+    }
+}
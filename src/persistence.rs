@@ -1,134 +1,553 @@
+pub mod migrations;
+
+use std::collections::HashSet;
 use std::env;
+use std::path::Path;
+use std::path::PathBuf;
 
 use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use time::Duration;
 use time::OffsetDateTime;
+use time::Weekday;
 
 use crate::error::Result;
 use crate::error::TrsError;
+use crate::parser;
 use crate::parser::RssArticle;
 use crate::parser::RssChannel;
 
-const SCHEMA_CHANNELS: &'static str = "CREATE TABLE IF NOT EXISTS Channels ( \
-    id INTEGER PRIMARY KEY, \
-    name TEXT NOT NULL, \
-    link TEXT NOT NULL UNIQUE, \
-    feed_link TEXT NOT NULL UNIQUE, \
-    description TEXT, \
-    last_update INTEGER\
-)";
-
-const SCHEMA_ARTICLES: &'static str = "CREATE TABLE IF NOT EXISTS Articles ( \
-    id INTEGER PRIMARY KEY, \
-    channel_id INTEGER NOT NULL, \
-    title TEXT NOT NULL, \
-    description TEXT, \
-    link TEXT NOT NULL UNIQUE, \
-    pub_date INTEGER, \
-    last_update INTEGER , \
-    unread BOOLEAN DEFAULT TRUE, \
-    FOREIGN KEY(channel_id) REFERENCES Channels(id) ON DELETE CASCADE \
-)";
-
-const ADD_CHANNEL: &'static str = "INSERT INTO Channels (name, link, feed_link, description, last_update) \
-          VALUES (?1, ?2, ?3, ?4, ?5)\
-          ON CONFLICT(link) DO UPDATE SET name=?1, description=?4, last_update=?5";
-const REMOVE_CHANNEL: &'static str = "DELETE FROM Channels WHERE id = ?1";
-const LIST_CHANNELS: &'static str =
-    "SELECT id, name, link, feed_link, description, last_update FROM Channels order by last_update DESC LIMIT ?1";
-const GET_CHANNEL: &'static str =
-    "SELECT id, name, link, feed_link, description, last_update FROM Channels WHERE link = ?1";
-
-const ADD_ARTICLE: &'static str =
-    "INSERT INTO Articles (channel_id, title, description, link, pub_date, last_update, unread) \
-          VALUES (?1, ?2, ?3, ?4, ?5, ?6, true) \
-          ON CONFLICT(link) DO UPDATE SET last_update=?6";
-
-const GET_ARTICLES_BY_CHANNEL: &'static str =
-    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread FROM Articles WHERE channel_id = ?1";
-
-const GET_ARTICLE: &'static str =
-    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread FROM Articles WHERE link = ?1";
-
-const LIST_ARTICLES: &'static str =
-    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread FROM Articles order by last_update DESC";
-
-const MARK_ARTICLE_READ: &'static str = "UPDATE Articles SET unread = FALSE WHERE id = ?1";
-
-const MARK_ARTICLE_UNREAD: &'static str = "UPDATE Articles SET unread = TRUE WHERE id = ?1";
+const ADD_CHANNEL: &str = "INSERT INTO Channels (name, link, feed_link, description, last_update, ttl, skip_hours, skip_days) \
+          VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)\
+          ON CONFLICT(feed_link) DO UPDATE SET name=?1, link=?2, description=?4, last_update=?5, ttl=?6, skip_hours=?7, skip_days=?8";
+const UPDATE_CHANNEL: &str =
+    "UPDATE Channels SET name = ?2, link = ?3, description = ?4, last_update = ?5, \
+          ttl = ?6, skip_hours = ?7, skip_days = ?8 WHERE id = ?1";
+const REMOVE_CHANNEL: &str = "DELETE FROM Channels WHERE id = ?1";
+const LIST_CHANNELS: &str =
+    "SELECT id, name, link, feed_link, description, last_update, etag, last_modified, ttl, skip_hours, skip_days, refresh_interval, last_fetch, folder_id, keep_articles, keep_days FROM Channels order by last_update DESC LIMIT ?1";
+const GET_CHANNEL: &str =
+    "SELECT id, name, link, feed_link, description, last_update, etag, last_modified, ttl, skip_hours, skip_days, refresh_interval, last_fetch, folder_id, keep_articles, keep_days FROM Channels WHERE id = ?1";
+const GET_CHANNEL_ID: &str = "SELECT id FROM Channels WHERE feed_link = ?1";
+const SET_CHANNEL_CACHE_HEADERS: &str =
+    "UPDATE Channels SET etag = ?2, last_modified = ?3 WHERE id = ?1";
+const SET_CHANNEL_FETCHED: &str = "UPDATE Channels SET last_fetch = ?2 WHERE id = ?1";
+const SET_CHANNEL_REFRESH_INTERVAL: &str =
+    "UPDATE Channels SET refresh_interval = ?2 WHERE id = ?1";
+const SET_CHANNEL_FOLDER: &str = "UPDATE Channels SET folder_id = ?2 WHERE id = ?1";
+
+const GET_CHANNEL_RETENTION: &str = "SELECT keep_articles, keep_days FROM Channels WHERE id = ?1";
+const SET_CHANNEL_RETENTION: &str =
+    "UPDATE Channels SET keep_articles = ?2, keep_days = ?3 WHERE id = ?1";
+const CHANNELS_RETENTION: &str =
+    "SELECT id, name, keep_articles, keep_days FROM Channels ORDER BY id";
+
+const GET_RETENTION: &str =
+    "SELECT keep_articles, keep_days, after_refresh FROM Retention WHERE id = 1";
+const SET_RETENTION: &str =
+    "UPDATE Retention SET keep_articles = ?1, keep_days = ?2, after_refresh = ?3 WHERE id = 1";
+
+// Only read articles past the newest ?2 or older than ?3 go, and none with
+// changes still to be synced
+const PRUNABLE_ARTICLES: &str = "SELECT id, guid FROM Articles \
+          WHERE channel_id = ?1 AND NOT unread AND NOT starred \
+          AND id NOT IN (SELECT article_id FROM PendingOperations) \
+          AND ((?2 IS NOT NULL AND id NOT IN ( \
+                  SELECT id FROM Articles WHERE channel_id = ?1 \
+                  ORDER BY COALESCE(pub_date, last_update) DESC, id DESC LIMIT ?2)) \
+              OR (?3 IS NOT NULL AND COALESCE(pub_date, last_update) < ?3))";
+const REMOVE_ARTICLE: &str = "DELETE FROM Articles WHERE id = ?1";
+// Pruned articles are remembered so that refreshes don't bring them back
+const ADD_PRUNED_ARTICLE: &str =
+    "INSERT OR IGNORE INTO PrunedArticles (channel_id, guid) VALUES (?1, ?2)";
+const PRUNED_ARTICLES: &str = "SELECT guid FROM PrunedArticles WHERE channel_id = ?1";
+const FORGET_PRUNED_ARTICLE: &str =
+    "DELETE FROM PrunedArticles WHERE channel_id = ?1 AND guid = ?2";
+
+const LIST_FOLDERS: &str = "SELECT id, name FROM Folders ORDER BY name COLLATE NOCASE, id";
+const GET_FOLDER: &str = "SELECT id, name FROM Folders WHERE id = ?1";
+const GET_FOLDER_BY_NAME: &str = "SELECT id, name FROM Folders WHERE name = ?1";
+const ADD_FOLDER: &str = "INSERT INTO Folders (name) VALUES (?1)";
+const RENAME_FOLDER: &str = "UPDATE Folders SET name = ?2 WHERE id = ?1";
+const REMOVE_FOLDER: &str = "DELETE FROM Folders WHERE id = ?1";
+
+const GET_ARTICLE_BY_ID: &str =
+    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread, guid, content, starred, starred_at FROM Articles WHERE id = ?1";
+
+const ADD_ARTICLE: &str =
+    "INSERT INTO Articles (channel_id, title, description, link, pub_date, last_update, unread, guid, content) \
+          VALUES (?1, ?2, ?3, ?4, ?5, ?6, true, ?7, ?8) \
+          ON CONFLICT(channel_id, guid) DO UPDATE SET title=?2, description=?3, link=?4, pub_date=?5, last_update=?6, content=?8";
+
+// Migrated articles were given their link as guid, they're matched up with
+// their real guid when the feed is next read
+const ADOPT_LEGACY_ARTICLE: &str =
+    "UPDATE Articles SET guid = ?3 WHERE channel_id = ?1 AND guid = ?2 AND link = ?2";
+
+const GET_ARTICLES_BY_CHANNEL: &str =
+    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread, guid, content, starred, starred_at FROM Articles WHERE channel_id = ?1";
+
+const GET_ARTICLE: &str =
+    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread, guid, content, starred, starred_at FROM Articles WHERE channel_id = ?1 AND guid = ?2";
+
+const LIST_ARTICLES: &str =
+    "SELECT id, channel_id, title, description, link, pub_date, last_update, unread, guid, content, starred, starred_at FROM Articles order by last_update DESC";
+
+const ARTICLES_MATCHING: &str =
+    "FROM Articles WHERE (?1 IS NULL OR channel_id = ?1) AND (NOT ?2 OR unread) \
+          AND (?3 IS NULL OR COALESCE(pub_date, last_update) >= ?3) \
+          AND (?4 IS NULL OR COALESCE(pub_date, last_update) < ?4) \
+          AND (NOT ?5 OR starred) \
+          AND (?6 IS NULL OR channel_id IN (SELECT id FROM Channels WHERE folder_id = ?6))";
+
+// Matches in titles count the most
+const SEARCH_ARTICLES: &str =
+    "SELECT a.id, a.channel_id, a.title, a.description, a.link, a.pub_date, a.last_update, a.unread, a.guid, a.content, a.starred, a.starred_at, \
+          snippet(ArticlesSearch, -1, ?2, ?3, '…', 16) \
+          FROM ArticlesSearch JOIN Articles a ON a.id = ArticlesSearch.rowid \
+          WHERE ArticlesSearch MATCH ?1 ORDER BY bm25(ArticlesSearch, 10.0, 2.0, 1.0) LIMIT ?4";
+
+const UNREAD_ARTICLE_IDS: &str = "SELECT id FROM Articles WHERE unread ORDER BY id";
+
+const MARK_READ_BEFORE: &str = "UPDATE Articles SET unread = FALSE \
+          WHERE unread AND (?1 IS NULL OR channel_id = ?1) AND COALESCE(pub_date, last_update) <= ?2";
+
+const MARK_ARTICLE_READ: &str = "UPDATE Articles SET unread = FALSE WHERE id = ?1";
+
+const MARK_ARTICLE_UNREAD: &str = "UPDATE Articles SET unread = TRUE WHERE id = ?1";
+
+const STAR_ARTICLE: &str = "UPDATE Articles SET starred = TRUE, starred_at = ?2 WHERE id = ?1";
+
+const UNSTAR_ARTICLE: &str = "UPDATE Articles SET starred = FALSE, starred_at = NULL WHERE id = ?1";
+
+const STARRED_ARTICLE_IDS: &str = "SELECT id FROM Articles WHERE starred ORDER BY id";
+
+const QUEUE_OPERATION: &str = "INSERT INTO PendingOperations (article_id, tag, added, created_at) \
+          SELECT id, ?2, CASE ?2 WHEN 'read' THEN NOT unread ELSE starred END, ?3 FROM Articles \
+          WHERE id = ?1 AND remote_id IS NOT NULL \
+          ON CONFLICT(article_id, tag) DO UPDATE SET added = excluded.added, created_at = excluded.created_at";
+
+const QUEUE_READ_BEFORE: &str = "INSERT INTO PendingOperations (article_id, tag, added, created_at) \
+          SELECT id, 'read', TRUE, ?3 FROM Articles \
+          WHERE unread AND remote_id IS NOT NULL AND (?1 IS NULL OR channel_id = ?1) AND COALESCE(pub_date, last_update) <= ?2 \
+          ON CONFLICT(article_id, tag) DO UPDATE SET added = excluded.added, created_at = excluded.created_at";
+
+const PENDING_OPERATIONS: &str = "SELECT p.article_id, a.remote_id, p.tag, p.added, p.created_at \
+          FROM PendingOperations p JOIN Articles a ON a.id = p.article_id ORDER BY p.created_at";
+
+const REMOVE_PENDING_OPERATION: &str =
+    "DELETE FROM PendingOperations WHERE article_id = ?1 AND tag = ?2";
+
+const GET_REMOTE: &str = "SELECT url, user, last_sync FROM Remote WHERE id = 1";
+
+const SET_REMOTE: &str = "INSERT INTO Remote (id, url, user, last_sync) VALUES (1, ?1, ?2, ?3) \
+          ON CONFLICT(id) DO UPDATE SET url = ?1, user = ?2, last_sync = ?3";
+
+const LINK_REMOTE_CHANNEL: &str =
+    "UPDATE Channels SET remote_id = ?1 WHERE feed_link = ?2 AND remote_id IS NULL";
+
+const ADD_REMOTE_CHANNEL: &str =
+    "INSERT INTO Channels (name, link, feed_link, description, last_update, remote_id) \
+          VALUES (?2, ?3, ?4, '', ?5, ?1)";
+
+const GET_REMOTE_CHANNEL: &str = "SELECT id FROM Channels WHERE remote_id = ?1";
+
+const UPDATE_REMOTE_ARTICLE: &str =
+    "UPDATE Articles SET title = ?2, link = ?3, pub_date = ?4, content = ?5 WHERE remote_id = ?1";
+
+const LINK_REMOTE_ARTICLE: &str = "UPDATE Articles SET remote_id = ?1 \
+          WHERE id = (SELECT id FROM Articles WHERE channel_id = ?2 AND link = ?3 AND remote_id IS NULL LIMIT 1)";
+
+const ADD_REMOTE_ARTICLE: &str =
+    "INSERT INTO Articles (channel_id, title, description, link, pub_date, last_update, unread, guid, content, remote_id, starred, starred_at) \
+          VALUES (?1, ?2, '', ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, CASE WHEN ?10 THEN ?5 END) \
+          ON CONFLICT(channel_id, guid) DO UPDATE SET remote_id = ?9";
+
+const SYNCED_ARTICLES: &str = "SELECT id, remote_id, CASE ?1 WHEN 'read' THEN NOT unread ELSE starred END FROM Articles \
+          WHERE remote_id IS NOT NULL AND id NOT IN (SELECT article_id FROM PendingOperations WHERE tag = ?1)";
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Where every command kept its database before profiles, in ~/.config/trs
+const LEGACY_DB_FILE: &str = "test4.db";
+
+/// Environment variable holding the path to the database file
+pub const DB_ENV_VAR: &str = "TRS_DB";
+
+/// Work out which database file to use. In order of preference: an explicit
+/// path, an explicit profile, $TRS_DB, and finally the default profile.
+/// Profiles live in $XDG_DATA_HOME/trs, or ~/.config/trs when it isn't set.
+pub fn resolve_db_path(db: Option<&Path>, profile: Option<&str>) -> Result<PathBuf> {
+    let db_env = env::var_os(DB_ENV_VAR).map(PathBuf::from);
+    let default_profile = db.is_none()
+        && profile.is_none()
+        && db_env
+            .as_ref()
+            .is_none_or(|path| path.as_os_str().is_empty());
+    let home_dir = env::home_dir();
+    let db_path = resolve_db_path_with(
+        db,
+        profile,
+        db_env,
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from),
+        home_dir.clone(),
+    )?;
+
+    Ok(match home_dir.filter(|_| default_profile) {
+        Some(home_dir) => adopt_legacy_db(
+            &home_dir.join(".config").join("trs").join(LEGACY_DB_FILE),
+            db_path,
+        ),
+        None => db_path,
+    })
+}
+
+/// Move the database of versions from before profiles to the default
+/// profile, unless it already has one. When it can't be moved it's used where
+/// it is.
+fn adopt_legacy_db(legacy: &Path, db_path: PathBuf) -> PathBuf {
+    if db_path.exists() || !legacy.exists() {
+        return db_path;
+    }
+
+    if let Some(db_dir) = db_path.parent() {
+        _ = std::fs::create_dir_all(db_dir);
+    }
+    match std::fs::rename(legacy, &db_path) {
+        Ok(()) => {
+            eprintln!(
+                "Moved the database from {} to {}",
+                legacy.display(),
+                db_path.display()
+            );
+            db_path
+        }
+        Err(e) => {
+            eprintln!(
+                "Using the database at {}, it couldn't be moved to {}: {}",
+                legacy.display(),
+                db_path.display(),
+                e
+            );
+            legacy.to_path_buf()
+        }
+    }
+}
+
+fn resolve_db_path_with(
+    db: Option<&Path>,
+    profile: Option<&str>,
+    db_env: Option<PathBuf>,
+    data_home: Option<PathBuf>,
+    home_dir: Option<PathBuf>,
+) -> Result<PathBuf> {
+    if let Some(db) = db {
+        return Ok(db.to_path_buf());
+    }
+
+    if profile.is_none() {
+        if let Some(db) = db_env.filter(|path| !path.as_os_str().is_empty()) {
+            return Ok(db);
+        }
+    }
+
+    let profile = profile.unwrap_or(DEFAULT_PROFILE);
+    if profile.is_empty() || profile.contains(['/', '\\']) {
+        return Err(TrsError::Error(format!(
+            "Invalid profile name '{}'",
+            profile
+        )));
+    }
+
+    let db_dir = match data_home.filter(|path| path.is_absolute()) {
+        Some(data_home) => data_home.join("trs"),
+        None => home_dir
+            .map(|dir| dir.join(".config").join("trs"))
+            .ok_or(TrsError::Error(
+                "Unable to determine home directory".to_string(),
+            ))?,
+    };
+
+    Ok(db_dir.join(format!("{}.db", profile)))
+}
 
 pub struct Db {
     connection: Connection,
 }
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub new: usize,
+    pub updated: usize,
+    /// The server answered 304 Not Modified
+    pub not_modified: bool,
+}
+
+enum ArticleChange {
+    New,
+    Updated,
+    Unchanged,
+}
+
+/// Which articles to list, a page at a time
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArticleQuery {
+    pub channel_id: Option<i64>,
+    /// Articles of the channels in this folder
+    pub folder_id: Option<i64>,
+    pub unread: bool,
+    pub starred: bool,
+    /// Unix timestamps bounding the publication date
+    pub newer_than: Option<i64>,
+    pub older_than: Option<i64>,
+    pub oldest_first: bool,
+    /// Only the articles past this one in the order asked for, see
+    /// [`ArticleQuery::position`]. Unlike `offset` it isn't thrown off when
+    /// articles stop matching in between pages.
+    pub after: Option<(i64, i64)>,
+    pub offset: u32,
+    pub limit: u32,
+}
+
+impl ArticleQuery {
+    /// Where `article` sorts among the results
+    pub fn position(article: &RssArticleD) -> (i64, i64) {
+        let date = article.pub_date.or(article.last_update);
+        (date.map_or(0, |date| date.unix_timestamp()), article.id)
+    }
+}
+
+/// Surround the matched words in `SearchHit::snippet`
+pub const MATCH_START: &str = "\u{2}";
+pub const MATCH_END: &str = "\u{3}";
+
+/// An article matching a search, with the passage that matched
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub article: RssArticleD,
+    pub snippet: String,
+}
+
+/// Articles picked by id, for clients that keep their own copy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ArticleIds {
+    /// Oldest first
+    After(i64),
+    /// Newest first
+    Before(i64),
+    Only(Vec<i64>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArticlePage {
+    /// Articles matching the query, on this page or not
+    pub total: usize,
+    pub articles: Vec<RssArticleD>,
+}
+
+/// How long the articles of a channel are kept, 0 meaning no limit. Starred
+/// and unread articles are always kept.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Only the newest articles
+    pub keep_articles: Option<u32>,
+    /// Read articles are dropped after this many days
+    pub keep_days: Option<u32>,
+}
+
+impl Retention {
+    /// The settings of a channel, falling back to `defaults` for those it
+    /// doesn't have
+    pub fn or(self, defaults: Retention) -> Retention {
+        Retention {
+            keep_articles: self.keep_articles.or(defaults.keep_articles),
+            keep_days: self.keep_days.or(defaults.keep_days),
+        }
+    }
+}
+
+/// Retention for channels without settings of their own
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetentionSettings {
+    pub defaults: Retention,
+    /// Prune after every refresh, rather than only when asked
+    pub after_refresh: bool,
+}
+
+/// The articles pruned from a channel, or that would be
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub channel_id: i64,
+    pub title: String,
+    pub articles: usize,
+}
+
+/// A group of channels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Folder {
+    pub id: i64,
+    pub name: String,
+}
+
+/// The reader this database syncs with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remote {
+    pub url: String,
+    pub user: String,
+    pub last_sync: Option<OffsetDateTime>,
+}
+
+/// What a pending operation changes, named after the Google Reader tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tag {
+    Read,
+    Starred,
+}
+
+impl Tag {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tag::Read => "read",
+            Tag::Starred => "starred",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "read" => Some(Tag::Read),
+            "starred" => Some(Tag::Starred),
+            _ => None,
+        }
+    }
+}
+
+/// A change made locally that the remote doesn't know about yet: `tag` was
+/// added to the article or removed from it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingOperation {
+    pub article_id: i64,
+    pub remote_id: i64,
+    pub tag: Tag,
+    pub added: bool,
+    pub created_at: OffsetDateTime,
+}
+
+/// An article as the remote has it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteArticle {
+    pub remote_id: i64,
+    pub title: String,
+    pub link: String,
+    pub content: String,
+    pub published: Option<OffsetDateTime>,
+    pub unread: bool,
+    pub starred: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RssChannelD {
     pub id: i64,
     pub title: String,
     pub link: String,
     pub feed_link: String,
     pub description: String,
+    #[serde(with = "time::serde::rfc3339")]
     pub last_update: OffsetDateTime,
+    /// HTTP validators from the last download, sent back on the next one
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Caching hints from the feed, see [`RssChannel`]
+    pub ttl: Option<u32>,
+    pub skip_hours: Vec<u8>,
+    pub skip_days: Vec<Weekday>,
+    /// Minutes between automatic refreshes, overriding the default
+    pub refresh_interval: Option<u32>,
+    /// When the feed was last downloaded, whether or not it had changed
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_fetch: Option<OffsetDateTime>,
+    pub folder_id: Option<i64>,
+    /// The channel's own settings, see [`Retention::or`]
+    pub retention: Retention,
     pub articles: Vec<RssArticleD>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RssArticleD {
     pub id: i64,
     pub channel_id: i64,
     pub title: String,
     pub description: String,
     pub link: String,
+    #[serde(with = "time::serde::rfc3339::option")]
     pub pub_date: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
     pub last_update: Option<OffsetDateTime>,
     pub unread: bool,
-}
-
-macro_rules! schema_sql {
-    ($conn:expr, $sql:expr) => {
-        $conn
-            .execute($sql, ())
-            .map_err(|e| TrsError::SqlError(e, "Failed to create Channels table".to_string()))?;
-    };
+    pub guid: String,
+    /// The full post, when the feed has more than a summary
+    pub content: String,
+    pub starred: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub starred_at: Option<OffsetDateTime>,
 }
 
 impl Db {
-    pub fn create(instance_name: &str) -> Result<Self> {
-        let home_dir = env::home_dir();
-        let db_dir = home_dir
-            .map(|dir| dir.join(".config").join("trs"))
-            .ok_or(TrsError::Error(
-                "Unable to determine home directory".to_string(),
-            ))?;
-
-        match std::fs::create_dir_all(&db_dir) {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(TrsError::Error(format!(
-                    "Failed to create database directory: {}",
-                    e
-                )));
+    pub fn create(db_file: &Path) -> Result<Self> {
+        if let Some(db_dir) = db_file.parent() {
+            match std::fs::create_dir_all(db_dir) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(TrsError::Error(format!(
+                        "Failed to create database directory: {}",
+                        e
+                    )));
+                }
             }
         }
 
-        let db_file = db_dir.join(format!("{}.db", instance_name));
         let connection = Connection::open(db_file)?;
+        Db::from_connection(connection)
+    }
 
-        schema_sql!(connection, SCHEMA_CHANNELS);
-        schema_sql!(connection, SCHEMA_ARTICLES);
+    fn from_connection(mut connection: Connection) -> Result<Self> {
+        migrations::migrate(&mut connection)?;
+        // Off by default in sqlite. Turned on once migrations are done, as
+        // they may rebuild tables.
+        connection
+            .pragma_update(None, "foreign_keys", true)
+            .map_err(|e| TrsError::SqlError(e, "Failed to enable foreign keys".to_string()))?;
         Ok(Db { connection })
     }
 
-    pub fn get_channel(&self, link: &str) -> Result<RssChannelD> {
+    pub fn get_channel(&self, id: i64) -> Result<RssChannelD> {
         let mut channel = self
             .connection
-            .query_row(GET_CHANNEL, (link,), Db::map_rsschanneld)
-            .map_err(|e| {
-                TrsError::SqlError(e, format!("Failed to retrieve channel with link {}", link))
-            })?;
+            .query_row(GET_CHANNEL, (id,), Db::map_rsschanneld)
+            .optional()
+            .map_err(|e| TrsError::SqlError(e, format!("Failed to retrieve channel {}", id)))?
+            .ok_or_else(|| TrsError::NotFound(format!("No channel with id {}", id)))?;
 
         channel.articles = self.list_articles_by_channel(channel.id)?;
         Ok(channel)
     }
 
-    pub fn add_channel(&self, feed_link: impl AsRef<str>, channel: &RssChannel) -> Result<RssChannelD> {
+    pub fn add_channel(
+        &self,
+        feed_link: impl AsRef<str>,
+        channel: &RssChannel,
+    ) -> Result<RssChannelD> {
+        self.sync_channel(feed_link, channel)
+            .map(|(channel, _)| channel)
+    }
+
+    /// Add the channel downloaded from `feed_link`, or update the one already
+    /// subscribed to it, reporting how many articles were new or changed
+    pub fn sync_channel(
+        &self,
+        feed_link: impl AsRef<str>,
+        channel: &RssChannel,
+    ) -> Result<(RssChannelD, SyncReport)> {
         self.connection
             .execute(
                 ADD_CHANNEL,
@@ -138,29 +557,384 @@ impl Db {
                     feed_link.as_ref(),
                     &channel.description,
                     OffsetDateTime::now_utc().unix_timestamp(),
+                    channel.ttl,
+                    join(&channel.skip_hours),
+                    join(&channel.skip_days),
                 ),
             )
             .map_err(|e| TrsError::SqlError(e, "Failed to add channel".to_string()))?;
 
-        let mut inserted_channel = self.get_channel(&channel.link).map_err(|e| {
-            TrsError::Error(format!("Failed to retrieve channel after adding: {}", e))
-        })?;
+        let id = self
+            .connection
+            .query_row(GET_CHANNEL_ID, (feed_link.as_ref(),), |row| row.get(0))
+            .map_err(|e| {
+                TrsError::SqlError(e, "Failed to retrieve channel after adding".to_string())
+            })?;
+        self.store_articles(id, channel)
+    }
+
+    /// Update a subscribed channel with what was downloaded from its feed.
+    /// The site's link may have changed since, so it's matched by id.
+    pub fn refresh_channel(
+        &self,
+        id: i64,
+        channel: &RssChannel,
+    ) -> Result<(RssChannelD, SyncReport)> {
+        let updated = self
+            .connection
+            .execute(
+                UPDATE_CHANNEL,
+                (
+                    id,
+                    &channel.title,
+                    &channel.link,
+                    &channel.description,
+                    OffsetDateTime::now_utc().unix_timestamp(),
+                    channel.ttl,
+                    join(&channel.skip_hours),
+                    join(&channel.skip_days),
+                ),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to update channel".to_string()))?;
+        if updated == 0 {
+            return Err(TrsError::NotFound(format!("No channel with id {}", id)));
+        }
+
+        self.store_articles(id, channel)
+    }
 
+    fn store_articles(&self, id: i64, channel: &RssChannel) -> Result<(RssChannelD, SyncReport)> {
+        let mut inserted_channel = self.get_channel(id)?;
+        let mut pruned = self.pruned_articles(inserted_channel.id)?;
+        let mut report = SyncReport::default();
         let mut articles = Vec::new();
         for article in &channel.articles {
-            let article = self.add_article(inserted_channel.id, article)?;
+            if pruned.remove(&article.unique_id()) {
+                continue;
+            }
+
+            let (article, change) = self.add_article(inserted_channel.id, article)?;
+            match change {
+                ArticleChange::New => report.new += 1,
+                ArticleChange::Updated => report.updated += 1,
+                ArticleChange::Unchanged => {}
+            }
             articles.push(article);
         }
 
+        // What's left isn't in the feed anymore, so it won't come back. An
+        // empty feed is more likely a broken one.
+        if !channel.articles.is_empty() {
+            for guid in pruned {
+                self.connection
+                    .execute(FORGET_PRUNED_ARTICLE, (inserted_channel.id, guid))
+                    .map_err(|e| {
+                        TrsError::SqlError(e, "Failed to forget pruned article".to_string())
+                    })?;
+            }
+        }
+
         inserted_channel.articles = articles;
-        Ok(inserted_channel)
+        Ok((inserted_channel, report))
     }
 
-    pub fn remove_channel(&self, id: u32) -> Result<usize> {
+    pub fn set_channel_cache_headers(
+        &self,
+        id: i64,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+    ) -> Result<usize> {
+        self.connection
+            .execute(SET_CHANNEL_CACHE_HEADERS, (id, etag, last_modified))
+            .map_err(|e| TrsError::SqlError(e, "Failed to store HTTP cache headers".to_string()))
+    }
+
+    pub fn set_channel_fetched(&self, id: i64, fetched: OffsetDateTime) -> Result<usize> {
+        self.connection
+            .execute(SET_CHANNEL_FETCHED, (id, fetched.unix_timestamp()))
+            .map_err(|e| TrsError::SqlError(e, "Failed to record channel fetch".to_string()))
+    }
+
+    pub fn set_channel_refresh_interval(&self, id: i64, minutes: Option<u32>) -> Result<()> {
+        let updated = self
+            .connection
+            .execute(SET_CHANNEL_REFRESH_INTERVAL, (id, minutes))
+            .map_err(|e| {
+                TrsError::SqlError(e, "Failed to set channel refresh interval".to_string())
+            })?;
+        match updated {
+            0 => Err(TrsError::NotFound(format!("No channel with id {}", id))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Move a channel into a folder, or out of any with `None`
+    pub fn set_channel_folder(&self, id: i64, folder_id: Option<i64>) -> Result<()> {
+        if let Some(folder_id) = folder_id {
+            self.get_folder(folder_id)?;
+        }
+
+        let updated = self
+            .connection
+            .execute(SET_CHANNEL_FOLDER, (id, folder_id))
+            .map_err(|e| TrsError::SqlError(e, "Failed to move channel".to_string()))?;
+        match updated {
+            0 => Err(TrsError::NotFound(format!("No channel with id {}", id))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn retention_settings(&self) -> Result<RetentionSettings> {
+        self.connection
+            .query_row(GET_RETENTION, [], |row| {
+                Ok(RetentionSettings {
+                    defaults: Retention {
+                        keep_articles: row.get(0)?,
+                        keep_days: row.get(1)?,
+                    },
+                    after_refresh: row.get(2)?,
+                })
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to read retention settings".to_string()))
+    }
+
+    pub fn set_retention_settings(&self, settings: &RetentionSettings) -> Result<()> {
+        self.connection
+            .execute(
+                SET_RETENTION,
+                (
+                    settings.defaults.keep_articles,
+                    settings.defaults.keep_days,
+                    settings.after_refresh,
+                ),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to store retention settings".to_string()))?;
+        Ok(())
+    }
+
+    pub fn channel_retention(&self, id: i64) -> Result<Retention> {
         self.connection
+            .query_row(GET_CHANNEL_RETENTION, (id,), |row| {
+                Ok(Retention {
+                    keep_articles: row.get(0)?,
+                    keep_days: row.get(1)?,
+                })
+            })
+            .optional()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read retention".to_string()))?
+            .ok_or_else(|| TrsError::NotFound(format!("No channel with id {}", id)))
+    }
+
+    pub fn set_channel_retention(&self, id: i64, retention: &Retention) -> Result<()> {
+        let updated = self
+            .connection
+            .execute(
+                SET_CHANNEL_RETENTION,
+                (id, retention.keep_articles, retention.keep_days),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to set retention".to_string()))?;
+        match updated {
+            0 => Err(TrsError::NotFound(format!("No channel with id {}", id))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete the articles that every channel's retention lets go of, or
+    /// only count them with `dry_run`. Channels that lose nothing aren't
+    /// reported.
+    pub fn prune(&self, dry_run: bool, now: OffsetDateTime) -> Result<Vec<PruneReport>> {
+        let defaults = self.retention_settings()?.defaults;
+        let channels = self
+            .connection
+            .prepare(CHANNELS_RETENTION)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map([], |row| {
+                let retention = Retention {
+                    keep_articles: row.get(2)?,
+                    keep_days: row.get(3)?,
+                };
+                Ok((
+                    row.get::<usize, i64>(0)?,
+                    row.get::<usize, String>(1)?,
+                    retention,
+                ))
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to list channels".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read channels".to_string()))?;
+
+        let tx = self.connection.unchecked_transaction()?;
+        let mut reports = Vec::new();
+        for (channel_id, title, retention) in channels {
+            let retention = retention.or(defaults);
+            let keep_articles = retention.keep_articles.filter(|keep| *keep > 0);
+            let older_than = retention
+                .keep_days
+                .filter(|days| *days > 0)
+                .map(|days| (now - Duration::days(days as i64)).unix_timestamp());
+            if keep_articles.is_none() && older_than.is_none() {
+                continue;
+            }
+
+            let prunable = tx
+                .prepare(PRUNABLE_ARTICLES)
+                .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+                .query_map((channel_id, keep_articles, older_than), |row| {
+                    Ok((row.get::<usize, i64>(0)?, row.get::<usize, String>(1)?))
+                })
+                .map_err(|e| TrsError::SqlError(e, "Failed to find old articles".to_string()))?
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| TrsError::SqlError(e, "Failed to read old articles".to_string()))?;
+            if prunable.is_empty() {
+                continue;
+            }
+
+            if !dry_run {
+                for (id, guid) in &prunable {
+                    tx.execute(ADD_PRUNED_ARTICLE, (channel_id, guid))
+                        .and_then(|_| tx.execute(REMOVE_ARTICLE, (id,)))
+                        .map_err(|e| {
+                            TrsError::SqlError(e, "Failed to prune article".to_string())
+                        })?;
+                }
+            }
+            reports.push(PruneReport {
+                channel_id,
+                title,
+                articles: prunable.len(),
+            });
+        }
+
+        tx.commit()?;
+        Ok(reports)
+    }
+
+    /// Give the space freed by deleted rows back to the file system
+    pub fn vacuum(&self) -> Result<()> {
+        self.connection
+            .execute_batch("VACUUM")
+            .map_err(|e| TrsError::SqlError(e, "Failed to vacuum the database".to_string()))
+    }
+
+    fn pruned_articles(&self, channel_id: i64) -> Result<HashSet<String>> {
+        self.connection
+            .prepare(PRUNED_ARTICLES)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map((channel_id,), |row| row.get(0))
+            .map_err(|e| TrsError::SqlError(e, "Failed to list pruned articles".to_string()))?
+            .collect::<std::result::Result<HashSet<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read pruned articles".to_string()))
+    }
+
+    pub fn list_folders(&self) -> Result<Vec<Folder>> {
+        self.connection
+            .prepare(LIST_FOLDERS)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map([], Db::map_folder)
+            .map_err(|e| TrsError::SqlError(e, "Failed to list folders".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read folders".to_string()))
+    }
+
+    pub fn get_folder(&self, id: i64) -> Result<Folder> {
+        self.connection
+            .query_row(GET_FOLDER, (id,), Db::map_folder)
+            .optional()
+            .map_err(|e| TrsError::SqlError(e, "Failed to look up folder".to_string()))?
+            .ok_or_else(|| TrsError::NotFound(format!("No folder with id {}", id)))
+    }
+
+    fn get_folder_by_name(&self, name: &str) -> Result<Option<Folder>> {
+        self.connection
+            .query_row(GET_FOLDER_BY_NAME, (name,), Db::map_folder)
+            .optional()
+            .map_err(|e| TrsError::SqlError(e, "Failed to look up folder".to_string()))
+    }
+
+    pub fn add_folder(&self, name: &str) -> Result<Folder> {
+        let name = folder_name(name)?;
+        if self.get_folder_by_name(name)?.is_some() {
+            return Err(TrsError::Error(format!(
+                "There already is a folder named {}",
+                name
+            )));
+        }
+
+        self.connection
+            .execute(ADD_FOLDER, (name,))
+            .map_err(|e| TrsError::SqlError(e, "Failed to add folder".to_string()))?;
+        Ok(Folder {
+            id: self.connection.last_insert_rowid(),
+            name: name.to_string(),
+        })
+    }
+
+    /// The folder called `name`, added if there's none yet
+    pub fn folder_named(&self, name: &str) -> Result<Folder> {
+        match self.get_folder_by_name(folder_name(name)?)? {
+            Some(folder) => Ok(folder),
+            None => self.add_folder(name),
+        }
+    }
+
+    pub fn rename_folder(&self, id: i64, name: &str) -> Result<()> {
+        let name = folder_name(name)?;
+        if self
+            .get_folder_by_name(name)?
+            .is_some_and(|folder| folder.id != id)
+        {
+            return Err(TrsError::Error(format!(
+                "There already is a folder named {}",
+                name
+            )));
+        }
+
+        let updated = self
+            .connection
+            .execute(RENAME_FOLDER, (id, name))
+            .map_err(|e| TrsError::SqlError(e, "Failed to rename folder".to_string()))?;
+        match updated {
+            0 => Err(TrsError::NotFound(format!("No folder with id {}", id))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove a folder, its channels are kept outside of any folder
+    pub fn remove_folder(&self, id: i64) -> Result<()> {
+        let removed = self
+            .connection
+            .execute(REMOVE_FOLDER, (id,))
+            .map_err(|e| TrsError::SqlError(e, "Failed to remove folder".to_string()))?;
+        match removed {
+            0 => Err(TrsError::NotFound(format!("No folder with id {}", id))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn remove_channel(&self, id: u32) -> Result<usize> {
+        let removed = self
+            .connection
             .execute(REMOVE_CHANNEL, (id,))
-            .map(|rows| rows as usize)
-            .map_err(|e| TrsError::SqlError(e, "Failed to remove channel".to_string()))
+            .map_err(|e| TrsError::SqlError(e, "Failed to remove channel".to_string()))?;
+        match removed {
+            0 => Err(TrsError::NotFound(format!("No channel with id {}", id))),
+            removed => Ok(removed),
+        }
+    }
+
+    /// The channels among `ids`, without their articles
+    pub fn channels_by_id(&self, ids: &[i64]) -> Result<Vec<RssChannelD>> {
+        self.connection
+            .prepare(&format!(
+                "SELECT id, name, link, feed_link, description, last_update, etag, last_modified, ttl, skip_hours, skip_days, refresh_interval, last_fetch, folder_id, keep_articles, keep_days \
+                 FROM Channels WHERE id IN ({})",
+                vec!["?"; ids.len()].join(",")
+            ))
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map(rusqlite::params_from_iter(ids), Db::map_rsschanneld)
+            .map_err(|e| TrsError::SqlError(e, "Failed to list channels".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read channels".to_string()))
     }
 
     pub fn list_channels(&self, limit: u32) -> Result<Vec<RssChannelD>> {
@@ -187,20 +961,451 @@ impl Db {
     }
 
     pub fn mark_article_read(&self, id: i64) -> Result<usize> {
-        self.connection
+        let updated = self
+            .connection
             .execute(MARK_ARTICLE_READ, (id,))
-            .map(|rows| rows as usize)
-            .map_err(|e| TrsError::SqlError(e, "Failed to mark article as read".to_string()))
+            .map_err(|e| TrsError::SqlError(e, "Failed to mark article as read".to_string()))?;
+        article_found(id, updated)?;
+        self.queue_operation(id, Tag::Read)?;
+        Ok(updated)
     }
 
     pub fn mark_article_unread(&self, id: i64) -> Result<usize> {
-        self.connection
+        let updated = self
+            .connection
             .execute(MARK_ARTICLE_UNREAD, (id,))
-            .map(|rows| rows as usize)
-            .map_err(|e| TrsError::SqlError(e, "Failed to mark article as unread".to_string()))
+            .map_err(|e| TrsError::SqlError(e, "Failed to mark article as unread".to_string()))?;
+        article_found(id, updated)?;
+        self.queue_operation(id, Tag::Read)?;
+        Ok(updated)
     }
 
-    fn add_article(&self, channel_id: i64, article: &RssArticle) -> Result<RssArticleD> {
+    pub fn star_article(&self, id: i64) -> Result<usize> {
+        let updated = self
+            .connection
+            .execute(
+                STAR_ARTICLE,
+                (id, OffsetDateTime::now_utc().unix_timestamp()),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to star article".to_string()))?;
+        article_found(id, updated)?;
+        self.queue_operation(id, Tag::Starred)?;
+        Ok(updated)
+    }
+
+    pub fn unstar_article(&self, id: i64) -> Result<usize> {
+        let updated = self
+            .connection
+            .execute(UNSTAR_ARTICLE, (id,))
+            .map_err(|e| TrsError::SqlError(e, "Failed to unstar article".to_string()))?;
+        article_found(id, updated)?;
+        self.queue_operation(id, Tag::Starred)?;
+        Ok(updated)
+    }
+
+    pub fn starred_article_ids(&self) -> Result<Vec<i64>> {
+        self.connection
+            .prepare(STARRED_ARTICLE_IDS)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map([], |row| row.get(0))
+            .map_err(|e| TrsError::SqlError(e, "Failed to list starred articles".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read article ids".to_string()))
+    }
+
+    /// Remember to tell the remote about the article's new state, if it came
+    /// from one
+    fn queue_operation(&self, id: i64, tag: Tag) -> Result<usize> {
+        self.connection
+            .execute(
+                QUEUE_OPERATION,
+                (id, tag.name(), OffsetDateTime::now_utc().unix_timestamp()),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to queue change for sync".to_string()))
+    }
+
+    pub fn get_article_by_id(&self, id: i64) -> Result<RssArticleD> {
+        self.connection
+            .query_row(GET_ARTICLE_BY_ID, (id,), Db::map_rssarticled)
+            .optional()
+            .map_err(|e| TrsError::SqlError(e, format!("Failed to retrieve article #{}", id)))?
+            .ok_or_else(|| TrsError::NotFound(format!("No article with id {}", id)))
+    }
+
+    /// Mark the unread articles published up to `before` read, in one
+    /// channel or all of them
+    pub fn mark_read_before(
+        &self,
+        channel_id: Option<i64>,
+        before: OffsetDateTime,
+    ) -> Result<usize> {
+        let tx = self.connection.unchecked_transaction()?;
+        tx.execute(
+            QUEUE_READ_BEFORE,
+            (
+                channel_id,
+                before.unix_timestamp(),
+                OffsetDateTime::now_utc().unix_timestamp(),
+            ),
+        )
+        .map_err(|e| TrsError::SqlError(e, "Failed to queue changes for sync".to_string()))?;
+        let updated = tx
+            .execute(MARK_READ_BEFORE, (channel_id, before.unix_timestamp()))
+            .map_err(|e| TrsError::SqlError(e, "Failed to mark articles as read".to_string()))?;
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    pub fn remote(&self) -> Result<Option<Remote>> {
+        self.connection
+            .query_row(GET_REMOTE, [], |row| {
+                Ok(Remote {
+                    url: row.get(0)?,
+                    user: row.get(1)?,
+                    last_sync: Db::read_datetime(2, row).ok(),
+                })
+            })
+            .optional()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read sync settings".to_string()))
+    }
+
+    pub fn set_remote(&self, remote: &Remote) -> Result<usize> {
+        self.connection
+            .execute(
+                SET_REMOTE,
+                (
+                    &remote.url,
+                    &remote.user,
+                    remote.last_sync.map(|d| d.unix_timestamp()),
+                ),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to store sync settings".to_string()))
+    }
+
+    /// Match a remote subscription with the local channel for the same feed,
+    /// adding one if there is none, and return the local id
+    pub fn sync_remote_channel(
+        &self,
+        remote_id: &str,
+        title: &str,
+        link: &str,
+        feed_link: &str,
+    ) -> Result<i64> {
+        let failed = |e| TrsError::SqlError(e, format!("Failed to sync channel {}", feed_link));
+        let synced = || {
+            self.connection
+                .query_row(GET_REMOTE_CHANNEL, (remote_id,), |row| row.get(0))
+                .optional()
+                .map_err(failed)
+        };
+        if let Some(id) = synced()? {
+            return Ok(id);
+        }
+
+        let linked = self
+            .connection
+            .execute(LINK_REMOTE_CHANNEL, (remote_id, feed_link))
+            .map_err(failed)?;
+        if linked == 0 {
+            let link = if link.is_empty() { feed_link } else { link };
+            self.connection
+                .execute(
+                    ADD_REMOTE_CHANNEL,
+                    (
+                        remote_id,
+                        title,
+                        link,
+                        feed_link,
+                        OffsetDateTime::now_utc().unix_timestamp(),
+                    ),
+                )
+                .map_err(failed)?;
+        }
+
+        synced()?.ok_or_else(|| TrsError::NotFound(format!("No channel for {}", feed_link)))
+    }
+
+    /// Store an article pulled from the remote, matching it by link with one
+    /// downloaded from the feed. Returns whether it was new.
+    pub fn sync_remote_article(&self, channel_id: i64, article: &RemoteArticle) -> Result<bool> {
+        let failed = |e| {
+            TrsError::SqlError(
+                e,
+                format!("Failed to sync remote article {}", article.remote_id),
+            )
+        };
+        let published = article.published.map(|d| d.unix_timestamp());
+        let updated = self
+            .connection
+            .execute(
+                UPDATE_REMOTE_ARTICLE,
+                (
+                    article.remote_id,
+                    &article.title,
+                    &article.link,
+                    published,
+                    &article.content,
+                ),
+            )
+            .map_err(failed)?;
+        if updated > 0 {
+            return Ok(false);
+        }
+
+        let linked = self
+            .connection
+            .execute(
+                LINK_REMOTE_ARTICLE,
+                (article.remote_id, channel_id, &article.link),
+            )
+            .map_err(failed)?;
+        if linked > 0 {
+            return Ok(false);
+        }
+
+        let guid = match article.link.is_empty() {
+            true => format!("remote:{}", article.remote_id),
+            false => article.link.clone(),
+        };
+        if self.pruned_articles(channel_id)?.contains(&guid) {
+            return Ok(false);
+        }
+        self.connection
+            .execute(
+                ADD_REMOTE_ARTICLE,
+                (
+                    channel_id,
+                    &article.title,
+                    &article.link,
+                    published,
+                    OffsetDateTime::now_utc().unix_timestamp(),
+                    article.unread,
+                    guid,
+                    &article.content,
+                    article.remote_id,
+                    article.starred,
+                ),
+            )
+            .map_err(failed)?;
+        Ok(true)
+    }
+
+    pub fn pending_operations(&self) -> Result<Vec<PendingOperation>> {
+        self.connection
+            .prepare(PENDING_OPERATIONS)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map([], |row| {
+                let tag = row.get::<usize, String>(2)?;
+                Ok(PendingOperation {
+                    article_id: row.get(0)?,
+                    remote_id: row.get(1)?,
+                    tag: Tag::parse(&tag).ok_or_else(|| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            2,
+                            rusqlite::types::Type::Text,
+                            format!("Unknown tag {}", tag).into(),
+                        )
+                    })?,
+                    added: row.get(3)?,
+                    created_at: Db::read_datetime(4, row)?,
+                })
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to list pending changes".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read pending changes".to_string()))
+    }
+
+    pub fn remove_pending_operation(&self, operation: &PendingOperation) -> Result<usize> {
+        self.connection
+            .execute(
+                REMOVE_PENDING_OPERATION,
+                (operation.article_id, operation.tag.name()),
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to remove pending change".to_string()))
+    }
+
+    /// `(id, remote_id, has the tag)` of the articles from the remote
+    /// without a change to `tag` waiting to be pushed
+    pub fn synced_articles(&self, tag: Tag) -> Result<Vec<(i64, i64, bool)>> {
+        self.connection
+            .prepare(SYNCED_ARTICLES)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map((tag.name(),), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to list synced articles".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read synced articles".to_string()))
+    }
+
+    /// Take on the remote's state, adding or removing `tag` without queueing
+    /// the change to be pushed back
+    pub fn apply_remote_state(&self, tag: Tag, changes: &[(i64, bool)]) -> Result<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let tx = self.connection.unchecked_transaction()?;
+        for (id, added) in changes {
+            let applied = match (tag, added) {
+                (Tag::Read, true) => tx.execute(MARK_ARTICLE_READ, (id,)),
+                (Tag::Read, false) => tx.execute(MARK_ARTICLE_UNREAD, (id,)),
+                (Tag::Starred, true) => tx.execute(STAR_ARTICLE, (id, now)),
+                (Tag::Starred, false) => tx.execute(UNSTAR_ARTICLE, (id,)),
+            };
+            applied
+                .map_err(|e| TrsError::SqlError(e, "Failed to apply remote state".to_string()))?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn unread_article_ids(&self) -> Result<Vec<i64>> {
+        self.connection
+            .prepare(UNREAD_ARTICLE_IDS)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map([], |row| row.get(0))
+            .map_err(|e| TrsError::SqlError(e, "Failed to list unread articles".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read article ids".to_string()))
+    }
+
+    /// Up to `limit` articles picked by id. The total counts every article.
+    pub fn articles_by_id(&self, ids: &ArticleIds, limit: u32) -> Result<ArticlePage> {
+        let total = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM Articles", [], |row| {
+                row.get::<usize, usize>(0)
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to count articles".to_string()))?;
+
+        let (condition, order, mut params) = match ids {
+            ArticleIds::After(id) => ("id > ?".to_string(), "ASC", vec![*id]),
+            ArticleIds::Before(id) => ("id < ?".to_string(), "DESC", vec![*id]),
+            ArticleIds::Only(ids) => (
+                format!("id IN ({})", vec!["?"; ids.len()].join(",")),
+                "ASC",
+                ids.clone(),
+            ),
+        };
+        params.push(limit as i64);
+        let articles = self
+            .connection
+            .prepare(&format!(
+                "SELECT id, channel_id, title, description, link, pub_date, last_update, unread, guid, content, starred, starred_at \
+                 FROM Articles WHERE {} ORDER BY id {} LIMIT ?",
+                condition, order
+            ))
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map(rusqlite::params_from_iter(params), Db::map_rssarticled)
+            .map_err(|e| TrsError::SqlError(e, "Failed to list articles".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read articles".to_string()))?;
+
+        Ok(ArticlePage { total, articles })
+    }
+
+    /// One page of the articles matching `query`, newest first unless asked
+    /// otherwise
+    pub fn query_articles(&self, query: &ArticleQuery) -> Result<ArticlePage> {
+        let (order, past) = match query.oldest_first {
+            true => ("ASC", ">"),
+            false => ("DESC", "<"),
+        };
+        let matching = format!(
+            "{} AND (?7 IS NULL OR (COALESCE(pub_date, last_update, 0), id) {} (?7, ?8))",
+            ARTICLES_MATCHING, past
+        );
+        let filter = (
+            query.channel_id,
+            query.unread,
+            query.newer_than,
+            query.older_than,
+            query.starred,
+            query.folder_id,
+            query.after.map(|(date, _)| date),
+            query.after.map(|(_, id)| id),
+        );
+        let total = self
+            .connection
+            .query_row(&format!("SELECT COUNT(*) {}", matching), filter, |row| {
+                row.get::<usize, usize>(0)
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to count articles".to_string()))?;
+
+        let articles = self
+            .connection
+            .prepare(&format!(
+                "SELECT id, channel_id, title, description, link, pub_date, last_update, unread, guid, content, starred, starred_at {} \
+                 ORDER BY COALESCE(pub_date, last_update, 0) {order}, id {order} LIMIT ?9 OFFSET ?10",
+                matching,
+                order = order,
+            ))
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map(
+                (
+                    filter.0,
+                    filter.1,
+                    filter.2,
+                    filter.3,
+                    filter.4,
+                    filter.5,
+                    filter.6,
+                    filter.7,
+                    query.limit,
+                    query.offset,
+                ),
+                Db::map_rssarticled,
+            )
+            .map_err(|e| TrsError::SqlError(e, "Failed to list articles".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read articles".to_string()))?;
+
+        Ok(ArticlePage { total, articles })
+    }
+
+    /// Articles matching every word of `query`, best match first. Words
+    /// match as prefixes, so a query can be searched as it's typed.
+    pub fn search_articles(&self, query: &str, limit: u32) -> Result<Vec<SearchHit>> {
+        let Some(query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        self.connection
+            .prepare(SEARCH_ARTICLES)
+            .map_err(|e| TrsError::SqlError(e, "Failed to prepare query".to_string()))?
+            .query_map((query, MATCH_START, MATCH_END, limit), |row| {
+                Ok(SearchHit {
+                    article: Db::map_rssarticled(row)?,
+                    snippet: row.get(12)?,
+                })
+            })
+            .map_err(|e| TrsError::SqlError(e, "Failed to search articles".to_string()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| TrsError::SqlError(e, "Failed to read search results".to_string()))
+    }
+
+    fn add_article(
+        &self,
+        channel_id: i64,
+        article: &RssArticle,
+    ) -> Result<(RssArticleD, ArticleChange)> {
+        let guid = article.unique_id();
+        let lookup = || {
+            self.connection
+                .query_row(GET_ARTICLE, (channel_id, &guid), Db::map_rssarticled)
+                .optional()
+                .map_err(|e| TrsError::SqlError(e, "Failed to look up article".to_string()))
+        };
+        let mut existing = lookup()?;
+        if existing.is_none() && guid != article.link && !article.link.is_empty() {
+            let adopted = self
+                .connection
+                .execute(ADOPT_LEGACY_ARTICLE, (channel_id, &article.link, &guid))
+                .map_err(|e| TrsError::SqlError(e, "Failed to update article guid".to_string()))?;
+            if adopted > 0 {
+                existing = lookup()?;
+            }
+        }
+
         self.connection
             .execute(
                 ADD_ARTICLE,
@@ -211,19 +1416,38 @@ impl Db {
                     &article.link,
                     article.date.map(|d| d.unix_timestamp()),
                     OffsetDateTime::now_utc().unix_timestamp(),
+                    &guid,
+                    &article.content,
                 ),
             )
             .map_err(|e| TrsError::SqlError(e, "Failed to add article".to_string()))?;
 
-        self.get_article(&article.link)
-            .map_err(|e| TrsError::Error(format!("Failed to retrieve article after adding: {}", e)))
+        let change = match existing {
+            None => ArticleChange::New,
+            Some(existing)
+                if existing.title != article.title
+                    || existing.description != article.description
+                    || existing.content != article.content
+                    || existing.link != article.link
+                    || existing.pub_date.map(|d| d.unix_timestamp())
+                        != article.date.map(|d| d.unix_timestamp()) =>
+            {
+                ArticleChange::Updated
+            }
+            Some(_) => ArticleChange::Unchanged,
+        };
+
+        let added = self.get_article(channel_id, &guid).map_err(|e| {
+            TrsError::Error(format!("Failed to retrieve article after adding: {}", e))
+        })?;
+        Ok((added, change))
     }
 
-    fn get_article(&self, link: &str) -> Result<RssArticleD> {
+    fn get_article(&self, channel_id: i64, guid: &str) -> Result<RssArticleD> {
         self.connection
-            .query_row(GET_ARTICLE, (link,), Db::map_rssarticled)
+            .query_row(GET_ARTICLE, (channel_id, guid), Db::map_rssarticled)
             .map_err(|e| {
-                TrsError::SqlError(e, format!("Failed to retrieve article with link {}", link))
+                TrsError::SqlError(e, format!("Failed to retrieve article with guid {}", guid))
             })
     }
 
@@ -254,15 +1478,34 @@ impl Db {
     }
 
     fn map_rsschanneld(row: &rusqlite::Row) -> std::result::Result<RssChannelD, rusqlite::Error> {
-        Ok(RssChannelD::new(
-            row.get(0)?,
-            row.get(1)?,
-            row.get(2)?,
-            row.get(3)?,
-            row.get(4)?,
-            Db::read_datetime(5, &row)?,
-            Vec::new(),
-        ))
+        Ok(RssChannelD {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            link: row.get(2)?,
+            feed_link: row.get(3)?,
+            description: row.get(4)?,
+            last_update: Db::read_datetime(5, row)?,
+            etag: row.get(6)?,
+            last_modified: row.get(7)?,
+            ttl: row.get(8)?,
+            skip_hours: split(row.get(9)?, |hour| hour.parse().ok()),
+            skip_days: split(row.get(10)?, parser::parse_weekday),
+            refresh_interval: row.get(11)?,
+            last_fetch: Db::read_datetime(12, row).ok(),
+            folder_id: row.get(13)?,
+            retention: Retention {
+                keep_articles: row.get(14)?,
+                keep_days: row.get(15)?,
+            },
+            articles: Vec::new(),
+        })
+    }
+
+    fn map_folder(row: &rusqlite::Row) -> std::result::Result<Folder, rusqlite::Error> {
+        Ok(Folder {
+            id: row.get(0)?,
+            name: row.get(1)?,
+        })
     }
 
     fn map_rssarticled(row: &rusqlite::Row) -> std::result::Result<RssArticleD, rusqlite::Error> {
@@ -275,6 +1518,10 @@ impl Db {
             pub_date: Db::read_datetime(5, row).ok(),
             last_update: Db::read_datetime(6, row).ok(),
             unread: row.get(7)?,
+            guid: row.get(8)?,
+            content: row.get::<usize, Option<String>>(9)?.unwrap_or_default(),
+            starred: row.get(10)?,
+            starred_at: Db::read_datetime(11, row).ok(),
         })
     }
 
@@ -294,30 +1541,66 @@ impl Db {
     }
 }
 
-impl RssChannelD {
-    fn new(
-        id: i64,
-        title: String,
-        link: String,
-        feed_link: String,
-        description: String,
-        last_update: OffsetDateTime,
-        articles: Vec<RssArticleD>,
-    ) -> Self {
-        RssChannelD {
-            id,
-            title,
-            link,
-            feed_link,
-            description,
-            last_update,
-            articles,
-        }
+fn article_found(id: i64, updated: usize) -> Result<usize> {
+    match updated {
+        0 => Err(TrsError::NotFound(format!("No article with id {}", id))),
+        updated => Ok(updated),
+    }
+}
+
+fn folder_name(name: &str) -> Result<&str> {
+    match name.trim() {
+        "" => Err(TrsError::Error("A folder needs a name".to_string())),
+        name => Ok(name),
     }
 }
 
+/// Quote every word so that nothing typed is taken for FTS5 syntax, `None`
+/// when there's nothing to search for
+fn fts_query(query: &str) -> Option<String> {
+    let words = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    match words.is_empty() {
+        true => None,
+        false => Some(words.join(" ")),
+    }
+}
+
+/// Lists are stored comma separated
+fn join<T: ToString>(values: &[T]) -> Option<String> {
+    match values.is_empty() {
+        true => None,
+        false => Some(
+            values
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+    }
+}
+
+fn split<T>(value: Option<String>, parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|item| parse(item.trim()))
+        .collect()
+}
+
 impl RssArticleD {
-    fn dummy() -> Self {
+    /// What to show when reading the article: the full post if the feed
+    /// provided one, otherwise the summary
+    pub fn body(&self) -> &str {
+        match self.content.is_empty() {
+            true => &self.description,
+            false => &self.content,
+        }
+    }
+
+    pub fn dummy() -> Self {
         RssArticleD {
             id: -1,
             channel_id: 0,
@@ -327,6 +1610,623 @@ impl RssArticleD {
             pub_date: None,
             last_update: None,
             unread: false,
+            guid: String::new(),
+            content: String::new(),
+            starred: false,
+            starred_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(title: &str, link: &str, articles: Vec<RssArticle>) -> RssChannel {
+        RssChannel {
+            title: title.to_string(),
+            link: link.to_string(),
+            description: format!("{} description", title),
+            ttl: None,
+            skip_hours: Vec::new(),
+            skip_days: Vec::new(),
+            articles,
+        }
+    }
+
+    fn article(guid: &str, link: &str) -> RssArticle {
+        RssArticle {
+            title: format!("Article {}", guid),
+            link: link.to_string(),
+            description: String::new(),
+            content: String::new(),
+            guid: guid.to_string(),
+            date: None,
         }
     }
+
+    #[test]
+    fn db_path_resolution() {
+        let home = Some(PathBuf::from("/home/me"));
+        let resolve = |db: Option<&str>, profile: Option<&str>, db_env: Option<&str>, data_home| {
+            resolve_db_path_with(
+                db.map(Path::new),
+                profile,
+                db_env.map(PathBuf::from),
+                data_home,
+                home.clone(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            resolve(None, None, None, None),
+            PathBuf::from("/home/me/.config/trs/default.db")
+        );
+        assert_eq!(
+            resolve(None, Some("work"), None, Some(PathBuf::from("/data"))),
+            PathBuf::from("/data/trs/work.db")
+        );
+        assert_eq!(
+            resolve(None, None, Some("/tmp/env.db"), None),
+            PathBuf::from("/tmp/env.db")
+        );
+        assert_eq!(
+            resolve(None, Some("work"), Some("/tmp/env.db"), None),
+            PathBuf::from("/home/me/.config/trs/work.db")
+        );
+        assert_eq!(
+            resolve(
+                Some("/tmp/explicit.db"),
+                Some("work"),
+                Some("/tmp/env.db"),
+                None
+            ),
+            PathBuf::from("/tmp/explicit.db")
+        );
+        assert!(resolve_db_path_with(None, Some("../x"), None, None, home.clone()).is_err());
+    }
+
+    #[test]
+    fn legacy_db_is_moved_to_the_default_profile() {
+        let dir = std::env::temp_dir().join(format!("trs-legacy-test-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        let legacy = dir.join(".config").join("trs").join(LEGACY_DB_FILE);
+        std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        std::fs::write(&legacy, "old").unwrap();
+        let db_path = dir.join("data").join("trs").join("default.db");
+
+        assert_eq!(adopt_legacy_db(&legacy, db_path.clone()), db_path);
+        assert!(!legacy.exists());
+        assert_eq!(std::fs::read_to_string(&db_path).unwrap(), "old");
+
+        // The default profile's database wins over one left behind
+        std::fs::write(&legacy, "older").unwrap();
+        assert_eq!(adopt_legacy_db(&legacy, db_path.clone()), db_path);
+        assert_eq!(std::fs::read_to_string(&db_path).unwrap(), "old");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn articles_are_unique_per_channel() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let shared_link = "https://example.com/shared";
+        let first = db
+            .add_channel(
+                "https://one.example.com/feed",
+                &channel(
+                    "One",
+                    "https://one.example.com",
+                    vec![article("a", shared_link)],
+                ),
+            )
+            .unwrap();
+        let second = db
+            .add_channel(
+                "https://two.example.com/feed",
+                &channel(
+                    "Two",
+                    "https://two.example.com",
+                    vec![article("b", shared_link)],
+                ),
+            )
+            .unwrap();
+
+        assert_eq!(first.articles.len(), 1);
+        assert_eq!(second.articles.len(), 1);
+        assert_ne!(first.articles[0].id, second.articles[0].id);
+        assert_eq!(second.articles[0].channel_id, second.id);
+
+        // Syncing again doesn't create duplicates
+        let first = db
+            .add_channel(
+                "https://one.example.com/feed",
+                &channel(
+                    "One",
+                    "https://one.example.com",
+                    vec![article("a", shared_link)],
+                ),
+            )
+            .unwrap();
+        assert_eq!(first.articles.len(), 1);
+        assert_eq!(db.list_articles().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sync_reports_new_and_updated_articles() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let feed = |articles| channel("Example", "https://example.com", articles);
+        let (_, report) = db
+            .sync_channel(
+                "https://example.com/feed",
+                &feed(vec![article("a", "https://example.com/a")]),
+            )
+            .unwrap();
+        assert_eq!(
+            report,
+            SyncReport {
+                new: 1,
+                ..Default::default()
+            }
+        );
+
+        let mut edited = article("a", "https://example.com/a");
+        edited.title = "Edited title".to_string();
+        let (channel, report) = db
+            .sync_channel(
+                "https://example.com/feed",
+                &feed(vec![edited, article("b", "https://example.com/b")]),
+            )
+            .unwrap();
+        assert_eq!(
+            report,
+            SyncReport {
+                new: 1,
+                updated: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(channel.articles[0].title, "Edited title");
+
+        let (_, report) = db
+            .sync_channel(
+                "https://example.com/feed",
+                &feed(vec![article("b", "https://example.com/b")]),
+            )
+            .unwrap();
+        assert_eq!(report, SyncReport::default());
+
+        // Content arriving later counts as an update, and is read back
+        let mut full = article("b", "https://example.com/b");
+        full.content = "<p>The whole post</p>".to_string();
+        let (channel, report) = db
+            .sync_channel("https://example.com/feed", &feed(vec![full]))
+            .unwrap();
+        assert_eq!(report.updated, 1);
+        let b = channel.articles.iter().find(|a| a.guid == "b").unwrap();
+        assert_eq!(b.body(), "<p>The whole post</p>");
+    }
+
+    #[test]
+    fn cache_headers_are_stored() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let added = db
+            .add_channel(
+                "https://example.com/feed",
+                &channel("Example", "https://example.com", vec![]),
+            )
+            .unwrap();
+        assert_eq!(added.etag, None);
+
+        db.set_channel_cache_headers(
+            added.id,
+            Some("\"abc\""),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT"),
+        )
+        .unwrap();
+        let channel = db.get_channel(added.id).unwrap();
+        assert_eq!(channel.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(
+            channel.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+    }
+
+    #[test]
+    fn articles_without_guid_or_link() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let mut no_guid = article("", "https://example.com/post");
+        no_guid.title = "Has a link".to_string();
+        let mut no_link = article("", "");
+        no_link.title = "Only a title".to_string();
+
+        let added = db
+            .add_channel(
+                "https://example.com/feed",
+                &channel("Example", "https://example.com", vec![no_guid, no_link]),
+            )
+            .unwrap();
+
+        assert_eq!(added.articles.len(), 2);
+        assert_eq!(added.articles[0].guid, "https://example.com/post");
+        assert_eq!(added.articles[1].guid, "Only a title");
+    }
+
+    #[test]
+    fn starred_articles() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let added = db
+            .add_channel(
+                "https://example.com/feed",
+                &channel(
+                    "Example",
+                    "https://example.com",
+                    vec![
+                        article("a", "https://example.com/a"),
+                        article("b", "https://example.com/b"),
+                    ],
+                ),
+            )
+            .unwrap();
+        let id = added.articles[0].id;
+
+        db.star_article(id).unwrap();
+        let starred = db.get_article_by_id(id).unwrap();
+        assert!(starred.starred);
+        assert!(starred.starred_at.is_some());
+        assert_eq!(db.starred_article_ids().unwrap(), vec![id]);
+
+        let query = ArticleQuery {
+            starred: true,
+            limit: 10,
+            ..Default::default()
+        };
+        let page = db.query_articles(&query).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.articles[0].id, id);
+
+        db.unstar_article(id).unwrap();
+        let unstarred = db.get_article_by_id(id).unwrap();
+        assert!(!unstarred.starred);
+        assert_eq!(unstarred.starred_at, None);
+        assert_eq!(db.query_articles(&query).unwrap().total, 0);
+    }
+
+    #[test]
+    fn search_follows_article_changes() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let feed = |description: &str| {
+            let mut rust = article("rust", "https://example.com/rust");
+            rust.title = "Rust 2024".to_string();
+            let mut gardening = article("garden", "https://example.com/garden");
+            gardening.title = "Gardening".to_string();
+            gardening.description = description.to_string();
+            channel("Example", "https://example.com", vec![rust, gardening])
+        };
+        db.add_channel(
+            "https://example.com/feed",
+            &feed("Growing \"rusty\" tomatoes"),
+        )
+        .unwrap();
+
+        // Title matches rank first, words match as prefixes
+        let hits = db.search_articles("rust", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].article.title, "Rust 2024");
+        assert_eq!(
+            hits[1].snippet,
+            format!("Growing \"{}rusty{}\" tomatoes", MATCH_START, MATCH_END)
+        );
+
+        // Quotes and operators are searched for, not parsed
+        assert_eq!(db.search_articles("\"rusty", 10).unwrap().len(), 1);
+        assert!(db.search_articles("  ", 10).unwrap().is_empty());
+        assert!(db.search_articles("rust NOT", 10).unwrap().is_empty());
+
+        let (channel, _) = db
+            .sync_channel(
+                "https://example.com/feed",
+                &feed("<p class=\"rusty\">Growing <em>tomatoes</em></p>"),
+            )
+            .unwrap();
+        assert_eq!(db.search_articles("rust", 10).unwrap().len(), 1);
+        let hits = db.search_articles("tomato", 10).unwrap();
+        assert_eq!(
+            hits[0].snippet,
+            format!("Growing {}tomatoes{}", MATCH_START, MATCH_END)
+        );
+        assert!(db.search_articles("class", 10).unwrap().is_empty());
+
+        db.remove_channel(channel.id as u32).unwrap();
+        assert!(db.search_articles("tomato", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn folders_group_channels() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let channel = db
+            .add_channel(
+                "https://example.com/feed",
+                &channel(
+                    "Example",
+                    "https://example.com",
+                    vec![article("a", "https://example.com/a")],
+                ),
+            )
+            .unwrap();
+
+        let folder = db.add_folder(" News ").unwrap();
+        assert_eq!(folder.name, "News");
+        assert!(db.add_folder("News").is_err());
+        assert!(db.add_folder("  ").is_err());
+        assert_eq!(db.folder_named("News").unwrap(), folder);
+
+        db.set_channel_folder(channel.id, Some(folder.id)).unwrap();
+        assert!(db.set_channel_folder(channel.id, Some(42)).is_err());
+        assert_eq!(db.list_channels(10).unwrap()[0].folder_id, Some(folder.id));
+
+        let query = ArticleQuery {
+            folder_id: Some(folder.id),
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(db.query_articles(&query).unwrap().total, 1);
+
+        db.add_folder("Blogs").unwrap();
+        assert!(db.rename_folder(folder.id, "Blogs").is_err());
+        db.rename_folder(folder.id, "World").unwrap();
+        let names = db
+            .list_folders()
+            .unwrap()
+            .into_iter()
+            .map(|folder| folder.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Blogs", "World"]);
+
+        // The channels stay, out of any folder
+        db.remove_folder(folder.id).unwrap();
+        assert!(db.remove_folder(folder.id).is_err());
+        assert_eq!(db.list_channels(10).unwrap()[0].folder_id, None);
+        assert_eq!(db.query_articles(&query).unwrap().total, 0);
+    }
+
+    #[test]
+    fn prune_keeps_starred_and_unread_articles() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let now = OffsetDateTime::now_utc();
+        // "a" is published today, "e" four days ago
+        let feed = |guids: &[&str]| {
+            let articles = guids
+                .iter()
+                .map(|guid| {
+                    let mut article = article(guid, &format!("https://example.com/{}", guid));
+                    let age = (guid.as_bytes()[0] - b'a') as i64;
+                    article.date = Some(now - Duration::days(age));
+                    article
+                })
+                .collect();
+            channel("Example", "https://example.com", articles)
+        };
+        let added = db
+            .add_channel(
+                "https://example.com/feed",
+                &feed(&["a", "b", "c", "d", "e"]),
+            )
+            .unwrap();
+        let count = || {
+            let query = ArticleQuery {
+                channel_id: Some(added.id),
+                limit: 10,
+                ..Default::default()
+            };
+            db.query_articles(&query).unwrap().total
+        };
+        let id = |guid: &str| {
+            added
+                .articles
+                .iter()
+                .find(|article| article.guid == guid)
+                .unwrap()
+                .id
+        };
+        for guid in ["b", "c", "d"] {
+            db.mark_article_read(id(guid)).unwrap();
+        }
+        db.star_article(id("d")).unwrap();
+
+        // Nothing goes without settings
+        assert!(db.prune(false, now).unwrap().is_empty());
+
+        db.set_retention_settings(&RetentionSettings {
+            defaults: Retention {
+                keep_articles: Some(2),
+                keep_days: None,
+            },
+            after_refresh: false,
+        })
+        .unwrap();
+        let reports = db.prune(true, now).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!((reports[0].channel_id, reports[0].articles), (added.id, 1));
+        assert_eq!(count(), 5);
+
+        db.prune(false, now).unwrap();
+        assert!(db.get_article_by_id(id("c")).is_err());
+        assert_eq!(count(), 4);
+
+        // Pruned articles don't come back while they're in the feed
+        let (_, report) = db
+            .sync_channel(
+                "https://example.com/feed",
+                &feed(&["a", "b", "c", "d", "e"]),
+            )
+            .unwrap();
+        assert_eq!(report.new, 0);
+        db.sync_channel("https://example.com/feed", &feed(&["a"]))
+            .unwrap();
+        let (_, report) = db
+            .sync_channel("https://example.com/feed", &feed(&["a", "c"]))
+            .unwrap();
+        assert_eq!(report.new, 1);
+
+        // A channel's own settings win, 0 lifting the global limit
+        db.set_channel_retention(
+            added.id,
+            &Retention {
+                keep_articles: Some(0),
+                keep_days: Some(3),
+            },
+        )
+        .unwrap();
+        assert!(db.prune(false, now).unwrap().is_empty());
+        db.mark_article_read(id("e")).unwrap();
+        assert_eq!(db.prune(false, now).unwrap()[0].articles, 1);
+        assert!(db.get_article_by_id(id("e")).is_err());
+        assert!(db.get_article_by_id(id("d")).unwrap().starred);
+    }
+
+    #[test]
+    fn removed_channels_take_their_articles() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let kept = db
+            .add_channel(
+                "https://example.com/feed",
+                &channel(
+                    "Example",
+                    "https://example.com",
+                    vec![article("a", "https://example.com/a")],
+                ),
+            )
+            .unwrap();
+        let removed = db
+            .add_channel(
+                "https://other.example.com/feed",
+                &channel(
+                    "Other",
+                    "https://other.example.com",
+                    vec![article("b", "https://other.example.com/b")],
+                ),
+            )
+            .unwrap();
+
+        db.remove_channel(removed.id as u32).unwrap();
+        assert_eq!(db.unread_article_ids().unwrap(), vec![kept.articles[0].id]);
+        assert!(db.get_article_by_id(removed.articles[0].id).is_err());
+        let query = ArticleQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(db.query_articles(&query).unwrap().total, 1);
+        assert_eq!(db.search_articles("article", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn migrated_articles_keep_their_read_state() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(include_str!("../sample/baseline.sql"))
+            .unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        let db = Db::from_connection(connection).unwrap();
+
+        // The ploeh feed's guids lack the trailing slash of its links
+        let feed = parser::parse_feed(None, include_bytes!("../sample/rss2.xml")).unwrap();
+        let (channel, report) = db
+            .sync_channel("https://blog.ploeh.dk/rss.xml", &feed)
+            .unwrap();
+        assert_eq!(report.new, feed.articles.len() - 2);
+
+        let read = db.get_article_by_id(4).unwrap();
+        assert_eq!(
+            read.guid,
+            "https://blog.ploeh.dk/2025/06/09/song-recommendations-from-combinators"
+        );
+        assert!(!read.unread);
+        assert!(db.get_article_by_id(3).unwrap().unread);
+        assert_eq!(channel.articles.len(), feed.articles.len());
+    }
+
+    #[test]
+    fn refresh_follows_the_channel_not_its_link() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let articles = || vec![article("a", "https://example.com/a")];
+        let added = db
+            .add_channel(
+                "https://example.com/feed",
+                &channel("Example", "http://example.com", articles()),
+            )
+            .unwrap();
+
+        // The site moved to https
+        let (refreshed, report) = db
+            .refresh_channel(
+                added.id,
+                &channel("Example", "https://example.com/", articles()),
+            )
+            .unwrap();
+        assert_eq!(refreshed.id, added.id);
+        assert_eq!(refreshed.link, "https://example.com/");
+        assert_eq!(report.new, 0);
+        assert_eq!(db.list_channels(10).unwrap().len(), 1);
+
+        assert!(db
+            .refresh_channel(added.id + 1, &channel("Gone", "", Vec::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn remote_subscriptions_keep_their_own_channel() {
+        let db = Db::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let posts = db
+            .sync_remote_channel(
+                "feed/1",
+                "Posts",
+                "https://example.com",
+                "https://example.com/feed",
+            )
+            .unwrap();
+        let comments = db
+            .sync_remote_channel(
+                "feed/2",
+                "Comments",
+                "https://example.com",
+                "https://example.com/comments/feed",
+            )
+            .unwrap();
+        assert_ne!(posts, comments);
+
+        // Syncing again finds the same channels
+        for _ in 0..2 {
+            assert_eq!(
+                db.sync_remote_channel(
+                    "feed/1",
+                    "Posts",
+                    "https://example.com",
+                    "https://example.com/feed"
+                )
+                .unwrap(),
+                posts
+            );
+            assert_eq!(
+                db.sync_remote_channel(
+                    "feed/2",
+                    "Comments",
+                    "https://example.com",
+                    "https://example.com/comments/feed",
+                )
+                .unwrap(),
+                comments
+            );
+        }
+
+        // A feed added by hand shares its site's link too
+        let added = db
+            .add_channel(
+                "https://example.com/other/feed",
+                &channel("Other", "https://example.com", Vec::new()),
+            )
+            .unwrap();
+        assert!(added.id != posts && added.id != comments);
+        assert_eq!(db.list_channels(10).unwrap().len(), 3);
+    }
 }
@@ -0,0 +1,373 @@
+use rusqlite::{functions::FunctionFlags, Connection};
+
+use crate::error::Result;
+use crate::error::TrsError;
+use crate::html;
+
+/// A schema change, applied in its own transaction. The schema version of a
+/// database is stored in `PRAGMA user_version` and is the number of
+/// migrations applied to it, so migrations must only ever be appended.
+pub struct Migration {
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        // Databases created before migrations existed already have these
+        // tables, with user_version still at 0
+        description: "Create Channels and Articles tables",
+        sql: "CREATE TABLE IF NOT EXISTS Channels ( \
+            id INTEGER PRIMARY KEY, \
+            name TEXT NOT NULL, \
+            link TEXT NOT NULL UNIQUE, \
+            feed_link TEXT NOT NULL UNIQUE, \
+            description TEXT, \
+            last_update INTEGER\
+        ); \
+        CREATE TABLE IF NOT EXISTS Articles ( \
+            id INTEGER PRIMARY KEY, \
+            channel_id INTEGER NOT NULL, \
+            title TEXT NOT NULL, \
+            description TEXT, \
+            link TEXT NOT NULL UNIQUE, \
+            pub_date INTEGER, \
+            last_update INTEGER , \
+            unread BOOLEAN DEFAULT TRUE, \
+            FOREIGN KEY(channel_id) REFERENCES Channels(id) ON DELETE CASCADE \
+        );",
+    },
+    Migration {
+        description: "Identify articles by guid within their channel",
+        sql: "CREATE TABLE Articles_new ( \
+            id INTEGER PRIMARY KEY, \
+            channel_id INTEGER NOT NULL, \
+            guid TEXT NOT NULL, \
+            title TEXT NOT NULL, \
+            description TEXT, \
+            link TEXT NOT NULL, \
+            pub_date INTEGER, \
+            last_update INTEGER , \
+            unread BOOLEAN DEFAULT TRUE, \
+            UNIQUE(channel_id, guid), \
+            FOREIGN KEY(channel_id) REFERENCES Channels(id) ON DELETE CASCADE \
+        ); \
+        INSERT INTO Articles_new (id, channel_id, guid, title, description, link, pub_date, last_update, unread) \
+            SELECT id, channel_id, link, title, description, link, pub_date, last_update, unread FROM Articles; \
+        DROP TABLE Articles; \
+        ALTER TABLE Articles_new RENAME TO Articles;",
+    },
+    Migration {
+        description: "Remember HTTP cache validators of channels",
+        sql: "ALTER TABLE Channels ADD COLUMN etag TEXT; \
+        ALTER TABLE Channels ADD COLUMN last_modified TEXT;",
+    },
+    Migration {
+        description: "Store the full content of articles apart from their summary",
+        sql: "ALTER TABLE Articles ADD COLUMN content TEXT;",
+    },
+    Migration {
+        description: "Schedule refreshes from feed caching hints and user intervals",
+        sql: "ALTER TABLE Channels ADD COLUMN ttl INTEGER; \
+        ALTER TABLE Channels ADD COLUMN skip_hours TEXT; \
+        ALTER TABLE Channels ADD COLUMN skip_days TEXT; \
+        ALTER TABLE Channels ADD COLUMN refresh_interval INTEGER; \
+        ALTER TABLE Channels ADD COLUMN last_fetch INTEGER;",
+    },
+    Migration {
+        description: "Sync with a remote reader and queue changes made offline",
+        sql: "ALTER TABLE Channels ADD COLUMN remote_id TEXT; \
+        ALTER TABLE Articles ADD COLUMN remote_id INTEGER; \
+        CREATE UNIQUE INDEX Articles_remote_id ON Articles(remote_id); \
+        CREATE TABLE Remote ( \
+            id INTEGER PRIMARY KEY CHECK (id = 1), \
+            url TEXT NOT NULL, \
+            user TEXT NOT NULL, \
+            last_sync INTEGER \
+        ); \
+        CREATE TABLE PendingOperations ( \
+            article_id INTEGER PRIMARY KEY, \
+            unread BOOLEAN NOT NULL, \
+            created_at INTEGER NOT NULL, \
+            FOREIGN KEY(article_id) REFERENCES Articles(id) ON DELETE CASCADE \
+        );",
+    },
+    Migration {
+        description: "Star articles, and sync stars as well as read state",
+        sql: "ALTER TABLE Articles ADD COLUMN starred BOOLEAN NOT NULL DEFAULT FALSE; \
+        ALTER TABLE Articles ADD COLUMN starred_at INTEGER; \
+        CREATE TABLE PendingOperations_new ( \
+            article_id INTEGER NOT NULL, \
+            tag TEXT NOT NULL, \
+            added BOOLEAN NOT NULL, \
+            created_at INTEGER NOT NULL, \
+            PRIMARY KEY(article_id, tag), \
+            FOREIGN KEY(article_id) REFERENCES Articles(id) ON DELETE CASCADE \
+        ); \
+        INSERT INTO PendingOperations_new (article_id, tag, added, created_at) \
+            SELECT article_id, 'read', NOT unread, created_at FROM PendingOperations; \
+        DROP TABLE PendingOperations; \
+        ALTER TABLE PendingOperations_new RENAME TO PendingOperations;",
+    },
+    Migration {
+        description: "Index the text of articles for full-text search",
+        sql: "CREATE VIRTUAL TABLE ArticlesSearch USING fts5( \
+            title, description, content, \
+            content='Articles', content_rowid='id', tokenize='porter unicode61' \
+        ); \
+        CREATE TRIGGER Articles_search_insert AFTER INSERT ON Articles BEGIN \
+            INSERT INTO ArticlesSearch (rowid, title, description, content) \
+                VALUES (new.id, new.title, new.description, new.content); \
+        END; \
+        CREATE TRIGGER Articles_search_delete AFTER DELETE ON Articles BEGIN \
+            INSERT INTO ArticlesSearch (ArticlesSearch, rowid, title, description, content) \
+                VALUES ('delete', old.id, old.title, old.description, old.content); \
+        END; \
+        CREATE TRIGGER Articles_search_update AFTER UPDATE OF title, description, content ON Articles \
+            WHEN old.title IS NOT new.title OR old.description IS NOT new.description OR old.content IS NOT new.content \
+        BEGIN \
+            INSERT INTO ArticlesSearch (ArticlesSearch, rowid, title, description, content) \
+                VALUES ('delete', old.id, old.title, old.description, old.content); \
+            INSERT INTO ArticlesSearch (rowid, title, description, content) \
+                VALUES (new.id, new.title, new.description, new.content); \
+        END; \
+        INSERT INTO ArticlesSearch (ArticlesSearch) VALUES ('rebuild');",
+    },
+    Migration {
+        description: "Group channels in folders",
+        sql: "CREATE TABLE Folders ( \
+            id INTEGER PRIMARY KEY, \
+            name TEXT NOT NULL UNIQUE \
+        ); \
+        ALTER TABLE Channels ADD COLUMN folder_id INTEGER REFERENCES Folders(id) ON DELETE SET NULL;",
+    },
+    Migration {
+        description: "Prune old articles, by default or per channel",
+        sql: "ALTER TABLE Channels ADD COLUMN keep_articles INTEGER; \
+        ALTER TABLE Channels ADD COLUMN keep_days INTEGER; \
+        CREATE TABLE Retention ( \
+            id INTEGER PRIMARY KEY CHECK (id = 1), \
+            keep_articles INTEGER, \
+            keep_days INTEGER, \
+            after_refresh BOOLEAN NOT NULL DEFAULT FALSE \
+        ); \
+        INSERT INTO Retention (id) VALUES (1); \
+        CREATE TABLE PrunedArticles ( \
+            channel_id INTEGER NOT NULL, \
+            guid TEXT NOT NULL, \
+            PRIMARY KEY(channel_id, guid) \
+        );",
+    },
+    Migration {
+        // Foreign keys weren't enforced before, so removed channels left
+        // their articles behind
+        description: "Drop rows left behind by removed channels and folders",
+        sql: "DELETE FROM Articles WHERE channel_id NOT IN (SELECT id FROM Channels); \
+        DELETE FROM PendingOperations WHERE article_id NOT IN (SELECT id FROM Articles); \
+        UPDATE Channels SET folder_id = NULL WHERE folder_id NOT IN (SELECT id FROM Folders); \
+        CREATE TABLE PrunedArticles_new ( \
+            channel_id INTEGER NOT NULL, \
+            guid TEXT NOT NULL, \
+            PRIMARY KEY(channel_id, guid), \
+            FOREIGN KEY(channel_id) REFERENCES Channels(id) ON DELETE CASCADE \
+        ); \
+        INSERT INTO PrunedArticles_new (channel_id, guid) \
+            SELECT channel_id, guid FROM PrunedArticles WHERE channel_id IN (SELECT id FROM Channels); \
+        DROP TABLE PrunedArticles; \
+        ALTER TABLE PrunedArticles_new RENAME TO PrunedArticles;",
+    },
+    Migration {
+        // Feeds of the same site (posts and comments) share its link
+        description: "Identify channels by their feed, or their remote subscription",
+        sql: "CREATE TABLE Channels_new ( \
+            id INTEGER PRIMARY KEY, \
+            name TEXT NOT NULL, \
+            link TEXT NOT NULL, \
+            feed_link TEXT NOT NULL UNIQUE, \
+            description TEXT, \
+            last_update INTEGER, \
+            etag TEXT, \
+            last_modified TEXT, \
+            ttl INTEGER, \
+            skip_hours TEXT, \
+            skip_days TEXT, \
+            refresh_interval INTEGER, \
+            last_fetch INTEGER, \
+            remote_id TEXT, \
+            folder_id INTEGER REFERENCES Folders(id) ON DELETE SET NULL, \
+            keep_articles INTEGER, \
+            keep_days INTEGER \
+        ); \
+        INSERT INTO Channels_new (id, name, link, feed_link, description, last_update, etag, last_modified, \
+            ttl, skip_hours, skip_days, refresh_interval, last_fetch, remote_id, folder_id, keep_articles, keep_days) \
+            SELECT id, name, link, feed_link, description, last_update, etag, last_modified, \
+            ttl, skip_hours, skip_days, refresh_interval, last_fetch, remote_id, folder_id, keep_articles, keep_days \
+            FROM Channels; \
+        DROP TABLE Channels; \
+        ALTER TABLE Channels_new RENAME TO Channels; \
+        CREATE UNIQUE INDEX Channels_remote_id ON Channels(remote_id);",
+    },
+    Migration {
+        // The index used to hold the HTML, so tag and attribute names matched
+        // and snippets were cut out of the markup
+        description: "Index the text of articles without their markup",
+        sql: "DROP TRIGGER Articles_search_insert; \
+        DROP TRIGGER Articles_search_delete; \
+        DROP TRIGGER Articles_search_update; \
+        DROP TABLE ArticlesSearch; \
+        CREATE VIRTUAL TABLE ArticlesSearch USING fts5( \
+            title, description, content, tokenize='porter unicode61' \
+        ); \
+        CREATE TRIGGER Articles_search_insert AFTER INSERT ON Articles BEGIN \
+            INSERT INTO ArticlesSearch (rowid, title, description, content) \
+                VALUES (new.id, new.title, html_text(new.description), html_text(new.content)); \
+        END; \
+        CREATE TRIGGER Articles_search_delete AFTER DELETE ON Articles BEGIN \
+            DELETE FROM ArticlesSearch WHERE rowid = old.id; \
+        END; \
+        CREATE TRIGGER Articles_search_update AFTER UPDATE OF title, description, content ON Articles \
+            WHEN old.title IS NOT new.title OR old.description IS NOT new.description OR old.content IS NOT new.content \
+        BEGIN \
+            UPDATE ArticlesSearch SET title = new.title, description = html_text(new.description), \
+                content = html_text(new.content) WHERE rowid = new.id; \
+        END; \
+        INSERT INTO ArticlesSearch (rowid, title, description, content) \
+            SELECT id, title, html_text(description), html_text(content) FROM Articles;",
+    },
+];
+
+pub fn schema_version(connection: &Connection) -> Result<usize> {
+    connection
+        .pragma_query_value(None, "user_version", |row| row.get::<usize, i64>(0))
+        .map(|version| version as usize)
+        .map_err(|e| TrsError::SqlError(e, "Failed to read schema version".to_string()))
+}
+
+/// The search index triggers call these, so every connection writing
+/// articles needs them
+fn add_functions(connection: &Connection) -> Result<()> {
+    connection
+        .create_scalar_function(
+            "html_text",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|html| html::text(&html))),
+        )
+        .map_err(|e| TrsError::SqlError(e, "Failed to add sql functions".to_string()))
+}
+
+/// Bring the database up to date by applying every migration it's missing
+pub fn migrate(connection: &mut Connection) -> Result<()> {
+    add_functions(connection)?;
+    let version = schema_version(connection)?;
+    if version > MIGRATIONS.len() {
+        return Err(TrsError::Error(format!(
+            "Database schema version {} is newer than this version of trs supports ({})",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+
+    // Tables are rebuilt by dropping the old one, which must not cascade to
+    // the rows referencing it. This can't be changed within a transaction.
+    connection
+        .pragma_update(None, "foreign_keys", false)
+        .map_err(|e| TrsError::SqlError(e, "Failed to disable foreign keys".to_string()))?;
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let failed = |e| {
+            TrsError::SqlError(
+                e,
+                format!("Migration {} failed: {}", idx + 1, migration.description),
+            )
+        };
+
+        let tx = connection.transaction().map_err(failed)?;
+        tx.execute_batch(migration.sql).map_err(failed)?;
+        tx.pragma_update(None, "user_version", (idx + 1) as i64)
+            .map_err(failed)?;
+        tx.commit().map_err(failed)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline_db() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(include_str!("../../sample/baseline.sql"))
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn fresh_database_is_fully_migrated() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), MIGRATIONS.len());
+
+        // Nothing left to do the second time around
+        migrate(&mut connection).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn baseline_database_is_upgraded() {
+        let mut connection = baseline_db();
+        assert_eq!(schema_version(&connection).unwrap(), 0);
+
+        migrate(&mut connection).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), MIGRATIONS.len());
+
+        let channels: i64 = connection
+            .query_row("SELECT COUNT(*) FROM Channels", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(channels, 2);
+
+        // Read history survives
+        let (guid, unread): (String, bool) = connection
+            .query_row(
+                "SELECT guid, unread FROM Articles WHERE id = 2",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(guid, "https://brycev.com/blog/blogging-on-paper/");
+        assert!(!unread);
+
+        let articles: i64 = connection
+            .query_row("SELECT COUNT(*) FROM Articles", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(articles, 4);
+
+        // Articles from before the search index are indexed too
+        let found: i64 = connection
+            .query_row(
+                "SELECT rowid FROM ArticlesSearch WHERE ArticlesSearch MATCH 'paper'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(found, 2);
+        let markup: i64 = connection
+            .query_row(
+                "SELECT COUNT(*) FROM ArticlesSearch WHERE ArticlesSearch MATCH 'p'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(markup, 0);
+    }
+
+    #[test]
+    fn newer_database_is_rejected() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "user_version", (MIGRATIONS.len() + 1) as i64)
+            .unwrap();
+        assert!(migrate(&mut connection).is_err());
+    }
+}
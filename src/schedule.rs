@@ -0,0 +1,115 @@
+use time::{Duration, OffsetDateTime};
+
+use crate::persistence::RssChannelD;
+
+/// Minutes between automatic refreshes of a channel without its own interval
+pub const DEFAULT_REFRESH_INTERVAL: u32 = 60;
+
+/// How often to look for channels due for a background refresh
+pub const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long to wait between downloads of `channel`. An interval set by the
+/// user wins; otherwise the feed's `<ttl>` is respected when it asks for
+/// longer than the default.
+pub fn refresh_interval(channel: &RssChannelD, default_minutes: u32) -> Duration {
+    let minutes = channel
+        .refresh_interval
+        .unwrap_or_else(|| channel.ttl.unwrap_or(0).max(default_minutes));
+    Duration::minutes(minutes as i64)
+}
+
+/// Whether `channel` should be downloaded again at `now`
+pub fn is_due(channel: &RssChannelD, now: OffsetDateTime, default_minutes: u32) -> bool {
+    // skipHours and skipDays are in GMT
+    let now_utc = now.to_offset(time::UtcOffset::UTC);
+    if channel.skip_hours.contains(&now_utc.hour())
+        || channel.skip_days.contains(&now_utc.weekday())
+    {
+        return false;
+    }
+
+    match channel.last_fetch {
+        Some(last_fetch) => now - last_fetch >= refresh_interval(channel, default_minutes),
+        None => true,
+    }
+}
+
+/// Ids of the channels that are due for a refresh
+pub fn due_channels(
+    channels: &[RssChannelD],
+    now: OffsetDateTime,
+    default_minutes: u32,
+) -> Vec<u32> {
+    channels
+        .iter()
+        .filter(|channel| is_due(channel, now, default_minutes))
+        .map(|channel| channel.id as u32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Weekday};
+
+    use super::*;
+
+    fn channel(last_fetch: Option<OffsetDateTime>) -> RssChannelD {
+        RssChannelD {
+            id: 1,
+            title: "Example".to_string(),
+            link: "https://example.com".to_string(),
+            feed_link: "https://example.com/feed".to_string(),
+            description: String::new(),
+            last_update: datetime!(2025-06-01 00:00 UTC),
+            etag: None,
+            last_modified: None,
+            ttl: None,
+            skip_hours: Vec::new(),
+            skip_days: Vec::new(),
+            refresh_interval: None,
+            last_fetch,
+            folder_id: None,
+            retention: Default::default(),
+            articles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn intervals() {
+        // Sunday
+        let now = datetime!(2025-06-01 12:00 UTC);
+        assert!(is_due(&channel(None), now, 60));
+
+        let mut fetched = channel(Some(datetime!(2025-06-01 11:30 UTC)));
+        assert!(!is_due(&fetched, now, 60));
+        assert!(is_due(&fetched, now, 30));
+
+        // A longer ttl is respected, a shorter one doesn't speed things up
+        fetched.ttl = Some(120);
+        assert!(!is_due(&fetched, now, 30));
+        fetched.ttl = Some(5);
+        assert!(!is_due(&fetched, now, 60));
+
+        // The user knows best
+        fetched.refresh_interval = Some(10);
+        assert!(is_due(&fetched, now, 60));
+    }
+
+    #[test]
+    fn skip_hours_and_days_are_in_gmt() {
+        let mut skipping = channel(None);
+        skipping.skip_hours = vec![12];
+        assert!(!is_due(&skipping, datetime!(2025-06-01 12:59 UTC), 60));
+        assert!(!is_due(&skipping, datetime!(2025-06-01 14:30 +02:00), 60));
+        assert!(is_due(&skipping, datetime!(2025-06-01 13:00 UTC), 60));
+
+        skipping.skip_hours.clear();
+        skipping.skip_days = vec![Weekday::Sunday];
+        assert!(!is_due(&skipping, datetime!(2025-06-01 13:00 UTC), 60));
+        assert!(is_due(&skipping, datetime!(2025-06-02 00:00 UTC), 60));
+        assert_eq!(
+            due_channels(&[skipping], datetime!(2025-06-02 00:00 UTC), 60),
+            vec![1]
+        );
+    }
+}
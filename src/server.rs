@@ -0,0 +1,292 @@
+pub mod fever;
+pub mod greader;
+pub mod rest;
+
+use std::{convert::Infallible, env, path::Path, sync::Arc};
+
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    header,
+    server::conn::http1,
+    service::service_fn,
+    Method, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::TcpListener;
+
+use crate::{
+    args::{ListChannelArgs, ServeArgs},
+    commands::TrsEnv,
+    daemon,
+    error::{Result, TrsError},
+    ipc::{Request, Response, Worker},
+    persistence::{Folder, RssChannelD},
+};
+
+/// Environment variable holding the token clients have to send
+pub const TOKEN_ENV_VAR: &str = "TRS_TOKEN";
+
+/// Nothing the API accepts comes close to this
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+pub type HttpRequest = hyper::Request<Incoming>;
+pub type HttpResponse = hyper::Response<Full<Bytes>>;
+
+pub struct Server {
+    worker: Worker,
+    user: String,
+    token: String,
+}
+
+/// Serve the database over HTTP until interrupted
+pub async fn run(args: &ServeArgs, db_path: &Path) -> Result<()> {
+    let token = args
+        .token
+        .clone()
+        .or_else(|| env::var(TOKEN_ENV_VAR).ok())
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| TrsError::Error(format!("Pass --token or set ${}", TOKEN_ENV_VAR)))?;
+    let listener = TcpListener::bind(&args.bind)
+        .await
+        .map_err(|e| TrsError::IoError(e, format!("Unable to listen on {}", args.bind)))?;
+    let server = Server::new(TrsEnv::new(db_path)?, args.user.clone(), token);
+
+    println!("Listening on http://{}", args.bind);
+    tokio::select! {
+        result = server.serve(listener) => result,
+        result = daemon::shutdown() => result,
+    }
+}
+
+impl Server {
+    pub fn new(ctx: TrsEnv, user: String, token: String) -> Arc<Self> {
+        Arc::new(Server {
+            worker: Worker::start(ctx),
+            user,
+            token,
+        })
+    }
+
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, _) = listener
+                .accept()
+                .await
+                .map_err(|e| TrsError::IoError(e, "Unable to accept a client".to_string()))?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(|request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                });
+                // A client hanging up halfway through is its own problem
+                _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    }
+
+    async fn handle(&self, request: HttpRequest) -> HttpResponse {
+        let path = request.uri().path().to_string();
+        // Fever clients authenticate in the request itself
+        if path.trim_end_matches('/') == "/fever" {
+            return fever::handle(self, request)
+                .await
+                .unwrap_or_else(|e| error_response(&e));
+        }
+
+        if path.starts_with("/accounts/") || path.starts_with("/reader/") {
+            return greader::handle(self, request, &path)
+                .await
+                .unwrap_or_else(|e| error_response(&e));
+        }
+
+        let Some(route) = path.strip_prefix("/api/") else {
+            return error(StatusCode::NOT_FOUND, &format!("No route for {}", path));
+        };
+
+        if !self.authorized(&request) {
+            return error(StatusCode::UNAUTHORIZED, "Missing or wrong token");
+        }
+
+        rest::handle(&self.worker, request, route)
+            .await
+            .unwrap_or_else(|e| error_response(&e))
+    }
+
+    /// Expects `Authorization: Bearer <token>`
+    fn authorized(&self, request: &HttpRequest) -> bool {
+        request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| same_token(token, &self.token))
+    }
+}
+
+/// Compares in constant time, so the token can't be guessed a byte at a time
+pub fn same_token(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub fn json<T: Serialize>(status: StatusCode, body: &T) -> HttpResponse {
+    match serde_json::to_vec(body) {
+        Ok(body) => hyper::Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .unwrap(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+pub fn text(status: StatusCode, body: String) -> HttpResponse {
+    hyper::Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+pub fn no_content() -> HttpResponse {
+    hyper::Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Full::default())
+        .unwrap()
+}
+
+pub fn error(status: StatusCode, message: &str) -> HttpResponse {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+fn error_response(e: &TrsError) -> HttpResponse {
+    let status = match e {
+        TrsError::NotFound(_) => StatusCode::NOT_FOUND,
+        TrsError::Error(_) | TrsError::MultipleFeeds(_) | TrsError::SerdeJsonError(..) => {
+            StatusCode::BAD_REQUEST
+        }
+        // The feed's server, not ours
+        TrsError::ReqwestError(..) | TrsError::XmlRsError(..) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    error(status, &e.to_string())
+}
+
+pub async fn read_body(request: HttpRequest) -> Result<Bytes> {
+    Limited::new(request.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map(|body| body.to_bytes())
+        .map_err(|e| TrsError::Error(format!("Unable to read the request body: {}", e)))
+}
+
+pub async fn read_json<T: DeserializeOwned>(request: HttpRequest) -> Result<T> {
+    let body = read_body(request).await?;
+    serde_json::from_slice(&body)
+        .map_err(|e| TrsError::SerdeJsonError(e, "Invalid request body".to_string()))
+}
+
+/// The decoded query string
+pub fn query_params(request: &HttpRequest) -> Vec<(String, String)> {
+    form_urlencoded::parse(request.uri().query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect()
+}
+
+/// The query string followed by the form fields of a POST, for the APIs
+/// that take their parameters from either
+pub async fn request_params(request: HttpRequest) -> Result<Vec<(String, String)>> {
+    let mut params = query_params(&request);
+    if request.method() == Method::POST {
+        let body = read_body(request).await?;
+        params.extend(form_urlencoded::parse(&body).into_owned());
+    }
+
+    Ok(params)
+}
+
+pub fn parse_param<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| TrsError::Error(format!("Invalid {}: {}", name, value)))
+}
+
+/// Every channel, with its articles
+pub async fn channels(worker: &Worker) -> Result<Vec<RssChannelD>> {
+    match worker
+        .call(Request::ListChannels(ListChannelArgs { limit: None }))
+        .await?
+    {
+        Response::Channels(channels) => Ok(channels),
+        _ => Err(unexpected()),
+    }
+}
+
+pub async fn folders(worker: &Worker) -> Result<Vec<Folder>> {
+    match worker.call(Request::ListFolders).await? {
+        Response::Folders(folders) => Ok(folders),
+        _ => Err(unexpected()),
+    }
+}
+
+pub fn unexpected() -> TrsError {
+    TrsError::Error("Unexpected response from the database worker".to_string())
+}
+
+#[cfg(test)]
+pub mod test_support {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{parser, persistence::Db};
+
+    pub const USER: &str = "reader";
+    pub const TOKEN: &str = "secret";
+
+    /// A server on a random port, over a database holding the sample RSS
+    /// feed
+    pub struct TestServer {
+        pub url: String,
+        db_path: PathBuf,
+    }
+
+    impl TestServer {
+        pub async fn start(name: &str) -> Self {
+            let db_path =
+                std::env::temp_dir().join(format!("trs-{}-test-{}.db", name, std::process::id()));
+            _ = std::fs::remove_file(&db_path);
+            let feed = parser::parse_feed(None, include_bytes!("../sample/rss2.xml")).unwrap();
+            let db = Db::create(&db_path).unwrap();
+            let (channel, _) = db.sync_channel("http://localhost/rss2.xml", &feed).unwrap();
+            let folder = db.add_folder("Blogs").unwrap();
+            db.set_channel_folder(channel.id, Some(folder.id)).unwrap();
+            drop(db);
+
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let server = Server::new(
+                TrsEnv::new(&db_path).unwrap(),
+                USER.to_string(),
+                TOKEN.to_string(),
+            );
+            tokio::spawn(server.serve(listener));
+            TestServer { url, db_path }
+        }
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.db_path);
+        }
+    }
+}
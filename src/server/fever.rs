@@ -0,0 +1,396 @@
+use hyper::StatusCode;
+use md5::{Digest, Md5};
+use serde_json::{json, Map, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    args,
+    error::{Result, TrsError},
+    ipc::{Request, Response, Worker},
+    persistence::{ArticleIds, RssChannelD},
+};
+
+use super::{channels, folders, json, parse_param, request_params, same_token, unexpected};
+use super::{HttpRequest, HttpResponse, Server};
+
+const API_VERSION: u32 = 3;
+
+/// Fever hands out items 50 at a time
+const ITEMS_PER_REQUEST: u32 = 50;
+
+/// The Fever API (https://feedafever.com/api): one endpoint, the query
+/// string says what to return and the form body carries the credentials
+/// and any changes
+pub async fn handle(server: &Server, request: HttpRequest) -> Result<HttpResponse> {
+    let params = request_params(request).await?;
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let has = |name: &str| param(name).is_some();
+
+    let mut response = Map::new();
+    response.insert("api_version".to_string(), json!(API_VERSION));
+    // The api key is md5("user:password")
+    let expected = md5_hex(format!("{}:{}", server.user, server.token).as_bytes());
+    let authorized = param("api_key")
+        .is_some_and(|api_key| same_token(&api_key.to_ascii_lowercase(), &expected));
+    response.insert("auth".to_string(), json!(authorized as u8));
+    if !authorized {
+        return Ok(json(StatusCode::OK, &response));
+    }
+
+    let worker = &server.worker;
+    if let Some(kind) = param("mark") {
+        mark(worker, kind, param("as"), param("id"), param("before")).await?;
+    }
+
+    let channels = channels(worker).await?;
+    let last_refreshed = channels
+        .iter()
+        .filter_map(|channel| channel.last_fetch)
+        .max()
+        .map_or(0, |fetched| fetched.unix_timestamp());
+    response.insert("last_refreshed_on_time".to_string(), json!(last_refreshed));
+
+    // Groups are folders
+    if has("groups") {
+        let groups = folders(worker)
+            .await?
+            .iter()
+            .map(|folder| json!({ "id": folder.id, "title": folder.name }))
+            .collect::<Vec<_>>();
+        response.insert("groups".to_string(), Value::Array(groups));
+    }
+    if has("feeds") {
+        let feeds = channels
+            .iter()
+            .map(|channel| {
+                json!({
+                    "id": channel.id,
+                    "favicon_id": 0,
+                    "title": channel.title,
+                    "url": channel.feed_link,
+                    "site_url": channel.link,
+                    "is_spark": 0,
+                    "last_updated_on_time": channel.last_update.unix_timestamp(),
+                })
+            })
+            .collect::<Vec<_>>();
+        response.insert("feeds".to_string(), Value::Array(feeds));
+    }
+    if has("groups") || has("feeds") {
+        response.insert("feeds_groups".to_string(), feeds_groups(&channels));
+    }
+    if has("favicons") {
+        response.insert("favicons".to_string(), json!([]));
+    }
+    if has("links") {
+        response.insert("links".to_string(), json!([]));
+    }
+    if has("items") {
+        let (total, items) = items(
+            worker,
+            param("since_id"),
+            param("max_id"),
+            param("with_ids"),
+        )
+        .await?;
+        response.insert("total_items".to_string(), json!(total));
+        response.insert("items".to_string(), Value::Array(items));
+    }
+    if has("unread_item_ids") || has("mark") {
+        let ids = match worker.call(Request::UnreadArticleIds).await? {
+            Response::Ids(ids) => ids,
+            _ => return Err(unexpected()),
+        };
+        response.insert("unread_item_ids".to_string(), json!(join_ids(&ids)));
+    }
+    if has("saved_item_ids") || has("mark") {
+        let ids = match worker.call(Request::StarredArticleIds).await? {
+            Response::Ids(ids) => ids,
+            _ => return Err(unexpected()),
+        };
+        response.insert("saved_item_ids".to_string(), json!(join_ids(&ids)));
+    }
+
+    Ok(json(StatusCode::OK, &response))
+}
+
+/// Channels outside of any folder aren't in any group
+fn feeds_groups(channels: &[RssChannelD]) -> Value {
+    let mut groups: Vec<(i64, Vec<i64>)> = Vec::new();
+    for channel in channels {
+        let Some(folder_id) = channel.folder_id else {
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(group_id, _)| *group_id == folder_id)
+        {
+            Some((_, ids)) => ids.push(channel.id),
+            None => groups.push((folder_id, vec![channel.id])),
+        }
+    }
+
+    groups
+        .iter()
+        .map(|(group_id, ids)| json!({ "group_id": group_id, "feed_ids": join_ids(ids) }))
+        .collect()
+}
+
+/// `with_ids` wins over `max_id`, which wins over `since_id`
+async fn items(
+    worker: &Worker,
+    since_id: Option<&str>,
+    max_id: Option<&str>,
+    with_ids: Option<&str>,
+) -> Result<(usize, Vec<Value>)> {
+    let ids = match (with_ids, max_id) {
+        (Some(with_ids), _) => ArticleIds::Only(
+            with_ids
+                .split(',')
+                .map(|id| parse_param("with_ids", id.trim()))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        (None, Some(max_id)) => ArticleIds::Before(parse_param("max_id", max_id)?),
+        (None, None) => {
+            ArticleIds::After(since_id.map_or(Ok(0), |id| parse_param("since_id", id))?)
+        }
+    };
+
+    let page = match worker
+        .call(Request::ArticlesById {
+            ids,
+            limit: ITEMS_PER_REQUEST,
+        })
+        .await?
+    {
+        Response::Articles(page) => page,
+        _ => return Err(unexpected()),
+    };
+
+    let items = page
+        .articles
+        .iter()
+        .map(|article| {
+            let created = article.pub_date.or(article.last_update);
+            json!({
+                "id": article.id,
+                "feed_id": article.channel_id,
+                "title": article.title,
+                "author": "",
+                "html": article.body(),
+                "url": article.link,
+                "is_saved": article.starred as u8,
+                "is_read": (!article.unread) as u8,
+                "created_on_time": created.map_or(0, |created| created.unix_timestamp()),
+            })
+        })
+        .collect();
+    Ok((page.total, items))
+}
+
+/// `mark=item|feed|group`, `as=read|unread|saved|unsaved`, `id`, and for
+/// feeds and groups `before`, only items up to then are marked read
+async fn mark(
+    worker: &Worker,
+    kind: &str,
+    mark_as: Option<&str>,
+    id: Option<&str>,
+    before: Option<&str>,
+) -> Result<()> {
+    let id: i64 = parse_param("id", id.unwrap_or_default())?;
+    let before = match before {
+        Some(before) => parse_param("before", before)?,
+        None => OffsetDateTime::now_utc().unix_timestamp(),
+    };
+
+    let item_id =
+        || u32::try_from(id).map_err(|_| TrsError::NotFound(format!("No article with id {}", id)));
+    // A group is read a channel at a time
+    if (kind, mark_as) == ("group", Some("read")) && id > 0 {
+        for channel in channels(worker).await? {
+            if channel.folder_id == Some(id) {
+                worker
+                    .call(Request::MarkAllRead {
+                        channel_id: Some(channel.id),
+                        before,
+                    })
+                    .await?;
+            }
+        }
+        return Ok(());
+    }
+
+    let request = match (kind, mark_as.unwrap_or_default()) {
+        ("item", "read" | "unread") => Request::MarkRead(args::MarkReadArgs {
+            id: item_id()?,
+            unread: mark_as == Some("unread"),
+        }),
+        ("item", "saved" | "unsaved") => Request::Star(args::StarArgs {
+            id: item_id()?,
+            unstar: mark_as == Some("unsaved"),
+        }),
+        ("feed", "read") => Request::MarkAllRead {
+            channel_id: Some(id),
+            before,
+        },
+        // 0 is every item, -1 the "sparks" which trs doesn't have
+        ("group", "read") if id == 0 => Request::MarkAllRead {
+            channel_id: None,
+            before,
+        },
+        ("group", "read") => return Ok(()),
+        (kind, mark_as) => {
+            return Err(TrsError::Error(format!(
+                "Unable to mark {} as {}",
+                kind, mark_as
+            )))
+        }
+    };
+
+    worker.call(request).await.map(|_| ())
+}
+
+fn join_ids(ids: &[i64]) -> String {
+    ids.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn md5_hex(input: &[u8]) -> String {
+    Md5::digest(input)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::super::test_support::{TestServer, TOKEN, USER};
+    use super::*;
+
+    async fn fever(server: &TestServer, query: &str, form: &[(&str, &str)]) -> Value {
+        let api_key = md5_hex(format!("{}:{}", USER, TOKEN).as_bytes());
+        let mut form = form.to_vec();
+        form.push(("api_key", &api_key));
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(form)
+            .finish();
+        let response = reqwest::Client::new()
+            .post(format!("{}/fever/?api&{}", server.url, query))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fever_api() {
+        let server = TestServer::start("fever").await;
+        let response = reqwest::Client::new()
+            .post(format!("{}/fever/?api", server.url))
+            .body("api_key=wrong")
+            .send()
+            .await
+            .unwrap();
+        let denied: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(denied["auth"], 0);
+
+        let feeds = fever(&server, "feeds&groups", &[]).await;
+        assert_eq!(feeds["auth"], 1);
+        assert_eq!(feeds["api_version"], 3);
+        assert_eq!(feeds["feeds"][0]["title"], "ploeh blog");
+        let feed_id = feeds["feeds"][0]["id"].as_i64().unwrap();
+        assert_eq!(feeds["groups"][0]["title"], "Blogs");
+        let group_id = feeds["groups"][0]["id"].as_i64().unwrap();
+        assert_eq!(feeds["feeds_groups"][0]["group_id"], group_id);
+        assert_eq!(feeds["feeds_groups"][0]["feed_ids"], feed_id.to_string());
+
+        let items = fever(&server, "items&since_id=0", &[]).await;
+        assert_eq!(items["total_items"], 10);
+        let ids = items["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 10);
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let newest_first = fever(&server, &format!("items&max_id={}", ids[3]), &[]).await;
+        assert_eq!(newest_first["items"][0]["id"], ids[2]);
+        assert_eq!(newest_first["items"].as_array().unwrap().len(), 3);
+        let picked = fever(
+            &server,
+            &format!("items&with_ids={},{}", ids[0], ids[9]),
+            &[],
+        )
+        .await;
+        assert_eq!(picked["items"].as_array().unwrap().len(), 2);
+
+        let id = ids[0].to_string();
+        let marked = fever(
+            &server,
+            "",
+            &[("mark", "item"), ("as", "read"), ("id", &id)],
+        )
+        .await;
+        let unread = marked["unread_item_ids"].as_str().unwrap();
+        assert_eq!(unread.split(',').count(), 9);
+        assert!(!unread.split(',').any(|unread| unread == id));
+
+        let saved = fever(
+            &server,
+            "",
+            &[("mark", "item"), ("as", "saved"), ("id", &id)],
+        )
+        .await;
+        assert_eq!(saved["saved_item_ids"], id);
+        let picked = fever(&server, &format!("items&with_ids={}", id), &[]).await;
+        assert_eq!(picked["items"][0]["is_saved"], 1);
+
+        let feed = feed_id.to_string();
+        let before = OffsetDateTime::now_utc().unix_timestamp().to_string();
+        let marked = fever(
+            &server,
+            "",
+            &[
+                ("mark", "feed"),
+                ("as", "read"),
+                ("id", &feed),
+                ("before", &before),
+            ],
+        )
+        .await;
+        assert_eq!(marked["unread_item_ids"], "");
+
+        fever(
+            &server,
+            "",
+            &[("mark", "item"), ("as", "unread"), ("id", &id)],
+        )
+        .await;
+        let group = group_id.to_string();
+        let marked = fever(
+            &server,
+            "",
+            &[
+                ("mark", "group"),
+                ("as", "read"),
+                ("id", &group),
+                ("before", &before),
+            ],
+        )
+        .await;
+        assert_eq!(marked["unread_item_ids"], "");
+    }
+}
@@ -0,0 +1,667 @@
+use std::collections::HashMap;
+
+use hyper::{header, StatusCode};
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    args,
+    error::{Result, TrsError},
+    ipc::{Request, Response, Worker},
+    persistence::{ArticleIds, ArticlePage, ArticleQuery, Folder, RssArticleD},
+};
+
+use super::{channels, folders, json, parse_param, request_params, same_token, text, unexpected};
+use super::{HttpRequest, HttpResponse, Server};
+
+const READING_LIST: &str = "user/-/state/com.google/reading-list";
+const READ: &str = "user/-/state/com.google/read";
+const STARRED: &str = "user/-/state/com.google/starred";
+/// Folders are labels, followed by the folder name
+const LABEL_PREFIX: &str = "user/-/label/";
+
+/// Long form of an item id, the short form is the same number in decimal
+const ITEM_ID_PREFIX: &str = "tag:google.com,2005:reader/item/";
+
+const DEFAULT_COUNT: u32 = 20;
+const MAX_COUNT: u32 = 1000;
+/// Clients ask for many more ids than items at a time
+const MAX_ID_COUNT: u32 = 10000;
+
+/// What a stream id points at
+#[derive(Debug, PartialEq)]
+enum Stream {
+    ReadingList,
+    Starred,
+    Feed(i64),
+    /// The channels in a folder
+    Label(String),
+}
+
+/// The Google Reader API as FreshRSS and Miniflux serve it: log in at
+/// `/accounts/ClientLogin`, then `/reader/api/0/...` with the token it
+/// returned in `Authorization: GoogleLogin auth=<token>`
+pub async fn handle(server: &Server, request: HttpRequest, path: &str) -> Result<HttpResponse> {
+    if path == "/accounts/ClientLogin" {
+        let params = request_params(request).await?;
+        return Ok(client_login(server, &params));
+    }
+
+    let Some(route) = path.strip_prefix("/reader/api/0/") else {
+        return Err(TrsError::NotFound(format!("No route for {}", path)));
+    };
+    if !authorized(server, &request) {
+        return Ok(text(StatusCode::UNAUTHORIZED, "Unauthorized\n".to_string()));
+    }
+
+    // Stream ids end up in the path, slashes and all
+    let route = percent_decode_str(route).decode_utf8_lossy().into_owned();
+    let params = request_params(request).await?;
+    let worker = &server.worker;
+    match route.as_str() {
+        // Only there to be sent back with edits
+        "token" => Ok(text(StatusCode::OK, server.token.clone())),
+        "user-info" => Ok(json(
+            StatusCode::OK,
+            &json!({
+                "userId": "1",
+                "userName": server.user,
+                "userProfileId": "1",
+                "userEmail": "",
+            }),
+        )),
+        "subscription/list" => subscriptions(worker).await,
+        "tag/list" => tags(worker).await,
+        "stream/contents" => {
+            let stream = param(&params, "s").unwrap_or(READING_LIST);
+            stream_contents(worker, stream, &params).await
+        }
+        "stream/items/ids" => {
+            let stream = param(&params, "s").unwrap_or(READING_LIST);
+            stream_item_ids(worker, stream, &params).await
+        }
+        "stream/items/contents" => items_contents(worker, &params).await,
+        "edit-tag" => edit_tag(worker, &params).await,
+        "mark-all-as-read" => mark_all_as_read(worker, &params).await,
+        route => match route.strip_prefix("stream/contents/") {
+            Some(stream) => stream_contents(worker, stream, &params).await,
+            None => Err(TrsError::NotFound(format!("No route for {}", path))),
+        },
+    }
+}
+
+fn client_login(server: &Server, params: &[(String, String)]) -> HttpResponse {
+    let user = param(params, "Email").unwrap_or_default();
+    let password = param(params, "Passwd").unwrap_or_default();
+    if user != server.user || !same_token(password, &server.token) {
+        return text(
+            StatusCode::UNAUTHORIZED,
+            "Error=BadAuthentication\n".to_string(),
+        );
+    }
+
+    text(
+        StatusCode::OK,
+        format!(
+            "SID={token}\nLSID=null\nAuth={token}\n",
+            token = server.token
+        ),
+    )
+}
+
+fn authorized(server: &Server, request: &HttpRequest) -> bool {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("GoogleLogin auth="))
+        .is_some_and(|token| same_token(token, &server.token))
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(param, _)| param == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn params<'a>(params: &'a [(String, String)], name: &'a str) -> impl Iterator<Item = &'a str> {
+    params
+        .iter()
+        .filter(move |(param, _)| param == name)
+        .map(|(_, value)| value.as_str())
+}
+
+pub fn long_item_id(id: i64) -> String {
+    format!("{}{:016x}", ITEM_ID_PREFIX, id)
+}
+
+/// Items come back in either form
+pub fn parse_item_id(id: &str) -> Result<i64> {
+    let parsed = match id.strip_prefix(ITEM_ID_PREFIX) {
+        Some(hex) => u64::from_str_radix(hex, 16).map(|id| id as i64).ok(),
+        None => id.parse().ok(),
+    };
+    parsed.ok_or_else(|| TrsError::Error(format!("Invalid item id: {}", id)))
+}
+
+fn parse_stream(id: &str) -> Result<Stream> {
+    match id {
+        READING_LIST => return Ok(Stream::ReadingList),
+        STARRED => return Ok(Stream::Starred),
+        _ => {}
+    }
+
+    // Clients spell the user either as `-` or with their id
+    if let Some((_, label)) = id
+        .strip_prefix("user/")
+        .and_then(|id| id.split_once("/label/"))
+    {
+        return Ok(Stream::Label(label.to_string()));
+    }
+
+    id.strip_prefix("feed/")
+        .and_then(|channel_id| channel_id.parse().ok())
+        .map(Stream::Feed)
+        .ok_or_else(|| TrsError::NotFound(format!("Unknown stream {}", id)))
+}
+
+fn label(folder: &Folder) -> String {
+    format!("{}{}", LABEL_PREFIX, folder.name)
+}
+
+/// The folder a label stream stands for
+async fn label_folder(worker: &Worker, name: &str) -> Result<Folder> {
+    folders(worker)
+        .await?
+        .into_iter()
+        .find(|folder| folder.name == name)
+        .ok_or_else(|| TrsError::NotFound(format!("No folder named {}", name)))
+}
+
+/// `n` items after continuation `c`, `xt` excludes read ones, `r=o` is
+/// oldest first and `ot`/`nt` bound the publication time
+fn stream_query(
+    stream: &Stream,
+    params: &[(String, String)],
+    max_count: u32,
+) -> Result<ArticleQuery> {
+    let mut query = ArticleQuery {
+        channel_id: match stream {
+            Stream::ReadingList | Stream::Starred | Stream::Label(_) => None,
+            Stream::Feed(channel_id) => Some(*channel_id),
+        },
+        starred: *stream == Stream::Starred,
+        limit: DEFAULT_COUNT,
+        ..Default::default()
+    };
+    for (name, value) in params {
+        match name.as_str() {
+            "n" => query.limit = parse_param::<u32>(name, value)?.clamp(1, max_count),
+            "c" => query.after = Some(parse_continuation(value)?),
+            "xt" if value == READ => query.unread = true,
+            "r" => query.oldest_first = value == "o",
+            "ot" => query.newer_than = Some(parse_param(name, value)?),
+            "nt" => query.older_than = Some(parse_param(name, value)?),
+            _ => {}
+        }
+    }
+
+    Ok(query)
+}
+
+/// The query for `stream_id`, with its folder looked up for labels
+async fn query(
+    worker: &Worker,
+    stream_id: &str,
+    params: &[(String, String)],
+    max_count: u32,
+) -> Result<ArticleQuery> {
+    let stream = parse_stream(stream_id)?;
+    let mut query = stream_query(&stream, params, max_count)?;
+    if let Stream::Label(name) = &stream {
+        query.folder_id = Some(label_folder(worker, name).await?.id);
+    }
+
+    Ok(query)
+}
+
+/// Points past the last item of the page, while there are more. Items
+/// marked read in between pages don't make the next page skip any.
+fn continuation(page: &ArticlePage) -> Option<String> {
+    let last = page.articles.last()?;
+    let (date, id) = ArticleQuery::position(last);
+    (page.articles.len() < page.total).then(|| format!("{}_{}", date, id))
+}
+
+fn parse_continuation(value: &str) -> Result<(i64, i64)> {
+    let (date, id) = value
+        .split_once('_')
+        .ok_or_else(|| TrsError::Error(format!("Invalid c: {}", value)))?;
+    Ok((parse_param("c", date)?, parse_param("c", id)?))
+}
+
+async fn articles(worker: &Worker, request: Request) -> Result<ArticlePage> {
+    match worker.call(request).await? {
+        Response::Articles(page) => Ok(page),
+        _ => Err(unexpected()),
+    }
+}
+
+/// The starred state and a label per folder
+async fn tags(worker: &Worker) -> Result<HttpResponse> {
+    let mut tags = vec![json!({ "id": STARRED })];
+    tags.extend(
+        folders(worker)
+            .await?
+            .iter()
+            .map(|folder| json!({ "id": label(folder), "type": "folder" })),
+    );
+    Ok(json(StatusCode::OK, &json!({ "tags": tags })))
+}
+
+async fn subscriptions(worker: &Worker) -> Result<HttpResponse> {
+    let folders = folders(worker).await?;
+    let subscriptions = channels(worker)
+        .await?
+        .iter()
+        .map(|channel| {
+            let categories = folders
+                .iter()
+                .filter(|folder| Some(folder.id) == channel.folder_id)
+                .map(|folder| json!({ "id": label(folder), "label": folder.name }))
+                .collect::<Vec<_>>();
+            json!({
+                "id": format!("feed/{}", channel.id),
+                "title": channel.title,
+                "categories": categories,
+                "url": channel.feed_link,
+                "htmlUrl": channel.link,
+                "iconUrl": "",
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json(
+        StatusCode::OK,
+        &json!({ "subscriptions": subscriptions }),
+    ))
+}
+
+async fn stream_contents(
+    worker: &Worker,
+    stream_id: &str,
+    params: &[(String, String)],
+) -> Result<HttpResponse> {
+    let query = query(worker, stream_id, params, MAX_COUNT).await?;
+    let page = articles(worker, Request::ListArticles(query.clone())).await?;
+    let mut contents = items(worker, stream_id, &page.articles).await?;
+    if let Some(continuation) = continuation(&page) {
+        contents["continuation"] = json!(continuation);
+    }
+
+    Ok(json(StatusCode::OK, &contents))
+}
+
+async fn stream_item_ids(
+    worker: &Worker,
+    stream_id: &str,
+    params: &[(String, String)],
+) -> Result<HttpResponse> {
+    let query = query(worker, stream_id, params, MAX_ID_COUNT).await?;
+    let page = articles(worker, Request::ListArticles(query.clone())).await?;
+    let item_refs = page
+        .articles
+        .iter()
+        .map(|article| json!({ "id": article.id.to_string() }))
+        .collect::<Vec<_>>();
+    let mut ids = json!({ "itemRefs": item_refs });
+    if let Some(continuation) = continuation(&page) {
+        ids["continuation"] = json!(continuation);
+    }
+
+    Ok(json(StatusCode::OK, &ids))
+}
+
+/// The items picked by the `i` parameters
+async fn items_contents(worker: &Worker, params: &[(String, String)]) -> Result<HttpResponse> {
+    let ids = self::params(params, "i")
+        .map(parse_item_id)
+        .collect::<Result<Vec<_>>>()?;
+    let limit = ids.len() as u32;
+    let page = articles(
+        worker,
+        Request::ArticlesById {
+            ids: ArticleIds::Only(ids),
+            limit,
+        },
+    )
+    .await?;
+    Ok(json(
+        StatusCode::OK,
+        &items(worker, READING_LIST, &page.articles).await?,
+    ))
+}
+
+/// Only the channels of the page are looked up, and the folders when any of
+/// them is in one
+async fn items(worker: &Worker, stream_id: &str, articles: &[RssArticleD]) -> Result<Value> {
+    let mut ids = articles
+        .iter()
+        .map(|article| article.channel_id)
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    let channels = match ids.is_empty() {
+        true => Vec::new(),
+        false => match worker.call(Request::ChannelsById { ids }).await? {
+            Response::Channels(channels) => channels,
+            _ => return Err(unexpected()),
+        },
+    };
+    let channels = channels
+        .iter()
+        .map(|channel| (channel.id, channel))
+        .collect::<HashMap<_, _>>();
+    let labels = match channels.values().any(|channel| channel.folder_id.is_some()) {
+        true => folders(worker)
+            .await?
+            .iter()
+            .map(|folder| (folder.id, label(folder)))
+            .collect::<HashMap<_, _>>(),
+        false => HashMap::new(),
+    };
+
+    let items = articles
+        .iter()
+        .map(|article| {
+            let published = article
+                .pub_date
+                .or(article.last_update)
+                .map_or(0, |date| date.unix_timestamp());
+            let crawled = article
+                .last_update
+                .map_or(published, |date| date.unix_timestamp());
+            let channel = channels.get(&article.channel_id);
+            let mut categories = vec![READING_LIST];
+            if let Some(label) = channel
+                .and_then(|channel| channel.folder_id)
+                .and_then(|folder_id| labels.get(&folder_id))
+            {
+                categories.push(label);
+            }
+            if !article.unread {
+                categories.push(READ);
+            }
+            if article.starred {
+                categories.push(STARRED);
+            }
+
+            json!({
+                "id": long_item_id(article.id),
+                "crawlTimeMsec": (crawled * 1000).to_string(),
+                "timestampUsec": (published * 1_000_000).to_string(),
+                "published": published,
+                "updated": published,
+                "title": article.title,
+                "author": "",
+                "canonical": [{ "href": article.link }],
+                "alternate": [{ "href": article.link, "type": "text/html" }],
+                "summary": { "content": article.body() },
+                "categories": categories,
+                "origin": {
+                    "streamId": format!("feed/{}", article.channel_id),
+                    "title": channel.map(|channel| channel.title.as_str()),
+                    "htmlUrl": channel.map(|channel| channel.link.as_str()),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "id": stream_id,
+        "updated": OffsetDateTime::now_utc().unix_timestamp(),
+        "items": items,
+    }))
+}
+
+/// What adding or removing a state tag does to an item
+#[derive(Clone, Copy)]
+enum Edit {
+    MarkRead { unread: bool },
+    Star { unstar: bool },
+}
+
+impl Edit {
+    /// Clients spell the user either as `-` or with their id
+    fn parse(tag: &str, added: bool) -> Result<Self> {
+        match tag.split_once("/state/").map(|(_, state)| state) {
+            Some("com.google/read") => Ok(Edit::MarkRead { unread: !added }),
+            Some("com.google/kept-unread") => Ok(Edit::MarkRead { unread: added }),
+            Some("com.google/starred") => Ok(Edit::Star { unstar: !added }),
+            _ => Err(TrsError::Error(format!("Unsupported tag {}", tag))),
+        }
+    }
+
+    fn request(self, id: u32) -> Request {
+        match self {
+            Edit::MarkRead { unread } => Request::MarkRead(args::MarkReadArgs { id, unread }),
+            Edit::Star { unstar } => Request::Star(args::StarArgs { id, unstar }),
+        }
+    }
+}
+
+/// Adds (`a`) or removes (`r`) tags on the items in `i`
+async fn edit_tag(worker: &Worker, params: &[(String, String)]) -> Result<HttpResponse> {
+    let edits = self::params(params, "a")
+        .map(|tag| Edit::parse(tag, true))
+        .chain(self::params(params, "r").map(|tag| Edit::parse(tag, false)))
+        .collect::<Result<Vec<_>>>()?;
+    if edits.is_empty() {
+        return Err(TrsError::Error("Pass a tag to add or remove".to_string()));
+    }
+
+    for id in self::params(params, "i") {
+        let id = parse_item_id(id)?;
+        let id = u32::try_from(id)
+            .map_err(|_| TrsError::NotFound(format!("No article with id {}", id)))?;
+        for edit in &edits {
+            worker.call(edit.request(id)).await?;
+        }
+    }
+
+    Ok(text(StatusCode::OK, "OK".to_string()))
+}
+
+/// Everything in stream `s` up to `ts`, in microseconds
+async fn mark_all_as_read(worker: &Worker, params: &[(String, String)]) -> Result<HttpResponse> {
+    let before = match param(params, "ts") {
+        Some(ts) => parse_param::<i64>("ts", ts)? / 1_000_000,
+        None => OffsetDateTime::now_utc().unix_timestamp(),
+    };
+    let channel_ids = match parse_stream(param(params, "s").unwrap_or(READING_LIST))? {
+        Stream::ReadingList => vec![None],
+        Stream::Feed(channel_id) => vec![Some(channel_id)],
+        // A folder is read a channel at a time
+        Stream::Label(name) => {
+            let folder = label_folder(worker, &name).await?;
+            channels(worker)
+                .await?
+                .iter()
+                .filter(|channel| channel.folder_id == Some(folder.id))
+                .map(|channel| Some(channel.id))
+                .collect()
+        }
+        Stream::Starred => {
+            return Err(TrsError::Error(
+                "Only feeds, folders and the reading list can be marked read".to_string(),
+            ))
+        }
+    };
+
+    for channel_id in channel_ids {
+        worker
+            .call(Request::MarkAllRead { channel_id, before })
+            .await?;
+    }
+    Ok(text(StatusCode::OK, "OK".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::super::test_support::{TestServer, TOKEN, USER};
+    use super::*;
+
+    #[test]
+    fn item_ids() {
+        assert_eq!(
+            long_item_id(31),
+            "tag:google.com,2005:reader/item/000000000000001f"
+        );
+        assert_eq!(parse_item_id(&long_item_id(31)).unwrap(), 31);
+        assert_eq!(parse_item_id("31").unwrap(), 31);
+        assert_eq!(parse_stream("feed/3").unwrap(), Stream::Feed(3),);
+        assert_eq!(
+            parse_stream("user/1/label/Blogs").unwrap(),
+            Stream::Label("Blogs".to_string())
+        );
+        assert!(parse_item_id("tag:google.com,2005:reader/item/xyz").is_err());
+    }
+
+    async fn get(server: &TestServer, path: &str) -> Value {
+        let response = reqwest::Client::new()
+            .get(format!("{}/reader/api/0/{}", server.url, path))
+            .header("Authorization", format!("GoogleLogin auth={}", TOKEN))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn greader_api() {
+        let server = TestServer::start("greader").await;
+        let client = reqwest::Client::new();
+        let login = |password: &str| {
+            client
+                .post(format!("{}/accounts/ClientLogin", server.url))
+                .body(format!("Email={}&Passwd={}", USER, password))
+                .send()
+        };
+        assert_eq!(login("wrong").await.unwrap().status().as_u16(), 401);
+        let response = login(TOKEN).await.unwrap();
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains(&format!("Auth={}\n", TOKEN)));
+
+        let subscriptions = get(&server, "subscription/list?output=json").await;
+        let feed = subscriptions["subscriptions"][0]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(subscriptions["subscriptions"][0]["title"], "ploeh blog");
+        assert_eq!(
+            subscriptions["subscriptions"][0]["categories"][0]["id"],
+            "user/-/label/Blogs"
+        );
+        let tags = get(&server, "tag/list?output=json").await;
+        assert!(tags["tags"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({ "id": "user/-/label/Blogs", "type": "folder" })));
+        let folder = get(&server, "stream/contents/user/-/label/Blogs?n=100").await;
+        assert_eq!(folder["items"].as_array().unwrap().len(), 10);
+
+        // Page through the reading list with continuations
+        let first = get(
+            &server,
+            "stream/contents/user%2F-%2Fstate%2Fcom.google%2Freading-list?n=4",
+        )
+        .await;
+        assert_eq!(first["items"].as_array().unwrap().len(), 4);
+        assert_eq!(first["items"][0]["origin"]["streamId"], feed);
+        assert_eq!(first["items"][0]["origin"]["title"], "ploeh blog");
+        assert!(first["items"][0]["categories"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("user/-/label/Blogs")));
+        let continuation = first["continuation"].as_str().unwrap();
+        let rest = get(
+            &server,
+            &format!("stream/contents/{}?n=10&c={}", feed, continuation),
+        )
+        .await;
+        assert_eq!(rest["items"].as_array().unwrap().len(), 6);
+        assert!(rest.get("continuation").is_none());
+
+        let ids = get(&server, &format!("stream/items/ids?s={}&n=100", feed)).await;
+        let first_id: i64 = ids["itemRefs"][0]["id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(first["items"][0]["id"], long_item_id(first_id));
+
+        let response = client
+            .post(format!("{}/reader/api/0/edit-tag", server.url))
+            .header("Authorization", format!("GoogleLogin auth={}", TOKEN))
+            .body(format!(
+                "i={}&a=user/-/state/com.google/read&a=user/1/state/com.google/starred",
+                long_item_id(first_id)
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "OK");
+        let unread = get(
+            &server,
+            "stream/items/ids?s=user/-/state/com.google/reading-list&xt=user/-/state/com.google/read&n=100",
+        )
+        .await;
+        assert_eq!(unread["itemRefs"].as_array().unwrap().len(), 9);
+        let starred = get(&server, "stream/contents/user/-/state/com.google/starred").await;
+        assert_eq!(starred["items"].as_array().unwrap().len(), 1);
+        assert!(starred["items"][0]["categories"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("user/-/state/com.google/starred")));
+
+        // Reading a page doesn't make the next one skip what slid up
+        let page = get(
+            &server,
+            "stream/contents/user/-/state/com.google/reading-list?xt=user/-/state/com.google/read&n=4",
+        )
+        .await;
+        let read = page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| format!("i={}", item["id"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        let response = client
+            .post(format!("{}/reader/api/0/edit-tag", server.url))
+            .header("Authorization", format!("GoogleLogin auth={}", TOKEN))
+            .body(format!("{}&a=user/-/state/com.google/read", read.join("&")))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "OK");
+        let rest = get(
+            &server,
+            &format!(
+                "stream/contents/user/-/state/com.google/reading-list?xt=user/-/state/com.google/read&n=10&c={}",
+                page["continuation"].as_str().unwrap()
+            ),
+        )
+        .await;
+        assert_eq!(rest["items"].as_array().unwrap().len(), 5);
+
+        let response = client
+            .get(format!("{}/reader/api/0/subscription/list", server.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
@@ -0,0 +1,400 @@
+use hyper::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    args,
+    commands::RefreshResult,
+    error::{Result, TrsError},
+    ipc::{Request, Response, Worker},
+    persistence::{ArticleQuery, RssArticleD, RssChannelD},
+};
+
+use super::{
+    channels, json, no_content, parse_param, query_params, read_body, read_json, unexpected,
+};
+use super::{HttpRequest, HttpResponse};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// A channel without its articles, which are paged through separately
+#[derive(Serialize)]
+struct Channel {
+    id: i64,
+    title: String,
+    link: String,
+    feed_link: String,
+    description: String,
+    #[serde(with = "time::serde::rfc3339")]
+    last_update: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    last_fetch: Option<OffsetDateTime>,
+    refresh_interval: Option<u32>,
+    articles: usize,
+    unread: usize,
+}
+
+impl From<RssChannelD> for Channel {
+    fn from(channel: RssChannelD) -> Self {
+        Channel {
+            articles: channel.articles.len(),
+            unread: channel
+                .articles
+                .iter()
+                .filter(|article| article.unread)
+                .count(),
+            id: channel.id,
+            title: channel.title,
+            link: channel.link,
+            feed_link: channel.feed_link,
+            description: channel.description,
+            last_update: channel.last_update,
+            last_fetch: channel.last_fetch,
+            refresh_interval: channel.refresh_interval,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Page {
+    total: usize,
+    offset: u32,
+    limit: u32,
+    articles: Vec<RssArticleD>,
+}
+
+#[derive(Deserialize)]
+struct NewChannel {
+    link: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelUpdate {
+    /// Minutes, `null` goes back to the default
+    refresh_interval: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ArticleUpdate {
+    unread: Option<bool>,
+    starred: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+struct RefreshRequest {
+    /// Every channel when empty
+    #[serde(default)]
+    ids: Vec<u32>,
+}
+
+#[derive(Serialize)]
+struct Refreshed {
+    channel_id: i64,
+    title: String,
+    new: usize,
+    updated: usize,
+    not_modified: bool,
+    error: Option<String>,
+}
+
+impl From<RefreshResult> for Refreshed {
+    fn from(refreshed: RefreshResult) -> Self {
+        let (report, error) = match refreshed.result {
+            Ok(report) => (report, None),
+            Err(e) => (Default::default(), Some(e.to_string())),
+        };
+
+        Refreshed {
+            channel_id: refreshed.channel_id,
+            title: refreshed.title,
+            new: report.new,
+            updated: report.updated,
+            not_modified: report.not_modified,
+            error,
+        }
+    }
+}
+
+/// Route a request under `/api/`
+pub async fn handle(worker: &Worker, request: HttpRequest, route: &str) -> Result<HttpResponse> {
+    let method = request.method().clone();
+    let segments = route.trim_end_matches('/').split('/').collect::<Vec<_>>();
+    match (&method, segments.as_slice()) {
+        (&Method::GET, ["channels"]) => list_channels(worker).await,
+        (&Method::POST, ["channels"]) => add_channel(worker, request).await,
+        (&Method::GET, ["channels", id]) => get_channel(worker, parse_param("id", id)?).await,
+        (&Method::PATCH, ["channels", id]) => {
+            update_channel(worker, parse_param("id", id)?, request).await
+        }
+        (&Method::DELETE, ["channels", id]) => remove_channel(worker, parse_param("id", id)?).await,
+        (&Method::GET, ["articles"]) => list_articles(worker, &request).await,
+        (&Method::GET, ["articles", id]) => get_article(worker, parse_param("id", id)?).await,
+        (&Method::PATCH, ["articles", id]) => {
+            update_article(worker, parse_param("id", id)?, request).await
+        }
+        (&Method::POST, ["refresh"]) => refresh(worker, request).await,
+        _ => Err(TrsError::NotFound(format!(
+            "No route for {} /api/{}",
+            method, route
+        ))),
+    }
+}
+
+async fn list_channels(worker: &Worker) -> Result<HttpResponse> {
+    let channels = channels(worker)
+        .await?
+        .into_iter()
+        .map(Channel::from)
+        .collect::<Vec<_>>();
+    Ok(json(StatusCode::OK, &channels))
+}
+
+async fn get_channel(worker: &Worker, id: u32) -> Result<HttpResponse> {
+    match worker.call(Request::GetChannel { id: id as i64 }).await? {
+        Response::Channel(channel) => Ok(json(StatusCode::OK, &Channel::from(channel))),
+        _ => Err(unexpected()),
+    }
+}
+
+async fn add_channel(worker: &Worker, request: HttpRequest) -> Result<HttpResponse> {
+    let new: NewChannel = read_json(request).await?;
+    let link = args::valid_url(&new.link).map_err(TrsError::Error)?;
+    match worker
+        .call(Request::AddChannel(args::AddChannelArgs { link }))
+        .await?
+    {
+        Response::Channel(channel) => Ok(json(StatusCode::CREATED, &Channel::from(channel))),
+        _ => Err(unexpected()),
+    }
+}
+
+async fn update_channel(worker: &Worker, id: u32, request: HttpRequest) -> Result<HttpResponse> {
+    let update: ChannelUpdate = read_json(request).await?;
+    worker
+        .call(Request::SetInterval(args::SetIntervalArgs {
+            id,
+            minutes: update.refresh_interval,
+            default: update.refresh_interval.is_none(),
+        }))
+        .await?;
+    get_channel(worker, id).await
+}
+
+async fn remove_channel(worker: &Worker, id: u32) -> Result<HttpResponse> {
+    worker
+        .call(Request::RemoveChannel(args::RemoveChannelArgs { id }))
+        .await?;
+    Ok(no_content())
+}
+
+/// `?channel_id=&unread=true&starred=true&offset=&limit=`
+async fn list_articles(worker: &Worker, request: &HttpRequest) -> Result<HttpResponse> {
+    let mut query = ArticleQuery {
+        limit: DEFAULT_PAGE_SIZE,
+        ..Default::default()
+    };
+    for (name, value) in query_params(request) {
+        match name.as_str() {
+            "channel_id" => query.channel_id = Some(parse_param(&name, &value)?),
+            "unread" => query.unread = parse_flag(&name, &value)?,
+            "starred" => query.starred = parse_flag(&name, &value)?,
+            "offset" => query.offset = parse_param(&name, &value)?,
+            "limit" => query.limit = parse_param::<u32>(&name, &value)?.clamp(1, MAX_PAGE_SIZE),
+            _ => {}
+        }
+    }
+
+    match worker.call(Request::ListArticles(query.clone())).await? {
+        Response::Articles(page) => Ok(json(
+            StatusCode::OK,
+            &Page {
+                total: page.total,
+                offset: query.offset,
+                limit: query.limit,
+                articles: page.articles,
+            },
+        )),
+        _ => Err(unexpected()),
+    }
+}
+
+async fn get_article(worker: &Worker, id: u32) -> Result<HttpResponse> {
+    match worker
+        .call(Request::ShowArticle(args::ShowArticleArgs { id }))
+        .await?
+    {
+        Response::Article(article) => Ok(json(StatusCode::OK, &article)),
+        _ => Err(unexpected()),
+    }
+}
+
+async fn update_article(worker: &Worker, id: u32, request: HttpRequest) -> Result<HttpResponse> {
+    let update: ArticleUpdate = read_json(request).await?;
+    if let Some(unread) = update.unread {
+        worker
+            .call(Request::MarkRead(args::MarkReadArgs { id, unread }))
+            .await?;
+    }
+    if let Some(starred) = update.starred {
+        worker
+            .call(Request::Star(args::StarArgs {
+                id,
+                unstar: !starred,
+            }))
+            .await?;
+    }
+
+    get_article(worker, id).await
+}
+
+/// Waits for the downloads, an empty body refreshes everything
+async fn refresh(worker: &Worker, request: HttpRequest) -> Result<HttpResponse> {
+    let body = read_body(request).await?;
+    let refresh: RefreshRequest = match body.is_empty() {
+        true => RefreshRequest::default(),
+        false => serde_json::from_slice(&body)
+            .map_err(|e| TrsError::SerdeJsonError(e, "Invalid request body".to_string()))?,
+    };
+
+    match worker
+        .call(Request::Refresh(args::RefreshArgs {
+            id: refresh.ids,
+            jobs: args::DEFAULT_JOBS,
+        }))
+        .await?
+    {
+        Response::Refreshed(results) => Ok(json(
+            StatusCode::OK,
+            &results.into_iter().map(Refreshed::from).collect::<Vec<_>>(),
+        )),
+        _ => Err(unexpected()),
+    }
+}
+
+fn parse_flag(name: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(TrsError::Error(format!("Invalid {}: {}", name, value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::super::test_support::{TestServer, TOKEN};
+
+    async fn call(
+        server: &TestServer,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let mut request = reqwest::Client::new()
+            .request(method, format!("{}{}", server.url, path))
+            .bearer_auth(TOKEN);
+        if let Some(body) = body {
+            request = request.body(body.to_string());
+        }
+
+        let response = request.send().await.unwrap();
+        let status = response.status().as_u16();
+        let text = response.text().await.unwrap();
+        (status, serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rest_api() {
+        let server = TestServer::start("rest").await;
+        let response = reqwest::get(format!("{}/api/channels", server.url))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+
+        let (status, channels) = call(&server, reqwest::Method::GET, "/api/channels", None).await;
+        assert_eq!(status, 200);
+        assert_eq!(channels[0]["articles"], 10);
+        assert_eq!(channels[0]["unread"], 10);
+        let channel_id = channels[0]["id"].as_i64().unwrap();
+
+        // Paging
+        let (_, page) = call(
+            &server,
+            reqwest::Method::GET,
+            "/api/articles?unread=true&limit=3",
+            None,
+        )
+        .await;
+        assert_eq!(page["total"], 10);
+        assert_eq!(page["articles"].as_array().unwrap().len(), 3);
+        let (_, last_page) = call(
+            &server,
+            reqwest::Method::GET,
+            "/api/articles?unread=1&offset=9&limit=3",
+            None,
+        )
+        .await;
+        assert_eq!(last_page["articles"].as_array().unwrap().len(), 1);
+
+        // Reading an article takes it off the unread list
+        let article_id = page["articles"][0]["id"].as_i64().unwrap();
+        let (status, article) = call(
+            &server,
+            reqwest::Method::PATCH,
+            &format!("/api/articles/{}", article_id),
+            Some(serde_json::json!({ "unread": false, "starred": true })),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(article["unread"], false);
+        assert_eq!(article["starred"], true);
+        let (_, starred) = call(
+            &server,
+            reqwest::Method::GET,
+            "/api/articles?starred=true",
+            None,
+        )
+        .await;
+        assert_eq!(starred["total"], 1);
+        let (_, page) = call(
+            &server,
+            reqwest::Method::GET,
+            &format!("/api/articles?unread=true&channel_id={}", channel_id),
+            None,
+        )
+        .await;
+        assert_eq!(page["total"], 9);
+
+        let (status, channel) = call(
+            &server,
+            reqwest::Method::PATCH,
+            &format!("/api/channels/{}", channel_id),
+            Some(serde_json::json!({ "refresh_interval": 15 })),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(channel["refresh_interval"], 15);
+        assert_eq!(channel["unread"], 9);
+
+        let (status, error) = call(
+            &server,
+            reqwest::Method::POST,
+            "/api/channels",
+            Some(serde_json::json!({ "link": "ftp://example.com" })),
+        )
+        .await;
+        assert_eq!(status, 400);
+        assert_eq!(error["error"], "Invalid URL: ftp://example.com");
+
+        let (status, _) = call(&server, reqwest::Method::GET, "/api/articles/9999", None).await;
+        assert_eq!(status, 404);
+
+        let path = format!("/api/channels/{}", channel_id);
+        let (status, _) = call(&server, reqwest::Method::DELETE, &path, None).await;
+        assert_eq!(status, 204);
+        let (status, _) = call(&server, reqwest::Method::GET, &path, None).await;
+        assert_eq!(status, 404);
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::{HashMap, HashSet};
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+use crate::{
+    args::SyncArgs,
+    error::{Result, TrsError},
+    persistence::{Db, PendingOperation, Remote, RemoteArticle, Tag},
+    server::greader,
+};
+
+/// Environment variable holding the password for the remote reader
+pub const PASSWORD_ENV_VAR: &str = "TRS_SYNC_PASSWORD";
+
+const READING_LIST: &str = "user/-/state/com.google/reading-list";
+const READ: &str = "user/-/state/com.google/read";
+const STARRED: &str = "user/-/state/com.google/starred";
+
+/// Items per page when pulling articles, ids per page when pulling state
+const PAGE_SIZE: u32 = 1000;
+const ID_PAGE_SIZE: u32 = 10000;
+/// Items per edit-tag or stream/items/contents request
+const BATCH_SIZE: usize = 100;
+/// Pull articles published a little before the last sync, the remote may
+/// have only just fetched them
+const PULL_OVERLAP: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub channels: usize,
+    pub new_articles: usize,
+    /// Local changes sent to the remote
+    pub pushed: usize,
+    /// Articles whose read or starred state changed on the remote
+    pub pulled: usize,
+    /// Local changes dropped because the remote no longer has the article
+    pub dropped: usize,
+    /// Local changes dropped because the article changed on the remote after
+    /// them
+    pub overridden: usize,
+}
+
+/// What to do with a change made while offline
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    Push,
+    /// The remote already agrees
+    Skip,
+    /// The remote no longer has the article
+    Drop,
+    /// The remote changed the article later, its state is kept
+    Overridden,
+}
+
+/// The remote's state of an article
+struct RemoteState {
+    read: bool,
+    starred: bool,
+    /// When the article last changed on the remote, if it says
+    updated: Option<OffsetDateTime>,
+}
+
+impl RemoteState {
+    fn has(&self, tag: Tag) -> bool {
+        match tag {
+            Tag::Read => self.read,
+            Tag::Starred => self.starred,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SubscriptionList {
+    subscriptions: Vec<Subscription>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Subscription {
+    id: String,
+    title: String,
+    url: String,
+    #[serde(default)]
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct StreamContents {
+    items: Vec<Item>,
+    continuation: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    id: String,
+    #[serde(default)]
+    title: String,
+    published: Option<i64>,
+    #[serde(default)]
+    canonical: Vec<Link>,
+    #[serde(default)]
+    alternate: Vec<Link>,
+    summary: Option<Content>,
+    content: Option<Content>,
+    #[serde(default)]
+    categories: Vec<String>,
+    origin: Origin,
+    #[serde(rename = "timestampUsec")]
+    timestamp_usec: Option<Value>,
+    #[serde(rename = "crawlTimeMsec")]
+    crawl_time_msec: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    content: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Origin {
+    stream_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemIds {
+    #[serde(default)]
+    item_refs: Vec<ItemRef>,
+    continuation: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct ItemRef {
+    id: String,
+}
+
+impl Item {
+    fn remote_id(&self) -> Result<i64> {
+        greader::parse_item_id(&self.id)
+    }
+
+    /// Servers put the states in the user's own namespace
+    fn has(&self, tag: Tag) -> bool {
+        let suffix = format!("/state/com.google/{}", tag.name());
+        self.categories
+            .iter()
+            .any(|category| category.ends_with(&suffix))
+    }
+
+    /// The later of the item's timestamp and crawl time, in micro and
+    /// milliseconds. Servers don't say when the read or starred state
+    /// changed, only when the item did.
+    fn updated(&self) -> Option<OffsetDateTime> {
+        let timestamp = number(self.timestamp_usec.as_ref()).map(|usec| usec / 1000);
+        let crawled = number(self.crawl_time_msec.as_ref());
+        let msec = timestamp.max(crawled)?;
+        OffsetDateTime::from_unix_timestamp_nanos(msec as i128 * 1_000_000).ok()
+    }
+
+    fn to_article(&self) -> Result<RemoteArticle> {
+        Ok(RemoteArticle {
+            remote_id: self.remote_id()?,
+            title: self.title.clone(),
+            link: self
+                .canonical
+                .iter()
+                .chain(&self.alternate)
+                .map(|link| link.href.clone())
+                .next()
+                .unwrap_or_default(),
+            content: self
+                .content
+                .as_ref()
+                .or(self.summary.as_ref())
+                .map(|content| content.content.clone())
+                .unwrap_or_default(),
+            published: self
+                .published
+                .and_then(|published| OffsetDateTime::from_unix_timestamp(published).ok()),
+            unread: !self.has(Tag::Read),
+            starred: self.has(Tag::Starred),
+        })
+    }
+}
+
+/// Continuations are strings for some servers and numbers for others
+fn continuation(value: Option<Value>) -> Option<String> {
+    match value? {
+        Value::String(continuation) if !continuation.is_empty() => Some(continuation),
+        Value::Number(continuation) => Some(continuation.to_string()),
+        _ => None,
+    }
+}
+
+/// Timestamps are strings for most servers and numbers for others
+fn number(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::String(number) => number.parse().ok(),
+        Value::Number(number) => number.as_i64(),
+        _ => None,
+    }
+}
+
+/// A logged in session with the remote
+struct Client<'a> {
+    http: &'a reqwest::Client,
+    url: String,
+    auth: String,
+}
+
+impl<'a> Client<'a> {
+    async fn login(
+        http: &'a reqwest::Client,
+        url: &str,
+        user: &str,
+        password: &str,
+    ) -> Result<Client<'a>> {
+        let url = url.trim_end_matches('/').to_string();
+        let response = http
+            .post(format!("{}/accounts/ClientLogin", url))
+            .form(&[("Email", user), ("Passwd", password)])
+            .send()
+            .await
+            .map_err(|e| TrsError::ReqwestError(e, format!("Unable to reach {}", url)))?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(TrsError::Error(format!("{} refused the login", url)));
+        }
+
+        let body = response
+            .error_for_status()
+            .map_err(|e| TrsError::ReqwestError(e, format!("Unable to log in to {}", url)))?
+            .text()
+            .await?;
+        let auth = body
+            .lines()
+            .find_map(|line| line.strip_prefix("Auth="))
+            .ok_or_else(|| TrsError::Error(format!("No token in the login response of {}", url)))?
+            .to_string();
+        Ok(Client { http, url, auth })
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, path: &str) -> Result<String> {
+        request
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("GoogleLogin auth={}", self.auth),
+            )
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| TrsError::ReqwestError(e, format!("Request to {} failed", path)))?
+            .text()
+            .await
+            .map_err(|e| TrsError::ReqwestError(e, format!("Unable to read {}", path)))
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        let url = format!("{}/reader/api/0/{}", self.url, path);
+        let body = self
+            .send(
+                self.http
+                    .get(&url)
+                    .query(&[("output", "json")])
+                    .query(query),
+                path,
+            )
+            .await?;
+        serde_json::from_str(&body)
+            .map_err(|e| TrsError::SerdeJsonError(e, format!("Unexpected response to {}", path)))
+    }
+
+    async fn post(&self, path: &str, form: &[(&str, String)]) -> Result<String> {
+        let url = format!("{}/reader/api/0/{}", self.url, path);
+        self.send(self.http.post(&url).form(form), path).await
+    }
+
+    /// Sent back with every edit, so the server knows it's us
+    async fn token(&self) -> Result<String> {
+        let url = format!("{}/reader/api/0/token", self.url);
+        self.send(self.http.get(&url), "token").await
+    }
+
+    async fn subscriptions(&self) -> Result<Vec<Subscription>> {
+        self.get::<SubscriptionList>("subscription/list", &[])
+            .await
+            .map(|list| list.subscriptions)
+    }
+
+    /// Every item in the reading list published after `since`
+    async fn items(&self, since: Option<i64>) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        let mut next = None;
+        loop {
+            let mut query = vec![("n", PAGE_SIZE.to_string())];
+            query.extend(since.map(|since| ("ot", since.to_string())));
+            query.extend(next.map(|next| ("c", next)));
+            let page: StreamContents = self
+                .get(
+                    &format!(
+                        "stream/contents/{}",
+                        utf8_percent_encode(READING_LIST, NON_ALPHANUMERIC)
+                    ),
+                    &query,
+                )
+                .await?;
+            items.extend(page.items);
+            next = continuation(page.continuation);
+            if next.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    async fn items_by_id(&self, ids: &[i64]) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        for batch in ids.chunks(BATCH_SIZE) {
+            let form = batch
+                .iter()
+                .map(|id| ("i", id.to_string()))
+                .collect::<Vec<_>>();
+            let body = self.post("stream/items/contents", &form).await?;
+            let page: StreamContents = serde_json::from_str(&body).map_err(|e| {
+                TrsError::SerdeJsonError(e, "Unexpected response to stream/items/contents".into())
+            })?;
+            items.extend(page.items);
+        }
+
+        Ok(items)
+    }
+
+    /// Ids of the items in `stream`, leaving out those tagged `exclude`
+    async fn item_ids(&self, stream: &str, exclude: Option<&str>) -> Result<HashSet<i64>> {
+        let mut ids = HashSet::new();
+        let mut next = None;
+        loop {
+            let mut query = vec![("s", stream.to_string()), ("n", ID_PAGE_SIZE.to_string())];
+            query.extend(exclude.map(|exclude| ("xt", exclude.to_string())));
+            query.extend(next.map(|next| ("c", next)));
+            let page: ItemIds = self.get("stream/items/ids", &query).await?;
+            for item in page.item_refs {
+                ids.insert(greader::parse_item_id(&item.id)?);
+            }
+            next = continuation(page.continuation);
+            if next.is_none() {
+                return Ok(ids);
+            }
+        }
+    }
+
+    /// Add `tag` to the items, or remove it
+    async fn mark(&self, ids: &[i64], tag: Tag, added: bool, token: &str) -> Result<()> {
+        let action = match added {
+            true => "a",
+            false => "r",
+        };
+        let tag = format!("user/-/state/com.google/{}", tag.name());
+        for batch in ids.chunks(BATCH_SIZE) {
+            let mut form = vec![("T", token.to_string()), (action, tag.clone())];
+            form.extend(batch.iter().map(|id| ("i", greader::long_item_id(*id))));
+            self.post("edit-tag", &form).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pull subscriptions, articles, read and starred state from the remote and push the
+/// changes made locally since the last sync. Nothing is lost when the remote
+/// can't be reached: local changes stay queued until the next sync.
+pub async fn sync(db: &Db, http: &reqwest::Client, args: &SyncArgs) -> Result<SyncResult> {
+    let remote = remote(db, args)?;
+    let password = args
+        .password
+        .as_deref()
+        .ok_or_else(|| TrsError::Error(format!("Pass --password or set ${}", PASSWORD_ENV_VAR)))?;
+    let started = OffsetDateTime::now_utc();
+    let client = Client::login(http, &remote.url, &remote.user, password).await?;
+    let mut result = SyncResult::default();
+
+    let mut channels = HashMap::new();
+    for subscription in client.subscriptions().await? {
+        let link = if subscription.html_url.is_empty() {
+            &subscription.url
+        } else {
+            &subscription.html_url
+        };
+        let channel_id = db.sync_remote_channel(
+            &subscription.id,
+            &subscription.title,
+            link,
+            &subscription.url,
+        )?;
+        channels.insert(subscription.id, channel_id);
+    }
+    result.channels = channels.len();
+
+    let since = remote
+        .last_sync
+        .map(|last_sync| last_sync.unix_timestamp() - PULL_OVERLAP);
+    for item in client.items(since).await? {
+        // Subscriptions come and go between requests
+        let Some(channel_id) = channels.get(&item.origin.stream_id) else {
+            continue;
+        };
+        if db.sync_remote_article(*channel_id, &item.to_article()?)? {
+            result.new_articles += 1;
+        }
+    }
+
+    push(db, &client, &mut result).await?;
+
+    let unread = client.item_ids(READING_LIST, Some(READ)).await?;
+    result.pulled += pull(db, Tag::Read, |id| !unread.contains(&id))?;
+    let starred = client.item_ids(STARRED, None).await?;
+    result.pulled += pull(db, Tag::Starred, |id| starred.contains(&id))?;
+
+    db.set_remote(&Remote {
+        last_sync: Some(started),
+        ..remote
+    })?;
+    Ok(result)
+}
+
+/// The remote this database syncs with, set up from `args` the first time
+fn remote(db: &Db, args: &SyncArgs) -> Result<Remote> {
+    match (db.remote()?, &args.url) {
+        (Some(remote), Some(url))
+            if remote.url.trim_end_matches('/') != url.trim_end_matches('/') =>
+        {
+            Err(TrsError::Error(format!(
+                "This database syncs with {}, use another profile for {}",
+                remote.url, url
+            )))
+        }
+        (Some(remote), _) => Ok(Remote {
+            user: args.user.clone().unwrap_or(remote.user),
+            ..remote
+        }),
+        (None, Some(url)) => Ok(Remote {
+            url: url.clone(),
+            user: args
+                .user
+                .clone()
+                .ok_or_else(|| TrsError::Error("Pass --user for the first sync".to_string()))?,
+            last_sync: None,
+        }),
+        (None, None) => Err(TrsError::Error(
+            "Pass --url and --user for the first sync".to_string(),
+        )),
+    }
+}
+
+/// Take on the remote's state of `tag` for the articles without a local
+/// change waiting, returning how many changed
+fn pull(db: &Db, tag: Tag, has_tag: impl Fn(i64) -> bool) -> Result<usize> {
+    let changes = db
+        .synced_articles(tag)?
+        .into_iter()
+        .filter(|(_, remote_id, had_tag)| has_tag(*remote_id) != *had_tag)
+        .map(|(id, remote_id, _)| (id, has_tag(remote_id)))
+        .collect::<Vec<_>>();
+    db.apply_remote_state(tag, &changes)?;
+    Ok(changes.len())
+}
+
+/// Send the queued changes, unless the remote's item changed after them. The
+/// remote's state of those is pulled afterwards.
+async fn push(db: &Db, client: &Client<'_>, result: &mut SyncResult) -> Result<()> {
+    let operations = db.pending_operations()?;
+    if operations.is_empty() {
+        return Ok(());
+    }
+
+    let ids = operations
+        .iter()
+        .map(|operation| operation.remote_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let states = client
+        .items_by_id(&ids)
+        .await?
+        .into_iter()
+        .map(|item| {
+            let state = RemoteState {
+                read: item.has(Tag::Read),
+                starred: item.has(Tag::Starred),
+                updated: item.updated(),
+            };
+            item.remote_id().map(|id| (id, state))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let mut pushed = HashMap::<_, Vec<_>>::new();
+    for operation in &operations {
+        match resolve(operation, states.get(&operation.remote_id)) {
+            Resolution::Push => pushed
+                .entry((operation.tag, operation.added))
+                .or_default()
+                .push(operation.remote_id),
+            Resolution::Skip => {}
+            Resolution::Drop => result.dropped += 1,
+            Resolution::Overridden => result.overridden += 1,
+        }
+    }
+
+    if !pushed.is_empty() {
+        let token = client.token().await?;
+        for ((tag, added), ids) in &pushed {
+            client.mark(ids, *tag, *added, token.trim()).await?;
+            result.pushed += ids.len();
+        }
+    }
+    for operation in &operations {
+        db.remove_pending_operation(operation)?;
+    }
+
+    Ok(())
+}
+
+/// The newer change wins, the local one when the remote doesn't say when its
+/// item changed
+fn resolve(operation: &PendingOperation, remote: Option<&RemoteState>) -> Resolution {
+    match remote {
+        None => Resolution::Drop,
+        Some(remote) if remote.has(operation.tag) == operation.added => Resolution::Skip,
+        Some(remote)
+            if remote
+                .updated
+                .is_some_and(|updated| updated > operation.created_at) =>
+        {
+            Resolution::Overridden
+        }
+        Some(_) => Resolution::Push,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::server::test_support::{TestServer, TOKEN, USER};
+
+    #[test]
+    fn newer_changes_win() {
+        let operation = PendingOperation {
+            article_id: 1,
+            remote_id: 10,
+            tag: Tag::Read,
+            added: true,
+            created_at: datetime!(2025-01-01 12:00 UTC),
+        };
+        let remote = |read, updated| RemoteState {
+            read,
+            starred: false,
+            updated,
+        };
+
+        assert_eq!(resolve(&operation, None), Resolution::Drop);
+        let newer = Some(datetime!(2025-01-01 12:30 UTC));
+        assert_eq!(
+            resolve(&operation, Some(&remote(true, newer))),
+            Resolution::Skip
+        );
+        // Read locally, then the remote's item changed
+        assert_eq!(
+            resolve(&operation, Some(&remote(false, newer))),
+            Resolution::Overridden
+        );
+        let older = Some(datetime!(2025-01-01 11:30 UTC));
+        assert_eq!(
+            resolve(&operation, Some(&remote(false, older))),
+            Resolution::Push
+        );
+        assert_eq!(
+            resolve(&operation, Some(&remote(false, None))),
+            Resolution::Push
+        );
+
+        let item: Item = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "origin": { "streamId": "feed/1" },
+            "timestampUsec": "1735732800000000",
+            "crawlTimeMsec": 1735734600000_i64,
+        }))
+        .unwrap();
+        assert_eq!(item.updated(), newer);
+    }
+
+    fn unread_count(db: &Db) -> usize {
+        db.unread_article_ids().unwrap().len()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sync_with_a_greader_server() {
+        let server = TestServer::start("sync-remote").await;
+        let db_path = std::env::temp_dir().join(format!("trs-sync-test-{}.db", std::process::id()));
+        _ = std::fs::remove_file(&db_path);
+        let db = Db::create(&db_path).unwrap();
+        let http = reqwest::Client::new();
+        let args = SyncArgs {
+            url: Some(server.url.clone()),
+            user: Some(USER.to_string()),
+            password: Some(TOKEN.to_string()),
+        };
+
+        let result = sync(&db, &http, &args).await.unwrap();
+        assert_eq!(result.channels, 1);
+        assert_eq!(result.new_articles, 10);
+        assert_eq!(unread_count(&db), 10);
+
+        // Read and star one article locally, another on the remote
+        let client = Client::login(&http, &server.url, USER, TOKEN)
+            .await
+            .unwrap();
+        let local_id = db.unread_article_ids().unwrap()[0];
+        db.mark_article_read(local_id).unwrap();
+        db.star_article(local_id).unwrap();
+        let local_remote_id = db.pending_operations().unwrap()[0].remote_id;
+        let remote_id = *client
+            .item_ids(READING_LIST, Some(READ))
+            .await
+            .unwrap()
+            .iter()
+            .find(|id| **id != local_remote_id)
+            .unwrap();
+        client
+            .mark(&[remote_id], Tag::Read, true, "")
+            .await
+            .unwrap();
+        client
+            .mark(&[remote_id], Tag::Starred, true, "")
+            .await
+            .unwrap();
+
+        // A failed sync keeps the changes queued, and the url is remembered
+        let offline = SyncArgs {
+            url: None,
+            user: None,
+            password: Some("wrong".to_string()),
+        };
+        assert!(sync(&db, &http, &offline).await.is_err());
+        assert_eq!(db.pending_operations().unwrap().len(), 2);
+
+        let args = SyncArgs { url: None, ..args };
+        let result = sync(&db, &http, &args).await.unwrap();
+        assert_eq!(result.new_articles, 0);
+        assert_eq!(result.pushed, 2);
+        assert_eq!(result.pulled, 2);
+        assert_eq!(result.dropped, 0);
+        assert_eq!(result.overridden, 0);
+        assert!(db.pending_operations().unwrap().is_empty());
+        assert_eq!(unread_count(&db), 8);
+        assert_eq!(db.starred_article_ids().unwrap().len(), 2);
+        let remote_unread = client.item_ids(READING_LIST, Some(READ)).await.unwrap();
+        assert_eq!(remote_unread.len(), 8);
+        let remote_starred = client.item_ids(STARRED, None).await.unwrap();
+        assert_eq!(remote_starred, HashSet::from([local_remote_id, remote_id]));
+
+        let other = SyncArgs {
+            url: Some("http://example.com".to_string()),
+            ..args
+        };
+        assert!(sync(&db, &http, &other).await.is_err());
+        _ = std::fs::remove_file(&db_path);
+    }
+}
@@ -4,18 +4,26 @@ pub mod backend;
 pub mod channels;
 pub mod controls;
 pub mod debug;
+pub mod reader;
+pub mod scroll;
+pub mod search;
+pub mod status;
 pub mod title;
 
 use std::{
+    collections::HashSet,
     io::Stdout,
+    path::Path,
     sync::mpsc::{channel, Sender},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
     args::{self, UiArgs},
     error::{Result, TrsError},
-    persistence::RssChannelD,
+    ipc::{Request, Session},
+    persistence::{Folder, RssChannelD},
+    schedule,
 };
 use articles::ArticlesWidget;
 use channels::ChannelsWidget;
@@ -27,6 +35,11 @@ use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Padding},
 };
+use reader::{ReaderState, ReaderWidget};
+use scroll::ListViewport;
+use search::SearchState;
+use status::{Status, StatusKind, StatusWidget};
+use time::OffsetDateTime;
 use title::TitleWidget;
 use tokio::sync::mpsc::UnboundedReceiver;
 
@@ -34,14 +47,34 @@ pub struct AppState {
     exit: bool,
     debug_enabled: bool,
     debug: bool,
+    /// What the channels pane lists, see [`channels::channel_tree`]
     channels: Vec<RssChannelD>,
+    /// The channels and folders as the backend sent them
+    subscriptions: Vec<RssChannelD>,
+    folders: Vec<Folder>,
+    /// Ids of the folders whose channels are hidden
+    collapsed: HashSet<i64>,
     focussed: FocussedPane,
     highlighted_channel: Option<usize>,
     highlighted_article: Option<usize>,
+    channels_view: ListViewport,
+    articles_view: ListViewport,
     last_action: Option<UiAction>,
     show_add_channel_ui: bool,
     add_channel: String,
-    dispatcher: Sender<UiCommandDispatchActions>,
+    reader: Option<ReaderState>,
+    search: Option<SearchState>,
+    status: Option<Status>,
+    /// Ticks since start, drives the spinner
+    ticks: usize,
+    /// Minutes between background refreshes, 0 when turned off
+    refresh_interval: u32,
+    /// Connected to a daemon, which does the refreshing
+    daemon: bool,
+    next_refresh_check: Instant,
+    /// A background refresh was sent and the backend hasn't answered yet
+    refresh_pending: bool,
+    dispatcher: Sender<Request>,
     receiver: UnboundedReceiver<Event>,
 }
 
@@ -62,10 +95,20 @@ pub enum UiAction {
     FocusEntryDown,
     ToggleDebug,
     OpenArticle,
+    OpenArticleInBrowser,
+    CloseArticle,
+    PageUp,
+    PageDown,
+    JumpToTop,
+    JumpToBottom,
     ShowAddChannelUi,
     RemoveChannel,
     ToggleReadStatus,
+    ToggleStarred,
     SyncChannel,
+    ToggleFolder,
+    StartSearch,
+    ClearSearch,
     Exit,
 }
 
@@ -78,53 +121,57 @@ pub enum PopupUiAction {
     Close,
 }
 
-#[derive(Debug)]
-pub enum UiCommandDispatchActions {
-    AddChannel(args::AddChannelArgs),
-    RemoveChannel(args::RemoveChannelArgs),
-    MarkArticleRead(args::MarkReadArgs),
-    ListChannels(args::ListChannelArgs),
-}
-
 /// APP
 ///  - Listen Event
-///  - Publish UiCommandDispatchActions
+///  - Publish ipc::Request
 ///
 /// BACKEND
-///  - Listen UiCommandDispatchActions
+///  - Listen ipc::Request
 ///  - Publish BackendEvent
 ///
 /// EVENT LOOP
 ///  - Listen BackendEvent
 ///  - Listen crossterm::event::Event
 ///  - Publish Event
-pub async fn ui(args: &UiArgs, db_name: &str) -> Result<()> {
+pub async fn ui(args: &UiArgs, db_path: &Path) -> Result<()> {
+    let session = Session::open(db_path).await?;
     let (ui_action_publisher, ui_action_receiver) = channel();
     let (backend_event_publisher, backend_event_receiver) = tokio::sync::mpsc::unbounded_channel();
     let event_receiver = start_event_loop(backend_event_receiver);
 
     let mut app_state = AppState {
         channels: Vec::new(),
+        subscriptions: Vec::new(),
+        folders: Vec::new(),
+        collapsed: HashSet::new(),
         exit: false,
         debug_enabled: args.debug,
         debug: false,
         focussed: FocussedPane::Channels,
         highlighted_article: None,
         highlighted_channel: None,
+        channels_view: ListViewport::default(),
+        articles_view: ListViewport::default(),
         last_action: None,
         show_add_channel_ui: false,
         add_channel: String::new(),
+        reader: None,
+        search: None,
+        status: None,
+        ticks: 0,
+        refresh_interval: args.refresh_interval,
+        daemon: session.is_daemon(),
+        next_refresh_check: Instant::now(),
+        refresh_pending: false,
         dispatcher: ui_action_publisher,
         receiver: event_receiver,
     };
 
-    start_backend(db_name, ui_action_receiver, backend_event_publisher);
+    start_backend(session, ui_action_receiver, backend_event_publisher);
 
     app_state
         .dispatcher
-        .send(UiCommandDispatchActions::ListChannels(
-            args::ListChannelArgs { limit: None },
-        ))
+        .send(Request::ListChannels(args::ListChannelArgs { limit: None }))
         .map_err(|e| TrsError::Error(format!("Unable to send initial app: {}", e)))?;
 
     let mut terminal = ratatui::init();
@@ -142,13 +189,13 @@ pub async fn ui(args: &UiArgs, db_name: &str) -> Result<()> {
 }
 
 fn start_backend(
-    db_name: &str,
-    app_recv: std::sync::mpsc::Receiver<UiCommandDispatchActions>,
+    session: Session,
+    app_recv: std::sync::mpsc::Receiver<Request>,
     executor_dispatch: tokio::sync::mpsc::UnboundedSender<BackendEvent>,
 ) {
-    let db_name = db_name.to_string();
+    let runtime = tokio::runtime::Handle::current();
     std::thread::spawn(move || {
-        backend::start(db_name, app_recv, executor_dispatch);
+        backend::start(session, runtime, app_recv, executor_dispatch);
     });
 }
 
@@ -165,26 +212,15 @@ fn start_event_loop(
             let crossterm_event = reader.next().fuse();
             tokio::select! {
               user_input = crossterm_event => {
-                match user_input {
-                  Some(Ok(evt)) => {
-                    match evt {
-                      crossterm::event::Event::Key(key) => {
-                        if key.kind == KeyEventKind::Press {
-                          _event_tx.send(Event::UserInput(crossterm::event::Event::Key(key))).unwrap();
-                        }
-                      },
-                      _ => {}
-                    }
-                  },
-                  _ => {}
+                if let Some(Ok(crossterm::event::Event::Key(key))) = user_input {
+                  if key.kind == KeyEventKind::Press {
+                    _event_tx.send(Event::UserInput(crossterm::event::Event::Key(key))).unwrap();
+                  }
                 }
               },
               executor_event = executor_recv.recv() => {
-                  match executor_event {
-                    Some(backend_event) => {
-                      _event_tx.send(Event::BackendEvent(backend_event)).unwrap();
-                    },
-                    None => {}
+                  if let Some(backend_event) = executor_event {
+                    _event_tx.send(Event::BackendEvent(backend_event)).unwrap();
                   }
               },
               _ = tick_delay => {
@@ -202,7 +238,7 @@ fn draw(app_state: &AppState, terminal: &mut Terminal<CrosstermBackend<Stdout>>)
         .draw(|f| {
             f.render_widget(AppStateWidget::new(app_state), f.area());
         })
-        .map_err(|e| TrsError::TuiError(e))?;
+        .map_err(TrsError::TuiError)?;
 
     Ok(())
 }
@@ -214,7 +250,13 @@ pub enum Event {
 }
 
 pub enum BackendEvent {
-    ReloadState(Vec<RssChannelD>),
+    ReloadState(Vec<RssChannelD>, Vec<Folder>),
+    /// Ids of the articles matching a query, best first
+    SearchResults(String, Vec<i64>),
+    /// Something slow started, like a download
+    InProgress(String),
+    Completed(String),
+    Failed(String),
 }
 
 async fn handle_events(state: &mut AppState) -> Result<()> {
@@ -228,19 +270,87 @@ async fn handle_events(state: &mut AppState) -> Result<()> {
             handle_user_input(state, event)?;
         }
         Event::BackendEvent(backend_event) => match backend_event {
-            BackendEvent::ReloadState(channels) => {
-                state.channels = channels;
-                if state.highlighted_channel.is_none() && !state.channels.is_empty() {
-                    state.highlighted_channel = Some(0);
+            BackendEvent::ReloadState(channels, folders) => {
+                state.refresh_pending = false;
+                state.subscriptions = channels;
+                state.folders = folders;
+                actions::rebuild_channel_tree(state);
+            }
+            BackendEvent::SearchResults(query, ids) => {
+                // Only the latest query counts, keys are faster than the backend
+                if let Some(search) = state.search.as_mut().filter(|s| s.query == query) {
+                    search.matches = Some(ids);
+                    actions::reset_highlighted_article(state);
                 }
             }
+            BackendEvent::InProgress(message) => {
+                state.status = Some(Status::new(StatusKind::InProgress, message))
+            }
+            BackendEvent::Completed(message) => {
+                state.status = Some(Status::new(StatusKind::Done, message))
+            }
+            BackendEvent::Failed(message) => {
+                state.refresh_pending = false;
+                state.status = Some(Status::new(StatusKind::Error, message))
+            }
         },
-        Event::Tick => {}
+        Event::Tick => {
+            state.ticks = state.ticks.wrapping_add(1);
+            if state
+                .status
+                .as_ref()
+                .is_some_and(|status| status.is_expired(Instant::now()))
+            {
+                state.status = None;
+            }
+            schedule_refresh(state)?;
+        }
     };
 
     Ok(())
 }
 
+/// Every so often, ask the backend to refresh the channels that are due.
+/// A daemon refreshes them itself, so only pick up what it fetched.
+fn schedule_refresh(state: &mut AppState) -> Result<()> {
+    let now = Instant::now();
+    if state.daemon && now >= state.next_refresh_check {
+        state.next_refresh_check = now + schedule::CHECK_INTERVAL;
+        return state
+            .dispatcher
+            .send(Request::ListChannels(args::ListChannelArgs { limit: None }))
+            .map_err(|e| TrsError::Error(format!("Unable to reload channels: {}", e)));
+    }
+
+    if state.daemon
+        || state.refresh_interval == 0
+        || state.refresh_pending
+        || state.subscriptions.is_empty()
+        || now < state.next_refresh_check
+    {
+        return Ok(());
+    }
+
+    state.next_refresh_check = now + schedule::CHECK_INTERVAL;
+    let due = schedule::due_channels(
+        &state.subscriptions,
+        OffsetDateTime::now_utc(),
+        state.refresh_interval,
+    );
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    state.refresh_pending = true;
+    state
+        .dispatcher
+        .send(Request::Refresh(args::RefreshArgs {
+            id: due,
+            jobs: args::DEFAULT_JOBS,
+        }))
+        .map_err(|e| TrsError::Error(format!("Unable to schedule refresh: {}", e)))
+}
+
 fn handle_user_input(state: &mut AppState, event: event::Event) -> Result<()> {
     if state.show_add_channel_ui {
         let popup_ui_action = controls::parse_popup_ui_action(event);
@@ -248,10 +358,19 @@ fn handle_user_input(state: &mut AppState, event: event::Event) -> Result<()> {
         return Ok(());
     }
 
-    let ui_action = controls::parse_ui_action(event);
+    if state.search.as_ref().is_some_and(|search| search.editing) {
+        let search_action = controls::parse_popup_ui_action(event);
+        actions::handle_search_action(state, search_action)?;
+        return Ok(());
+    }
+
+    let ui_action = match state.reader {
+        Some(_) => controls::parse_reader_ui_action(event),
+        None => controls::parse_ui_action(event),
+    };
     state.last_action = Some(ui_action.clone());
     actions::handle_action(state, ui_action)?;
-    return Ok(());
+    Ok(())
 }
 
 struct AppStateWidget<'a> {
@@ -289,46 +408,60 @@ impl<'a> Widget for AppStateWidget<'a> {
             .direction(Direction::Vertical)
             .constraints(vec![
                 Constraint::Percentage(100), // Channels + Articles
+                Constraint::Length(1),       // Status
                 Constraint::Min(4),          // Controls + Title
             ])
             .split(main_area)
             .to_vec();
 
-        // OTHER APP WIDGETS
-        let child_widgets_areas = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(Constraint::from_fills([4, 6]))
-            .split(main_area_splits[0])
-            .to_vec();
-
-        // CHANNELS
-        let channels_area = child_widgets_areas[0];
-        draw_app_widget_styled(
-            get_child_widget_style(
-                "Channels",
-                self.app_state.focussed == FocussedPane::Channels,
-            ),
-            &channels_area,
-            buf,
-            ChannelsWidget::new(self.app_state),
-        );
-
-        // ARTICLES
-        let articles_area = child_widgets_areas[1];
-        draw_app_widget_styled(
-            get_child_widget_style(
-                "Articles",
-                self.app_state.focussed == FocussedPane::Articles,
-            ),
-            &articles_area,
-            buf,
-            ArticlesWidget::new(self.app_state),
-        );
+        // READER, takes over from the channels and articles when open
+        if self.app_state.reader.is_some() {
+            draw_app_widget_styled(
+                get_child_widget_style("Reader", true),
+                &main_area_splits[0],
+                buf,
+                ReaderWidget::new(self.app_state),
+            );
+        } else {
+            // OTHER APP WIDGETS
+            let child_widgets_areas = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(Constraint::from_fills([4, 6]))
+                .split(main_area_splits[0])
+                .to_vec();
+
+            // CHANNELS
+            let channels_area = child_widgets_areas[0];
+            draw_app_widget_styled(
+                get_child_widget_style(
+                    "Channels",
+                    self.app_state.focussed == FocussedPane::Channels,
+                ),
+                &channels_area,
+                buf,
+                ChannelsWidget::new(self.app_state),
+            );
+
+            // ARTICLES
+            let articles_area = child_widgets_areas[1];
+            draw_app_widget_styled(
+                get_child_widget_style(
+                    "Articles",
+                    self.app_state.focussed == FocussedPane::Articles,
+                ),
+                &articles_area,
+                buf,
+                ArticlesWidget::new(self.app_state),
+            );
+        }
 
         let controls_title = Layout::default()
             .direction(Direction::Horizontal)
             .constraints(Constraint::from_fills([1, 7]))
-            .split(main_area_splits[1]);
+            .split(main_area_splits[2]);
+
+        // STATUS
+        StatusWidget::new(self.app_state).render(main_area_splits[1], buf);
 
         // TITLE
         let title_area = controls_title[0];
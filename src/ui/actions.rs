@@ -1,10 +1,17 @@
 use crate::{
     args,
     error::TrsError,
+    ipc::Request,
     persistence::{RssArticleD, RssChannelD},
 };
 
-use super::{AppState, FocussedPane, PopupUiAction, UiAction, UiCommandDispatchActions};
+use super::{
+    channels,
+    reader::ReaderState,
+    scroll::{self, ListViewport},
+    search::{self, SearchState},
+    AppState, FocussedPane, PopupUiAction, UiAction,
+};
 
 pub fn handle_action(
     app_state: &mut AppState,
@@ -18,35 +25,41 @@ pub fn handle_action(
         UiAction::FocusPaneRight => app_state.focussed = FocussedPane::Articles,
         UiAction::ToggleDebug => toggle_debug(app_state),
         UiAction::Exit => app_state.exit = true,
-        UiAction::FocusEntryDown => focus_entry_down(app_state),
-        UiAction::FocusEntryUp => focus_entry_up(app_state),
+        UiAction::FocusEntryDown => match &mut app_state.reader {
+            Some(reader) => reader.scroll_by(1),
+            None => move_entry_down(app_state, 1),
+        },
+        UiAction::FocusEntryUp => match &mut app_state.reader {
+            Some(reader) => reader.scroll_by(-1),
+            None => move_entry_up(app_state, 1),
+        },
         UiAction::OpenArticle => {
-            if let Some(channel_idx) = app_state.highlighted_channel {
-                if let Some(article_idx) = app_state.highlighted_article {
-                    if let Some(channel) = app_state.channels.get_mut(channel_idx) {
-                        if let Some(article) = channel.articles.get_mut(article_idx) {
-                            article.unread = false;
-                            app_state
-                                .dispatcher
-                                .send(UiCommandDispatchActions::MarkArticleRead(
-                                    args::MarkReadArgs {
-                                        id: article.id as u32,
-                                        unread: false,
-                                    },
-                                ))
-                                .unwrap();
-                            _ = open::that(&article.link);
-                        }
-                    }
-                }
+            if let Some(article) = mark_highlighted_article_read(app_state) {
+                app_state.reader = Some(ReaderState::new(&article));
             }
         }
+        UiAction::OpenArticleInBrowser => {
+            if let Some(article) = mark_highlighted_article_read(app_state) {
+                _ = open::that(&article.link);
+            }
+        }
+        UiAction::CloseArticle => app_state.reader = None,
+        UiAction::PageUp => page_up(app_state),
+        UiAction::PageDown => page_down(app_state),
+        UiAction::JumpToTop => match &mut app_state.reader {
+            Some(reader) => reader.scroll_by(i32::MIN),
+            None => move_entry_up(app_state, usize::MAX),
+        },
+        UiAction::JumpToBottom => match &mut app_state.reader {
+            Some(reader) => reader.scroll_by(i32::MAX),
+            None => move_entry_down(app_state, usize::MAX),
+        },
         UiAction::ShowAddChannelUi => {
             app_state.show_add_channel_ui = true;
         }
         UiAction::RemoveChannel => {
             let hi_channel = get_highlighted_channel(app_state);
-            let Some(channel) = hi_channel else {
+            let Some(channel) = hi_channel.filter(|c| !channels::is_virtual(c)) else {
                 return Ok(());
             };
 
@@ -56,7 +69,7 @@ pub fn handle_action(
 
             app_state
                 .dispatcher
-                .send(UiCommandDispatchActions::RemoveChannel(remove_channel_args))
+                .send(Request::RemoveChannel(remove_channel_args))
                 .unwrap();
         }
         UiAction::ToggleReadStatus => {
@@ -72,25 +85,68 @@ pub fn handle_action(
                 if let Some(unread) = unread {
                     app_state
                         .dispatcher
-                        .send(UiCommandDispatchActions::MarkArticleRead(
-                            args::MarkReadArgs {
-                                id: article_id as u32,
-                                unread,
-                            },
-                        ))
+                        .send(Request::MarkRead(args::MarkReadArgs {
+                            id: article_id as u32,
+                            unread,
+                        }))
                         .unwrap();
                 }
             }
         }
+        UiAction::ToggleStarred => {
+            if let Some(article) = get_highlighted_article(app_state) {
+                app_state
+                    .dispatcher
+                    .send(Request::Star(args::StarArgs {
+                        id: article.id as u32,
+                        unstar: article.starred,
+                    }))
+                    .unwrap();
+            }
+        }
+        UiAction::ToggleFolder => {
+            let folder_id = get_highlighted_channel(app_state).and_then(channels::entry_folder);
+            if let Some(folder_id) = folder_id {
+                if !app_state.collapsed.remove(&folder_id) {
+                    app_state.collapsed.insert(folder_id);
+                }
+                rebuild_channel_tree(app_state);
+            }
+        }
+        UiAction::StartSearch => {
+            app_state
+                .search
+                .get_or_insert_with(SearchState::default)
+                .editing = true;
+        }
+        UiAction::ClearSearch => {
+            if app_state.search.take().is_some() {
+                reset_highlighted_article(app_state);
+            }
+        }
         UiAction::SyncChannel => {
-            let channel = get_highlighted_channel(app_state);
-            if let Some(channel) = channel {
-                let sync_channel_args = args::AddChannelArgs {
-                    link: channel.feed_link.clone(),
+            // A folder refreshes all of its channels
+            let ids = match get_highlighted_channel(app_state) {
+                Some(channel) if !channels::is_virtual(channel) => vec![channel.id as u32],
+                Some(channel) => match channels::entry_folder(channel) {
+                    Some(folder_id) => app_state
+                        .subscriptions
+                        .iter()
+                        .filter(|channel| channel.folder_id == Some(folder_id))
+                        .map(|channel| channel.id as u32)
+                        .collect(),
+                    None => Vec::new(),
+                },
+                None => Vec::new(),
+            };
+            if !ids.is_empty() {
+                let refresh_args = args::RefreshArgs {
+                    jobs: ids.len().min(args::DEFAULT_JOBS),
+                    id: ids,
                 };
                 app_state
                     .dispatcher
-                    .send(UiCommandDispatchActions::AddChannel(sync_channel_args))
+                    .send(Request::Refresh(refresh_args))
                     .unwrap();
             }
         }
@@ -110,7 +166,7 @@ pub fn handle_popup_action(
             };
             state
                 .dispatcher
-                .send(UiCommandDispatchActions::AddChannel(add_channel_args))
+                .send(Request::AddChannel(add_channel_args))
                 .unwrap();
             state.show_add_channel_ui = false;
         }
@@ -128,55 +184,181 @@ pub fn handle_popup_action(
     Ok(())
 }
 
-fn saturating_add(num: usize, to_add: usize, max: usize) -> usize {
-    if num + to_add > max {
-        max
-    } else {
-        num + to_add
+/// Keys typed while searching, the articles are filtered as the query changes
+pub fn handle_search_action(
+    state: &mut AppState,
+    event: PopupUiAction,
+) -> std::result::Result<(), TrsError> {
+    let Some(search) = state.search.as_mut() else {
+        return Ok(());
+    };
+
+    match event {
+        PopupUiAction::None => return Ok(()),
+        PopupUiAction::Submit => {
+            search.editing = false;
+            if search.query.trim().is_empty() {
+                state.search = None;
+            }
+            return Ok(());
+        }
+        PopupUiAction::AddChar(c) => search.query.push(c),
+        PopupUiAction::Backspace => {
+            search.query.pop();
+        }
+        PopupUiAction::Close => state.search = None,
+    };
+
+    match state.search.as_mut() {
+        Some(search) if !search.query.trim().is_empty() => {
+            state
+                .dispatcher
+                .send(Request::Search(args::SearchArgs {
+                    query: vec![search.query.clone()],
+                    limit: u32::MAX,
+                }))
+                .unwrap();
+        }
+        // Nothing to search for shows everything again
+        Some(search) => search.matches = None,
+        None => {}
     }
+    reset_highlighted_article(state);
+
+    Ok(())
 }
 
-fn min(arg: usize, max_channel_idx: usize) -> usize {
-    if arg < max_channel_idx {
-        arg
-    } else {
-        max_channel_idx
-    }
+/// Rebuild the channels pane after the channels, folders or collapsed folders
+/// changed. The highlighted entry and article are looked up again by id, and
+/// when they're gone the highlight stays where it was, within bounds.
+pub fn rebuild_channel_tree(app_state: &mut AppState) {
+    let channel_id = get_highlighted_channel(app_state).map(|channel| channel.id);
+    let article_id = get_highlighted_article(app_state).map(|article| article.id);
+    app_state.channels = channels::channel_tree(
+        &app_state.subscriptions,
+        &app_state.folders,
+        &app_state.collapsed,
+    );
+
+    let found = channel_id.and_then(|id| {
+        app_state
+            .channels
+            .iter()
+            .position(|channel| channel.id == id)
+    });
+    app_state.highlighted_channel = scroll::reselect(
+        app_state.highlighted_channel,
+        found,
+        app_state.channels.len(),
+    );
+    let Some(channel_idx) = found else {
+        reset_highlighted_article(app_state);
+        return;
+    };
+
+    let shown = shown_articles(app_state);
+    let articles = &app_state.channels[channel_idx].articles;
+    let found = article_id.and_then(|id| shown.iter().position(|idx| articles[*idx].id == id));
+    app_state.highlighted_article =
+        scroll::reselect(app_state.highlighted_article, found, shown.len());
+}
+
+/// Back to the first of the articles shown, after they changed
+pub fn reset_highlighted_article(app_state: &mut AppState) {
+    app_state.articles_view.reset();
+    app_state.highlighted_article = match shown_articles(app_state).is_empty() {
+        true => None,
+        false => Some(0),
+    };
+}
+
+fn saturating_add(num: usize, to_add: usize, max: usize) -> usize {
+    num.saturating_add(to_add).min(max)
 }
 
 fn toggle_debug(app_state: &mut AppState) {
     app_state.debug = !app_state.debug;
 }
 
-fn get_highlighted_channel<'a>(app_state: &'a AppState) -> Option<&'a RssChannelD> {
+fn get_highlighted_channel(app_state: &AppState) -> Option<&RssChannelD> {
     app_state
         .highlighted_channel
-        .and_then(|idx| app_state.channels.get(idx).or_else(|| None))
+        .and_then(|idx| app_state.channels.get(idx))
 }
 
-fn get_highlighted_article<'a>(app_state: &'a AppState) -> Option<&'a RssArticleD> {
+/// Positions in the highlighted channel of the articles in the articles pane
+fn shown_articles(app_state: &AppState) -> Vec<usize> {
+    get_highlighted_channel(app_state)
+        .map(|channel| search::shown_articles(app_state.search.as_ref(), channel))
+        .unwrap_or_default()
+}
+
+fn get_highlighted_article(app_state: &AppState) -> Option<&RssArticleD> {
     let hi_article = app_state.highlighted_article?;
     let channel = get_highlighted_channel(app_state)?;
-    channel.articles.get(hi_article)
+    let idx = *shown_articles(app_state).get(hi_article)?;
+    channel.articles.get(idx)
 }
 
-fn focus_entry_up(app_state: &mut AppState) {
+/// Mark the highlighted article read, here and in the database, and return
+/// a copy of it
+fn mark_highlighted_article_read(app_state: &mut AppState) -> Option<RssArticleD> {
+    let channel_idx = app_state.highlighted_channel?;
+    let article_idx = *shown_articles(app_state).get(app_state.highlighted_article?)?;
+    let article = app_state
+        .channels
+        .get_mut(channel_idx)?
+        .articles
+        .get_mut(article_idx)?;
+    article.unread = false;
+    app_state
+        .dispatcher
+        .send(Request::MarkRead(args::MarkReadArgs {
+            id: article.id as u32,
+            unread: false,
+        }))
+        .unwrap();
+    Some(article.clone())
+}
+
+fn page_up(app_state: &mut AppState) {
+    match &mut app_state.reader {
+        Some(reader) => reader.scroll_by(-reader.page_size()),
+        None => move_entry_up(app_state, focussed_viewport(app_state).page_size()),
+    }
+}
+
+fn page_down(app_state: &mut AppState) {
+    match &mut app_state.reader {
+        Some(reader) => reader.scroll_by(reader.page_size()),
+        None => move_entry_down(app_state, focussed_viewport(app_state).page_size()),
+    }
+}
+
+fn focussed_viewport(app_state: &AppState) -> &ListViewport {
     match app_state.focussed {
-        FocussedPane::Channels => decrement_highlighted_channel_idx(app_state),
-        FocussedPane::Articles => decrement_highlighted_article_idx(app_state),
+        FocussedPane::Channels => &app_state.channels_view,
+        FocussedPane::Articles => &app_state.articles_view,
+    }
+}
+
+fn move_entry_up(app_state: &mut AppState, by: usize) {
+    match app_state.focussed {
+        FocussedPane::Channels => decrement_highlighted_channel_idx(app_state, by),
+        FocussedPane::Articles => decrement_highlighted_article_idx(app_state, by),
     }
     .unwrap_or(false);
 }
 
-fn focus_entry_down(app_state: &mut AppState) {
+fn move_entry_down(app_state: &mut AppState, by: usize) {
     match app_state.focussed {
-        FocussedPane::Channels => increment_highlighted_channel_idx(app_state),
-        FocussedPane::Articles => increment_highlighted_article_idx(app_state),
+        FocussedPane::Channels => increment_highlighted_channel_idx(app_state, by),
+        FocussedPane::Articles => increment_highlighted_article_idx(app_state, by),
     }
     .unwrap_or(false);
 }
 
-fn increment_highlighted_channel_idx(app_state: &mut AppState) -> Option<bool> {
+fn increment_highlighted_channel_idx(app_state: &mut AppState, by: usize) -> Option<bool> {
     let channels_len = app_state.channels.len();
     if channels_len == 0 {
         app_state.highlighted_channel = None;
@@ -184,25 +366,28 @@ fn increment_highlighted_channel_idx(app_state: &mut AppState) -> Option<bool> {
     }
 
     let max_channel_idx = channels_len.saturating_sub(1);
-    app_state.highlighted_channel = app_state
-        .highlighted_channel
-        .map(|idx| saturating_add(idx, 1, max_channel_idx))
-        .or_else(|| Some(0));
+    // Nothing highlighted yet counts as being just above the first entry
+    app_state.highlighted_channel = Some(match app_state.highlighted_channel {
+        Some(idx) => saturating_add(idx, by, max_channel_idx),
+        None => saturating_add(0, by - 1, max_channel_idx),
+    });
 
     update_highligted_article(app_state)
 }
 
-fn decrement_highlighted_channel_idx(app_state: &mut AppState) -> Option<bool> {
+fn decrement_highlighted_channel_idx(app_state: &mut AppState, by: usize) -> Option<bool> {
     app_state.highlighted_channel = app_state
         .highlighted_channel
-        .map(|idx| idx.saturating_sub(1));
+        .map(|idx| idx.saturating_sub(by));
 
     update_highligted_article(app_state)
 }
 
 // When changing the channel, update the idx of the article to be within the range
 fn update_highligted_article(app_state: &mut AppState) -> Option<bool> {
-    let hi_channel_articles_max = get_highlighted_channel(app_state)?.articles.len();
+    app_state.articles_view.reset();
+    get_highlighted_channel(app_state)?;
+    let hi_channel_articles_max = shown_articles(app_state).len();
     let hi_article_idx = app_state.highlighted_article?;
     let max_article_idx = hi_channel_articles_max.saturating_sub(1);
 
@@ -217,24 +402,22 @@ fn update_highligted_article(app_state: &mut AppState) -> Option<bool> {
     Some(true)
 }
 
-fn increment_highlighted_article_idx(app_state: &mut AppState) -> Option<bool> {
-    let hi_article_max_idx = get_highlighted_channel(app_state)?
-        .articles
-        .len()
-        .saturating_sub(1);
+fn increment_highlighted_article_idx(app_state: &mut AppState, by: usize) -> Option<bool> {
+    get_highlighted_channel(app_state)?;
+    let hi_article_max_idx = shown_articles(app_state).len().saturating_sub(1);
 
-    app_state.highlighted_article = app_state
-        .highlighted_article
-        .map(|idx| saturating_add(idx, 1, hi_article_max_idx))
-        .or_else(|| Some(min(0, hi_article_max_idx)));
+    app_state.highlighted_article = Some(match app_state.highlighted_article {
+        Some(idx) => saturating_add(idx, by, hi_article_max_idx),
+        None => saturating_add(0, by - 1, hi_article_max_idx),
+    });
 
     Some(true)
 }
 
-fn decrement_highlighted_article_idx(app_state: &mut AppState) -> Option<bool> {
+fn decrement_highlighted_article_idx(app_state: &mut AppState, by: usize) -> Option<bool> {
     app_state.highlighted_article = app_state
         .highlighted_article
-        .map(|idx| idx.saturating_sub(1));
+        .map(|idx| idx.saturating_sub(by));
 
     Some(true)
 }
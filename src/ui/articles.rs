@@ -1,12 +1,12 @@
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Paragraph, Widget},
 };
 
-use super::AppState;
+use super::{scroll::render_scrollbar, search, AppState};
 
 pub struct ArticlesWidget<'a> {
     state: &'a AppState,
@@ -29,25 +29,54 @@ impl<'a> Widget for ArticlesWidget<'a> {
             return;
         };
 
-        let count = channel.articles.len();
-        let para = Paragraph::new(format!("{} ({} articles)", channel.title, count)).centered();
+        let shown = search::shown_articles(self.state.search.as_ref(), channel);
+        let count = shown.len();
+        let mut header = vec![Line::from(match &self.state.search {
+            Some(_) => format!(
+                "{} ({} of {} articles)",
+                channel.title,
+                count,
+                channel.articles.len()
+            ),
+            None => format!("{} ({} articles)", channel.title, count),
+        })];
+        if let Some(search) = &self.state.search {
+            let mut spans = vec![
+                Span::styled("/", Style::default().fg(Color::Yellow)),
+                Span::raw(search.query.clone()),
+            ];
+            if search.editing {
+                spans.push(Span::styled(
+                    " ",
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ));
+            }
+            header.push(Line::from(spans));
+        }
+        let para = Paragraph::new(header).centered();
         para.render(area, buf);
 
         let height_per_entry = 1;
-        let total_articles = area.height / height_per_entry;
-        let total_articles = channel.articles.len().min(total_articles as usize);
+        let list_area = area.inner(Margin::new(2, 2));
+        let visible_rows = (list_area.height / height_per_entry) as usize;
+        let visible = self.state.articles_view.visible_range(
+            self.state.highlighted_article,
+            count,
+            visible_rows,
+        );
         let article_rows = Layout::default()
             .direction(Direction::Vertical)
-            .margin(2)
             .constraints(
-                (0..total_articles)
+                visible
+                    .clone()
                     .map(|_| Constraint::Length(height_per_entry))
                     .collect::<Vec<_>>(),
             )
-            .split(area)
+            .split(list_area)
             .to_vec();
 
-        for ((idx, row), article) in article_rows.into_iter().enumerate().zip(&channel.articles) {
+        for (row, idx) in article_rows.into_iter().zip(visible.clone()) {
+            let article = &channel.articles[shown[idx]];
             let current_highlighted = self
                 .state
                 .highlighted_article
@@ -67,13 +96,27 @@ impl<'a> Widget for ArticlesWidget<'a> {
                 get_article_title_style(current_highlighted),
             );
 
-            lines.push(Line::from(vec![id, title]));
+            let mut spans = vec![id];
+            if article.starred {
+                spans.push(Span::styled("★ ", Style::default().fg(Color::Yellow)));
+            }
+            spans.push(title);
+
+            lines.push(Line::from(spans));
             let para = Paragraph::new(lines)
                 .block(Block::default())
                 .style(get_channel_list_item_block_style(current_highlighted))
                 .alignment(Alignment::Left);
             para.render(row, buf);
         }
+
+        render_scrollbar(
+            area.inner(Margin::new(0, 2)),
+            buf,
+            visible.start,
+            count,
+            visible_rows,
+        );
     }
 }
 
@@ -1,66 +1,222 @@
 use std::sync::mpsc::Receiver;
 
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::{runtime::Handle, sync::mpsc::UnboundedSender};
 
-use crate::{commands::TrsEnv, ui::BackendEvent};
+use crate::{
+    args::ListChannelArgs,
+    commands::RefreshResult,
+    error::TrsError,
+    ipc::{Request, Session},
+    ui::BackendEvent,
+};
 
-use super::UiCommandDispatchActions;
-
-// This one will have to run on the same thread as this manages the sqlite connection
+// Runs on its own thread, the session may hold the sqlite connection
 pub fn start(
-    db_name: String,
-    cmd_recv: Receiver<UiCommandDispatchActions>,
+    mut session: Session,
+    runtime: Handle,
+    cmd_recv: Receiver<Request>,
     backend_dispatch: UnboundedSender<BackendEvent>,
-) -> () {
-    let ctx = TrsEnv::new(db_name.as_str()).unwrap();
+) {
     loop {
-        let action = cmd_recv.recv();
-        let Ok(action) = action else {
+        let request = cmd_recv.recv();
+        let Ok(request) = request else {
             break;
         };
 
-        match action {
-            UiCommandDispatchActions::AddChannel(args) => {
-                if let Ok(_) = crate::commands::add_channel(&ctx, &args) {
-                    send_new_state_default(&ctx, &backend_dispatch);
-                };
+        match request {
+            Request::AddChannel(args) => {
+                send(
+                    &backend_dispatch,
+                    BackendEvent::InProgress(format!("Adding {}", args.link)),
+                );
+                match runtime.block_on(session.add_channel(args)) {
+                    Ok(channel) => {
+                        send(
+                            &backend_dispatch,
+                            BackendEvent::Completed(format!("Added {}", channel.title)),
+                        );
+                        send_new_state_default(&runtime, &mut session, &backend_dispatch);
+                    }
+                    Err(e) => send_error(&backend_dispatch, "Unable to add channel", e),
+                }
             }
-            UiCommandDispatchActions::RemoveChannel(args) => {
-                if let Ok(_) = crate::commands::remove_channel(&ctx, &args) {
-                    send_new_state_default(&ctx, &backend_dispatch);
+            Request::RemoveChannel(args) => {
+                let id = args.id;
+                match runtime.block_on(session.execute(Request::RemoveChannel(args))) {
+                    Ok(_) => {
+                        send(
+                            &backend_dispatch,
+                            BackendEvent::Completed(format!("Removed channel #{}", id)),
+                        );
+                        send_new_state_default(&runtime, &mut session, &backend_dispatch);
+                    }
+                    Err(e) => send_error(&backend_dispatch, "Unable to remove channel", e),
                 }
             }
-            UiCommandDispatchActions::MarkArticleRead(args) => {
-                if let Ok(_) = crate::commands::mark_read(&ctx, &args) {
-                    send_new_state_default(&ctx, &backend_dispatch);
+            Request::Refresh(args) => {
+                let progress = match args.id.as_slice() {
+                    [] => "Refreshing all channels".to_string(),
+                    [id] => format!("Refreshing channel #{}", id),
+                    ids => format!("Refreshing {} channels", ids.len()),
+                };
+                send(&backend_dispatch, BackendEvent::InProgress(progress));
+                match runtime.block_on(session.refresh(args)) {
+                    Ok(results) => {
+                        send(&backend_dispatch, refresh_summary(&results));
+                        send_new_state_default(&runtime, &mut session, &backend_dispatch);
+                    }
+                    Err(e) => send_error(&backend_dispatch, "Unable to refresh", e),
                 }
             }
-            UiCommandDispatchActions::ListChannels(args) => {
-                send_new_state(&ctx, args, &backend_dispatch);
+            Request::ListChannels(args) => {
+                send_new_state(&runtime, &mut session, args, &backend_dispatch);
+            }
+            Request::Search(args) => {
+                let query = args.query.join(" ");
+                match runtime.block_on(session.search(args)) {
+                    Ok(hits) => send(
+                        &backend_dispatch,
+                        BackendEvent::SearchResults(
+                            query,
+                            hits.into_iter().map(|hit| hit.article.id).collect(),
+                        ),
+                    ),
+                    Err(e) => send_error(&backend_dispatch, "Unable to search", e),
+                }
+            }
+            request @ (Request::MarkRead(_) | Request::Star(_)) => {
+                match runtime.block_on(session.execute(request)) {
+                    Ok(_) => send_new_state_default(&runtime, &mut session, &backend_dispatch),
+                    Err(e) => send_error(&backend_dispatch, "Unable to update article", e),
+                }
             }
+            request => match runtime.block_on(session.execute(request)) {
+                Ok(_) => send_new_state_default(&runtime, &mut session, &backend_dispatch),
+                Err(e) => send_error(&backend_dispatch, "Request failed", e),
+            },
         }
     }
 }
 
+/// One line describing a refresh, leading with the first failure if any
+fn refresh_summary(results: &[RefreshResult]) -> BackendEvent {
+    let failures = results
+        .iter()
+        .filter_map(|refreshed| refreshed.result.as_ref().err().map(|e| (refreshed, e)))
+        .collect::<Vec<_>>();
+    if let Some((refreshed, e)) = failures.first() {
+        let others = match failures.len() {
+            1 => String::new(),
+            n => format!(" (and {} more)", n - 1),
+        };
+        return BackendEvent::Failed(format!(
+            "Failed to refresh {}: {}{}",
+            refreshed.title, e, others
+        ));
+    }
+
+    match results {
+        [RefreshResult {
+            title,
+            result: Ok(report),
+            ..
+        }] if report.not_modified => BackendEvent::Completed(format!("{}: not modified", title)),
+        [RefreshResult {
+            title,
+            result: Ok(report),
+            ..
+        }] => BackendEvent::Completed(format!(
+            "{}: {} new, {} updated",
+            title, report.new, report.updated
+        )),
+        _ => {
+            let new: usize = results
+                .iter()
+                .filter_map(|refreshed| refreshed.result.as_ref().ok())
+                .map(|report| report.new)
+                .sum();
+            BackendEvent::Completed(format!(
+                "Refreshed {} channels, {} new articles",
+                results.len(),
+                new
+            ))
+        }
+    }
+}
+
+fn send(dispatcher: &UnboundedSender<BackendEvent>, event: BackendEvent) {
+    dispatcher.send(event).unwrap_or_default();
+}
+
+fn send_error(dispatcher: &UnboundedSender<BackendEvent>, context: &str, e: TrsError) {
+    send(
+        dispatcher,
+        BackendEvent::Failed(format!("{}: {}", context, e)),
+    );
+}
+
 fn send_new_state_default(
-    ctx: &crate::commands::TrsEnv,
+    runtime: &Handle,
+    session: &mut Session,
     dispatcher: &UnboundedSender<BackendEvent>,
 ) {
     send_new_state(
-        ctx,
-        crate::args::ListChannelArgs { limit: None },
+        runtime,
+        session,
+        ListChannelArgs { limit: None },
         dispatcher,
     );
 }
 
 fn send_new_state(
-    ctx: &crate::commands::TrsEnv,
-    args: crate::args::ListChannelArgs,
+    runtime: &Handle,
+    session: &mut Session,
+    args: ListChannelArgs,
     dispatcher: &UnboundedSender<BackendEvent>,
 ) {
-    if let Ok(channels) = crate::commands::list_channels(ctx, &args) {
-        dispatcher
-            .send(BackendEvent::ReloadState(channels))
-            .unwrap_or_default();
+    let state = runtime.block_on(async {
+        let channels = session.list_channels(args).await?;
+        let folders = session.list_folders().await?;
+        Ok::<_, TrsError>((channels, folders))
+    });
+    match state {
+        Ok((channels, folders)) => send(dispatcher, BackendEvent::ReloadState(channels, folders)),
+        Err(e) => send_error(dispatcher, "Unable to load channels", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::SyncReport;
+
+    fn refreshed(title: &str, result: Result<SyncReport, TrsError>) -> RefreshResult {
+        RefreshResult {
+            channel_id: 1,
+            title: title.to_string(),
+            result,
+        }
+    }
+
+    #[test]
+    fn refresh_failures_are_reported() {
+        let report = SyncReport {
+            new: 2,
+            ..Default::default()
+        };
+        let results = vec![
+            refreshed("Ok", Ok(report)),
+            refreshed("Broken", Err(TrsError::Error("404".to_string()))),
+            refreshed("Gone", Err(TrsError::Error("410".to_string()))),
+        ];
+        let BackendEvent::Failed(message) = refresh_summary(&results) else {
+            panic!("expected a failure");
+        };
+        assert_eq!(message, "Failed to refresh Broken: 404 (and 1 more)");
+
+        let BackendEvent::Completed(message) = refresh_summary(&results[..1]) else {
+            panic!("expected success");
+        };
+        assert_eq!(message, "Ok: 2 new, 0 updated");
     }
 }
@@ -1,13 +1,21 @@
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Paragraph, Widget},
 };
-use time::format_description;
+use std::collections::HashSet;
 
-use super::AppState;
+use time::{format_description, OffsetDateTime};
+
+use super::{scroll::render_scrollbar, AppState};
+use crate::persistence::{Folder, RssArticleD, RssChannelD};
+
+/// Id of the entry at the top of the channels which gathers the starred
+/// articles of every channel. Real channels start at 1, folders are listed
+/// with their id negated.
+pub const STARRED_CHANNEL_ID: i64 = 0;
 
 pub struct ChannelsWidget<'a> {
     state: &'a AppState,
@@ -26,24 +34,27 @@ impl<'a> ChannelsWidget<'a> {
 impl<'a> Widget for ChannelsWidget<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let height_per_entry = 1;
-        let total_channels = area.height / height_per_entry;
-        let total_channels = self.state.channels.len().min(total_channels as usize);
+        let list_area = area.inner(Margin::new(2, 2));
+        let visible_rows = (list_area.height / height_per_entry) as usize;
+        let channels = &self.state.channels;
+        let visible = self.state.channels_view.visible_range(
+            self.state.highlighted_channel,
+            channels.len(),
+            visible_rows,
+        );
         let channel_rows = Layout::default()
             .direction(Direction::Vertical)
-            .margin(2)
             .constraints(
-                (0..total_channels)
+                visible
+                    .clone()
                     .map(|_| Constraint::Length(height_per_entry))
                     .collect::<Vec<_>>(),
             )
-            .split(area)
+            .split(list_area)
             .to_vec();
 
-        for ((idx, row), channel) in channel_rows
-            .into_iter()
-            .enumerate()
-            .zip(&self.state.channels)
-        {
+        for (row, idx) in channel_rows.into_iter().zip(visible.clone()) {
+            let channel = &channels[idx];
             let current_highlighted = self
                 .state
                 .highlighted_channel
@@ -56,12 +67,31 @@ impl<'a> Widget for ChannelsWidget<'a> {
             );
             spans.push(id);
 
+            // Folders are marked open or not, their channels indented
+            let marker = match entry_folder(channel) {
+                Some(folder_id) if self.state.collapsed.contains(&folder_id) => "▸ ",
+                Some(_) => "▾ ",
+                None if channel.folder_id.is_some() => "  ",
+                None => "",
+            };
             let title = Span::styled(
-                channel.title.clone(),
+                format!("{}{}", marker, channel.title),
                 get_channel_title_style(current_highlighted),
             );
             spans.push(title);
 
+            let unread = channel
+                .articles
+                .iter()
+                .filter(|article| article.unread)
+                .count();
+            if unread > 0 {
+                spans.push(Span::styled(
+                    format!(" ({})", unread),
+                    get_channel_unread_style(current_highlighted),
+                ));
+            }
+
             let format = format_description::parse("[year]-[month]-[day]").unwrap();
             if let Some(article) = channel.articles.first() {
                 let pub_date_text = match article.pub_date {
@@ -82,6 +112,14 @@ impl<'a> Widget for ChannelsWidget<'a> {
             para.render(row, buf);
         }
 
+        render_scrollbar(
+            area.inner(Margin::new(0, 2)),
+            buf,
+            visible.start,
+            channels.len(),
+            visible_rows,
+        );
+
         if self.state.show_add_channel_ui {
             let add_channel_area = Layout::default()
                 .direction(Direction::Vertical)
@@ -96,6 +134,97 @@ impl<'a> Widget for ChannelsWidget<'a> {
     }
 }
 
+/// The entries of the channels pane: "Starred" first, then every folder
+/// followed by its channels unless it's collapsed, then the channels outside
+/// of any folder. Folders and "Starred" gather the articles of the channels
+/// they stand for.
+pub fn channel_tree(
+    channels: &[RssChannelD],
+    folders: &[Folder],
+    collapsed: &HashSet<i64>,
+) -> Vec<RssChannelD> {
+    let mut starred = channels
+        .iter()
+        .flat_map(|channel| &channel.articles)
+        .filter(|article| article.starred)
+        .cloned()
+        .collect::<Vec<_>>();
+    starred.sort_by_key(|article| std::cmp::Reverse(article.starred_at));
+
+    let mut tree = vec![virtual_channel(
+        STARRED_CHANNEL_ID,
+        "Starred",
+        "Starred articles from every channel",
+        starred,
+    )];
+    for folder in folders {
+        let in_folder = channels
+            .iter()
+            .filter(|channel| channel.folder_id == Some(folder.id))
+            .collect::<Vec<_>>();
+        let mut articles = in_folder
+            .iter()
+            .flat_map(|channel| &channel.articles)
+            .cloned()
+            .collect::<Vec<_>>();
+        articles.sort_by_key(|article| std::cmp::Reverse(article.pub_date.or(article.last_update)));
+
+        tree.push(virtual_channel(
+            -folder.id,
+            &folder.name,
+            "Articles from every channel in the folder",
+            articles,
+        ));
+        if !collapsed.contains(&folder.id) {
+            tree.extend(in_folder.into_iter().cloned());
+        }
+    }
+    tree.extend(
+        channels
+            .iter()
+            .filter(|channel| channel.folder_id.is_none())
+            .cloned(),
+    );
+
+    tree
+}
+
+/// The folder an entry of the channels pane stands for
+pub fn entry_folder(channel: &RssChannelD) -> Option<i64> {
+    (channel.id < STARRED_CHANNEL_ID).then_some(-channel.id)
+}
+
+/// Entries that aren't channels of their own: "Starred" and the folders
+pub fn is_virtual(channel: &RssChannelD) -> bool {
+    channel.id <= STARRED_CHANNEL_ID
+}
+
+fn virtual_channel(
+    id: i64,
+    title: &str,
+    description: &str,
+    articles: Vec<RssArticleD>,
+) -> RssChannelD {
+    RssChannelD {
+        id,
+        title: title.to_string(),
+        link: String::new(),
+        feed_link: String::new(),
+        description: description.to_string(),
+        last_update: OffsetDateTime::now_utc(),
+        etag: None,
+        last_modified: None,
+        ttl: None,
+        skip_hours: Vec::new(),
+        skip_days: Vec::new(),
+        refresh_interval: None,
+        last_fetch: None,
+        folder_id: None,
+        retention: Default::default(),
+        articles,
+    }
+}
+
 fn get_channel_id_style(highlighted: bool) -> Style {
     if highlighted {
         Style::default()
@@ -126,6 +255,16 @@ fn get_channel_pub_date_style(highlighted: bool) -> Style {
     }
 }
 
+fn get_channel_unread_style(highlighted: bool) -> Style {
+    if highlighted {
+        Style::default()
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Red)
+    }
+}
+
 fn get_channel_title_style(highlighted: bool) -> Style {
     if highlighted {
         Style::default()
@@ -153,3 +292,43 @@ impl<'a> Widget for AddChannelWidget<'a> {
         para.render(area, buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(id: i64, folder_id: Option<i64>, unread: bool) -> RssChannelD {
+        let mut article = RssArticleD::dummy();
+        article.id = id * 10;
+        article.channel_id = id;
+        article.unread = unread;
+        let mut channel = virtual_channel(id, &format!("Channel {}", id), "", vec![article]);
+        channel.folder_id = folder_id;
+        channel
+    }
+
+    #[test]
+    fn folders_gather_their_channels() {
+        let channels = vec![
+            channel(1, None, true),
+            channel(2, Some(7), true),
+            channel(3, Some(7), false),
+        ];
+        let folders = vec![Folder {
+            id: 7,
+            name: "News".to_string(),
+        }];
+
+        let ids = |tree: &[RssChannelD]| tree.iter().map(|entry| entry.id).collect::<Vec<_>>();
+        let tree = channel_tree(&channels, &folders, &HashSet::new());
+        assert_eq!(ids(&tree), vec![STARRED_CHANNEL_ID, -7, 2, 3, 1]);
+        assert_eq!(entry_folder(&tree[1]), Some(7));
+        assert_eq!(tree[1].articles.len(), 2);
+        assert!(is_virtual(&tree[0]) && is_virtual(&tree[1]) && !is_virtual(&tree[2]));
+
+        // Collapsed folders keep their articles, but not their channels
+        let tree = channel_tree(&channels, &folders, &HashSet::from([7]));
+        assert_eq!(ids(&tree), vec![STARRED_CHANNEL_ID, -7, 1]);
+        assert_eq!(tree[1].articles.len(), 2);
+    }
+}
@@ -18,17 +18,19 @@ pub fn parse_popup_ui_action(raw_event: Event) -> PopupUiAction {
                 return PopupUiAction::None;
             }
 
-            if key_event.modifiers != KeyModifiers::NONE {
+            if key_event.modifiers != KeyModifiers::NONE
+                && key_event.modifiers != KeyModifiers::SHIFT
+            {
                 return PopupUiAction::None;
             }
 
-            return match key_event.code {
+            match key_event.code {
                 KeyCode::Backspace => PopupUiAction::Backspace,
                 KeyCode::Char(c) => PopupUiAction::AddChar(c),
                 KeyCode::Enter => PopupUiAction::Submit,
                 KeyCode::Esc => PopupUiAction::Close,
                 _ => PopupUiAction::None,
-            };
+            }
         }
         _ => PopupUiAction::None,
     }
@@ -47,12 +49,21 @@ pub fn parse_ui_action(raw_event: Event) -> UiAction {
                     KeyCode::Char('n') => UiAction::FocusEntryDown,
                     KeyCode::Char('l') => UiAction::FocusPaneRight,
                     KeyCode::Char('h') => UiAction::FocusPaneLeft,
+                    KeyCode::Char('u') | KeyCode::Char('b') => UiAction::PageUp,
+                    KeyCode::Char('d') | KeyCode::Char('f') => UiAction::PageDown,
                     _ => UiAction::None,
                 };
             }
 
+            match key_event.code {
+                KeyCode::Char('G') | KeyCode::End => return UiAction::JumpToBottom,
+                KeyCode::Char('/') => return UiAction::StartSearch,
+                _ => {}
+            }
+
             if key_event.modifiers == KeyModifiers::NONE {
                 return match key_event.code {
+                    KeyCode::Esc => UiAction::ClearSearch,
                     KeyCode::Char('l') => UiAction::FocusPaneRight,
                     KeyCode::Char('h') => UiAction::FocusPaneLeft,
                     KeyCode::Char('k') => UiAction::FocusEntryUp,
@@ -62,7 +73,13 @@ pub fn parse_ui_action(raw_event: Event) -> UiAction {
                     KeyCode::Char('a') => UiAction::ShowAddChannelUi,
                     KeyCode::Char('d') => UiAction::RemoveChannel,
                     KeyCode::Char('r') => UiAction::ToggleReadStatus,
+                    KeyCode::Char('f') => UiAction::ToggleStarred,
                     KeyCode::Char('s') => UiAction::SyncChannel,
+                    KeyCode::Char('c') => UiAction::ToggleFolder,
+                    KeyCode::Char('g') | KeyCode::Home => UiAction::JumpToTop,
+                    KeyCode::PageUp => UiAction::PageUp,
+                    KeyCode::PageDown => UiAction::PageDown,
+                    KeyCode::Char('o') => UiAction::OpenArticleInBrowser,
                     KeyCode::Enter => UiAction::OpenArticle,
                     _ => UiAction::None,
                 };
@@ -74,6 +91,50 @@ pub fn parse_ui_action(raw_event: Event) -> UiAction {
     }
 }
 
+/// Keys while an article is open in the reader
+pub fn parse_reader_ui_action(raw_event: Event) -> UiAction {
+    match raw_event {
+        Event::Key(key_event) => {
+            if key_event.kind != KeyEventKind::Press {
+                return UiAction::None;
+            }
+
+            if key_event.modifiers == KeyModifiers::CONTROL {
+                return match key_event.code {
+                    KeyCode::Char('p') => UiAction::FocusEntryUp,
+                    KeyCode::Char('n') => UiAction::FocusEntryDown,
+                    KeyCode::Char('u') | KeyCode::Char('b') => UiAction::PageUp,
+                    KeyCode::Char('d') | KeyCode::Char('f') => UiAction::PageDown,
+                    _ => UiAction::None,
+                };
+            }
+
+            if let KeyCode::Char('G') | KeyCode::End = key_event.code {
+                return UiAction::JumpToBottom;
+            }
+
+            if key_event.modifiers == KeyModifiers::NONE {
+                return match key_event.code {
+                    KeyCode::Char('k') | KeyCode::Up => UiAction::FocusEntryUp,
+                    KeyCode::Char('j') | KeyCode::Down => UiAction::FocusEntryDown,
+                    KeyCode::Char(' ') | KeyCode::PageDown => UiAction::PageDown,
+                    KeyCode::PageUp => UiAction::PageUp,
+                    KeyCode::Char('g') | KeyCode::Home => UiAction::JumpToTop,
+                    KeyCode::Char('o') => UiAction::OpenArticleInBrowser,
+                    KeyCode::Char('f') => UiAction::ToggleStarred,
+                    KeyCode::Char('q') | KeyCode::Char('h') | KeyCode::Esc => {
+                        UiAction::CloseArticle
+                    }
+                    _ => UiAction::None,
+                };
+            }
+
+            UiAction::None
+        }
+        _ => UiAction::None,
+    }
+}
+
 macro_rules! control {
     ($key:literal) => {
         Span::styled(
@@ -102,6 +163,8 @@ impl Widget for ControlsWidget {
             description!(" add a new RSS channel, "),
             control!("s"),
             description!(" sync channel, "),
+            control!("c"),
+            description!(" collapse/expand folder, "),
             control!("d"),
             description!(" delete an RSS channel, "),
             control!("r"),
@@ -114,8 +177,20 @@ impl Widget for ControlsWidget {
         );
 
         let controls_text_line_2 = Line::from(vec![
+            control!("enter"),
+            description!(" read article, "),
+            control!("o"),
+            description!(" open in browser, "),
+            control!("f"),
+            description!(" star/unstar, "),
+            control!("/"),
+            description!(" search, "),
+            control!("ctrl+d/u"),
+            description!(" page down/up, "),
+            control!("g/G"),
+            description!(" jump to top/bottom, "),
             control!("q"),
-            description!(" to exit"),
+            description!(" to close the article or exit"),
         ])
         .style(
             Style::default()
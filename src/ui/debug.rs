@@ -59,13 +59,11 @@ impl<'a> Widget for DebugWidget<'a> {
             .split(area)
             .to_vec();
 
-        let mut idx = 0;
-        for debug_line in lines {
+        for (idx, debug_line) in lines.into_iter().enumerate() {
             let para = Paragraph::new(debug_line)
                 .wrap(Wrap::default())
                 .block(Block::default().borders(Borders::BOTTOM));
             para.render(line_areas[idx], buf);
-            idx = idx + 1;
         }
     }
 }
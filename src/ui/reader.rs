@@ -0,0 +1,461 @@
+use std::cell::Cell;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget, Wrap},
+};
+
+use crate::{
+    html::{self, HtmlToken},
+    persistence::RssArticleD,
+};
+
+use super::AppState;
+
+/// An article opened in the reader, rendered once when it is opened
+pub struct ReaderState {
+    pub title: String,
+    pub link: String,
+    pub lines: Vec<Line<'static>>,
+    pub scroll: u16,
+    /// Rows visible and the furthest the text can scroll, as of the last draw
+    pub viewport: Cell<(u16, u16)>,
+}
+
+impl ReaderState {
+    pub fn new(article: &RssArticleD) -> Self {
+        ReaderState {
+            title: article.title.clone(),
+            link: article.link.clone(),
+            lines: html_to_lines(article.body()),
+            scroll: 0,
+            viewport: Cell::new((0, u16::MAX)),
+        }
+    }
+
+    pub fn scroll_by(&mut self, rows: i32) {
+        let (_, max_scroll) = self.viewport.get();
+        self.scroll = (self.scroll as i32)
+            .saturating_add(rows)
+            .clamp(0, max_scroll as i32) as u16;
+    }
+
+    pub fn page_size(&self) -> i32 {
+        let (height, _) = self.viewport.get();
+        (height as i32 - 2).max(1)
+    }
+}
+
+pub struct ReaderWidget<'a> {
+    state: &'a AppState,
+}
+
+impl<'a> ReaderWidget<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl<'a> Widget for ReaderWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let Some(reader) = &self.state.reader else {
+            return;
+        };
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Fill(1),
+            ])
+            .split(area)
+            .to_vec();
+
+        Paragraph::new(reader.title.clone())
+            .style(
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .centered()
+            .render(rows[0], buf);
+        Paragraph::new(reader.link.clone())
+            .style(Style::default().fg(Color::DarkGray))
+            .centered()
+            .render(rows[1], buf);
+
+        let body = Paragraph::new(reader.lines.clone()).wrap(Wrap { trim: false });
+        let text_area = rows[3];
+        let max_scroll = (body.line_count(text_area.width) as u16).saturating_sub(text_area.height);
+        reader.viewport.set((text_area.height, max_scroll));
+        body.scroll((reader.scroll.min(max_scroll), 0))
+            .render(text_area, buf);
+    }
+}
+
+/// Turn an article's HTML into styled lines for the terminal. Links are
+/// numbered inline and listed at the end, since they can't be clicked.
+pub fn html_to_lines(html: &str) -> Vec<Line<'static>> {
+    let mut renderer = Renderer::default();
+    for token in html::tokenize(html) {
+        match token {
+            HtmlToken::Text(text) => renderer.text(&text),
+            HtmlToken::Start { ref name, .. } => renderer.start(name, &token),
+            HtmlToken::End { name } => renderer.end(&name),
+        }
+    }
+
+    renderer.finish()
+}
+
+#[derive(Default)]
+struct Renderer {
+    lines: Vec<Line<'static>>,
+    current: Vec<Span<'static>>,
+    line_started: bool,
+    last_was_space: bool,
+    bold: usize,
+    italic: usize,
+    code: usize,
+    pre: usize,
+    quote: usize,
+    heading: Option<usize>,
+    /// `None` for a bulleted list, otherwise the next item's number
+    lists: Vec<Option<usize>>,
+    open_links: Vec<Option<String>>,
+    links: Vec<String>,
+}
+
+impl Renderer {
+    fn start(&mut self, name: &str, token: &HtmlToken) {
+        match name {
+            "p" | "blockquote" | "pre" | "table" | "figure" => {
+                self.paragraph_break();
+                match name {
+                    "blockquote" => self.quote += 1,
+                    "pre" => self.pre += 1,
+                    _ => {}
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.paragraph_break();
+                let level = name[1..].parse().unwrap_or(1);
+                self.heading = Some(level);
+                let marker = format!("{} ", "#".repeat(level));
+                self.push(Span::styled(marker, self.style()));
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.paragraph_break();
+                } else {
+                    self.end_line();
+                }
+                let start = token
+                    .attribute("start")
+                    .and_then(|start| start.parse().ok())
+                    .unwrap_or(1);
+                self.lists.push((name == "ol").then_some(start));
+            }
+            "li" => {
+                self.end_line();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.begin_line(self.lists.len().saturating_sub(1));
+                self.current
+                    .push(Span::styled(marker, Style::default().fg(Color::Yellow)));
+                self.last_was_space = true;
+            }
+            "div" | "section" | "article" | "header" | "footer" | "tr" | "dt" | "dd"
+            | "figcaption" => self.end_line(),
+            "br" => {
+                if !self.line_started {
+                    self.begin_line(self.lists.len());
+                }
+                self.end_line();
+            }
+            "hr" => {
+                self.paragraph_break();
+                self.lines.push(Line::styled(
+                    "─".repeat(40),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                self.lines.push(Line::default());
+            }
+            "td" | "th" if self.line_started => self.push(Span::raw("  ")),
+            "b" | "strong" => self.bold += 1,
+            "i" | "em" | "cite" => self.italic += 1,
+            "code" | "kbd" | "samp" => self.code += 1,
+            "a" => self
+                .open_links
+                .push(token.attribute("href").map(str::to_string)),
+            "img" => {
+                let alt = token.attribute("alt").unwrap_or_default().trim();
+                let text = match alt.is_empty() {
+                    true => "[image]".to_string(),
+                    false => format!("[image: {}]", alt),
+                };
+                self.push(Span::styled(
+                    text,
+                    Style::default()
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::ITALIC),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "p" | "table" | "figure" => self.paragraph_break(),
+            "blockquote" => {
+                self.paragraph_break();
+                self.quote = self.quote.saturating_sub(1);
+            }
+            "pre" => {
+                self.paragraph_break();
+                self.pre = self.pre.saturating_sub(1);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.heading = None;
+                self.paragraph_break();
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                match self.lists.is_empty() {
+                    true => self.paragraph_break(),
+                    false => self.end_line(),
+                }
+            }
+            "li" | "div" | "section" | "article" | "header" | "footer" | "tr" | "dt" | "dd"
+            | "figcaption" => self.end_line(),
+            "b" | "strong" => self.bold = self.bold.saturating_sub(1),
+            "i" | "em" | "cite" => self.italic = self.italic.saturating_sub(1),
+            "code" | "kbd" | "samp" => self.code = self.code.saturating_sub(1),
+            "a" => {
+                let Some(Some(href)) = self.open_links.pop() else {
+                    return;
+                };
+                let href = href.trim();
+                if href.is_empty() || href.starts_with('#') {
+                    return;
+                }
+
+                let number = match self.links.iter().position(|link| link == href) {
+                    Some(idx) => idx + 1,
+                    None => {
+                        self.links.push(href.to_string());
+                        self.links.len()
+                    }
+                };
+                self.push(Span::styled(
+                    format!("[{}]", number),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.pre > 0 {
+            for (idx, segment) in text.split('\n').enumerate() {
+                if idx > 0 {
+                    if !self.line_started {
+                        self.begin_line(self.lists.len());
+                    }
+                    self.end_line();
+                }
+                if !segment.is_empty() {
+                    self.push(Span::styled(segment.replace('\t', "    "), self.style()));
+                }
+            }
+            return;
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_whitespace() && c != '\u{a0}' {
+                if !self.last_was_space && self.line_started {
+                    collapsed.push(' ');
+                }
+                self.last_was_space = true;
+            } else {
+                collapsed.push(c);
+                self.last_was_space = false;
+                // Leading whitespace is only dropped until the line has text
+                self.line_started = true;
+            }
+        }
+
+        if !collapsed.is_empty() {
+            self.push(Span::styled(collapsed, self.style()));
+        }
+    }
+
+    /// Append to the current line, starting one if needed
+    fn push(&mut self, span: Span<'static>) {
+        if self.current.is_empty() {
+            self.begin_line(self.lists.len());
+        }
+        self.line_started = true;
+        self.current.push(span);
+    }
+
+    /// Quote bars and list indentation for a new line
+    fn begin_line(&mut self, indent: usize) {
+        if self.quote > 0 {
+            self.current.push(Span::styled(
+                "│ ".repeat(self.quote),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if indent > 0 {
+            self.current.push(Span::raw("  ".repeat(indent)));
+        }
+        self.line_started = true;
+    }
+
+    fn end_line(&mut self) {
+        if self.line_started || !self.current.is_empty() {
+            self.lines
+                .push(Line::from(std::mem::take(&mut self.current)));
+        }
+        self.line_started = false;
+        self.last_was_space = false;
+    }
+
+    /// End the line and leave a single blank line before whatever follows
+    fn paragraph_break(&mut self) {
+        self.end_line();
+        if self.lines.last().is_some_and(|line| !is_blank(line)) {
+            self.lines.push(Line::default());
+        }
+    }
+
+    fn style(&self) -> Style {
+        let mut style = Style::default();
+        if let Some(level) = self.heading {
+            style = style.fg(Color::Magenta).add_modifier(Modifier::BOLD);
+            if level == 1 {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+        }
+        if self.bold > 0 {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if self.italic > 0 || self.quote > 0 {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        if self.code > 0 || self.pre > 0 {
+            style = style.fg(Color::Green);
+        }
+        if self.open_links.iter().any(Option::is_some) {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        style
+    }
+
+    fn finish(mut self) -> Vec<Line<'static>> {
+        self.end_line();
+        while self.lines.last().is_some_and(is_blank) {
+            self.lines.pop();
+        }
+
+        if !self.links.is_empty() {
+            self.lines.push(Line::default());
+            self.lines.push(Line::styled(
+                "Links",
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            ));
+            for (idx, link) in self.links.into_iter().enumerate() {
+                self.lines.push(Line::from(vec![
+                    Span::styled(format!("[{}] ", idx + 1), Style::default().fg(Color::Cyan)),
+                    Span::raw(link),
+                ]));
+            }
+        }
+
+        self.lines
+    }
+}
+
+fn is_blank(line: &Line) -> bool {
+    line.spans.iter().all(|span| span.content.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blocks_and_links() {
+        let lines = html_to_lines(
+            "<h2>Title</h2>\n<p>Some <em>very</em>\n  <a href=\"https://a.example\">linked</a> text.</p>\
+             <ul><li>one</li><li>two <a href=\"https://b.example\">b</a></li></ul>\
+             <ol start=\"3\"><li>three</li></ol>\
+             <blockquote><p>quoted</p></blockquote>\
+             <pre>fn main() {\n    x();\n}</pre>\
+             <p>Again <a href=\"https://a.example\">a</a></p>",
+        );
+        assert_eq!(
+            plain(&lines),
+            vec![
+                "## Title",
+                "",
+                "Some very linked[1] text.",
+                "",
+                "• one",
+                "• two b[2]",
+                "",
+                "3. three",
+                "",
+                "│ quoted",
+                "",
+                "fn main() {",
+                "    x();",
+                "}",
+                "",
+                "Again a[1]",
+                "",
+                "Links",
+                "[1] https://a.example",
+                "[2] https://b.example",
+            ]
+        );
+        assert!(lines[2].spans[1]
+            .style
+            .add_modifier
+            .contains(Modifier::ITALIC));
+    }
+
+    #[test]
+    fn plain_text_description() {
+        let lines = html_to_lines("Just a summary &amp; nothing else.");
+        assert_eq!(plain(&lines), vec!["Just a summary & nothing else."]);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{cell::Cell, ops::Range};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget},
+};
+
+/// The part of a list that is on screen. The list's height is only known
+/// while drawing, so both are updated from there.
+#[derive(Default)]
+pub struct ListViewport {
+    offset: Cell<usize>,
+    height: Cell<usize>,
+}
+
+impl ListViewport {
+    /// Indices of the rows to draw. Scrolls only as far as needed to keep
+    /// `selected` on screen.
+    pub fn visible_range(
+        &self,
+        selected: Option<usize>,
+        len: usize,
+        height: usize,
+    ) -> Range<usize> {
+        let mut offset = self.offset.get().min(len.saturating_sub(height));
+        if let Some(selected) = selected {
+            if selected < offset {
+                offset = selected;
+            } else if height > 0 && selected >= offset + height {
+                offset = selected + 1 - height;
+            }
+        }
+
+        self.offset.set(offset);
+        self.height.set(height);
+        offset..(offset + height).min(len)
+    }
+
+    /// How far page up/down moves the selection, keeping one row of context
+    pub fn page_size(&self) -> usize {
+        self.height.get().saturating_sub(1).max(1)
+    }
+
+    pub fn reset(&self) {
+        self.offset.set(0);
+    }
+}
+
+/// Where the selection of a list goes once the list was replaced: to `found`,
+/// the entry it was on, or else the same position within the new length
+pub fn reselect(selected: Option<usize>, found: Option<usize>, len: usize) -> Option<usize> {
+    match found {
+        Some(idx) => Some(idx),
+        None if len == 0 => None,
+        None => Some(selected.unwrap_or(0).min(len - 1)),
+    }
+}
+
+/// Scrollbar along the right edge of `area`, only when the list overflows
+pub fn render_scrollbar(area: Rect, buf: &mut Buffer, offset: usize, len: usize, height: usize) {
+    if len <= height || area.is_empty() {
+        return;
+    }
+
+    let mut state = ScrollbarState::new(len - height + 1)
+        .position(offset)
+        .viewport_content_length(height);
+    Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .track_style(Style::default().fg(Color::DarkGray))
+        .render(area, buf, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_stays_visible() {
+        let viewport = ListViewport::default();
+        assert_eq!(viewport.visible_range(Some(3), 300, 10), 0..10);
+        // Moving past the bottom scrolls by as little as possible
+        assert_eq!(viewport.visible_range(Some(10), 300, 10), 1..11);
+        assert_eq!(viewport.visible_range(Some(299), 300, 10), 290..300);
+        // Moving back up inside the window doesn't scroll
+        assert_eq!(viewport.visible_range(Some(295), 300, 10), 290..300);
+        assert_eq!(viewport.visible_range(Some(0), 300, 10), 0..10);
+        // The list shrank underneath the viewport
+        viewport.visible_range(Some(299), 300, 10);
+        assert_eq!(viewport.visible_range(None, 5, 10), 0..5);
+        assert_eq!(viewport.page_size(), 9);
+    }
+
+    #[test]
+    fn selection_follows_its_entry() {
+        assert_eq!(reselect(Some(4), Some(2), 6), Some(2));
+        // Gone, the next entry moves up into its place
+        assert_eq!(reselect(Some(4), None, 6), Some(4));
+        assert_eq!(reselect(Some(4), None, 3), Some(2));
+        assert_eq!(reselect(Some(4), None, 0), None);
+        assert_eq!(reselect(None, None, 3), Some(0));
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use crate::persistence::RssChannelD;
+
+/// The `/` search, which narrows the articles pane down to the matches
+#[derive(Debug, Default)]
+pub struct SearchState {
+    pub query: String,
+    /// Keys go to the query rather than to the panes
+    pub editing: bool,
+    /// Ids of the matching articles, best first, once the backend answered
+    pub matches: Option<Vec<i64>>,
+}
+
+/// Positions in `channel.articles` of the articles to show, in the order to
+/// show them
+pub fn shown_articles(search: Option<&SearchState>, channel: &RssChannelD) -> Vec<usize> {
+    let Some(matches) = search.and_then(|search| search.matches.as_ref()) else {
+        return (0..channel.articles.len()).collect();
+    };
+
+    let positions = channel
+        .articles
+        .iter()
+        .enumerate()
+        .map(|(idx, article)| (article.id, idx))
+        .collect::<HashMap<_, _>>();
+    matches
+        .iter()
+        .filter_map(|id| positions.get(id).copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::channels;
+
+    #[test]
+    fn matches_are_shown_best_first() {
+        let mut channel = channels::channel_tree(&[], &[], &Default::default()).remove(0);
+        for id in 1..=4 {
+            let mut article = crate::persistence::RssArticleD::dummy();
+            article.id = id;
+            channel.articles.push(article);
+        }
+
+        assert_eq!(shown_articles(None, &channel), vec![0, 1, 2, 3]);
+
+        let mut search = SearchState {
+            query: "rust".to_string(),
+            editing: true,
+            matches: None,
+        };
+        // Still waiting for the backend
+        assert_eq!(shown_articles(Some(&search), &channel), vec![0, 1, 2, 3]);
+
+        // Matches from other channels are left out
+        search.matches = Some(vec![3, 42, 1]);
+        assert_eq!(shown_articles(Some(&search), &channel), vec![2, 0]);
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget},
+};
+
+use super::AppState;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// How long a finished task's message stays up
+const DONE_TIMEOUT: Duration = Duration::from_secs(4);
+const ERROR_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusKind {
+    InProgress,
+    Done,
+    Error,
+}
+
+/// The message in the status line. Work in progress stays up until the
+/// backend reports how it went, everything else expires.
+pub struct Status {
+    pub kind: StatusKind,
+    pub message: String,
+    expires: Option<Instant>,
+}
+
+impl Status {
+    pub fn new(kind: StatusKind, message: String) -> Self {
+        let expires = match kind {
+            StatusKind::InProgress => None,
+            StatusKind::Done => Some(Instant::now() + DONE_TIMEOUT),
+            StatusKind::Error => Some(Instant::now() + ERROR_TIMEOUT),
+        };
+
+        Status {
+            kind,
+            message,
+            expires,
+        }
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+pub struct StatusWidget<'a> {
+    state: &'a AppState,
+}
+
+impl<'a> StatusWidget<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl<'a> Widget for StatusWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let Some(status) = &self.state.status else {
+            return;
+        };
+
+        let (marker, style) = match status.kind {
+            StatusKind::InProgress => (
+                SPINNER[self.state.ticks % SPINNER.len()],
+                Style::default().fg(Color::Yellow),
+            ),
+            StatusKind::Done => ("✓", Style::default().fg(Color::Green)),
+            StatusKind::Error => (
+                "✗",
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+        };
+
+        Paragraph::new(Line::from(vec![
+            Span::styled(format!(" {} ", marker), style),
+            Span::styled(status.message.clone(), style),
+        ]))
+        .render(area, buf);
+    }
+}